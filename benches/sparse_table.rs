@@ -0,0 +1,66 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use kuehree::rmq::{Layout, Min, SparseTable};
+
+/// Deterministic xorshift so runs are comparable
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Uniformly random ranges
+fn random(state: &mut u64, len: usize) -> (usize, usize) {
+    let a = xorshift(state) as usize % len;
+    let b = xorshift(state) as usize % len;
+    (a.min(b), a.max(b))
+}
+
+/// Ranges of random length starting near a random anchor, like window
+/// queries around a point of interest
+fn clustered(state: &mut u64, len: usize) -> (usize, usize) {
+    let anchor = (xorshift(state) as usize % (len / 1024)) * 1024;
+    let start = anchor + xorshift(state) as usize % 16;
+    let end = start + xorshift(state) as usize % (len - start);
+    (start, end)
+}
+
+fn queries(c: &mut Criterion) {
+    for (name, gen) in [
+        ("random", random as fn(&mut u64, usize) -> (usize, usize)),
+        ("clustered", clustered),
+    ] {
+        bench_layouts(c, name, gen);
+    }
+}
+
+fn bench_layouts(c: &mut Criterion, name: &str, gen: fn(&mut u64, usize) -> (usize, usize)) {
+    let mut group = c.benchmark_group(format!("sparse_table_{name}_queries"));
+
+    for len in [1 << 16, 1 << 22] {
+        let mut state = 0x2545_f491_4f6c_dd1d;
+        let data: Vec<u64> = (0..len).map(|_| xorshift(&mut state)).collect();
+        let queries: Vec<(usize, usize)> = (0..4096).map(|_| gen(&mut state, len)).collect();
+
+        for layout in [Layout::Levels, Layout::Blocked] {
+            let table = SparseTable::<_, Min>::with_layout(data.iter().copied(), layout);
+            group.bench_with_input(
+                BenchmarkId::new(format!("{layout:?}"), len),
+                &queries,
+                |b, queries| {
+                    b.iter(|| {
+                        queries
+                            .iter()
+                            .map(|&(start, end)| table.query(start, end))
+                            .fold(0, |acc: u64, x| acc.wrapping_add(black_box(x)))
+                    });
+                },
+            );
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, queries);
+criterion_main!(benches);
@@ -0,0 +1,415 @@
+//! Range sums behind one API, backed by the structure that suits the
+//! workload best
+
+use core::ops::{Add, Sub};
+
+use alloc::vec::Vec;
+
+use crate::{
+    fenwick::Fenwick,
+    rmq::{SegmentTree, Sum},
+    trace::BuildSpan,
+    IndexableSumQuery, MemoryUsage, RangeQuery, SumQuery, UpdatableRangeQuery, Zero,
+};
+
+/// Elements split in blocks of about `sqrt(n)` elements with their totals,
+/// O(1) updates and O(sqrt(n)) queries
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SqrtDecomposition<T> {
+    block: usize,
+    data: Vec<T>,
+    totals: Vec<T>,
+}
+
+impl<T> SqrtDecomposition<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("SqrtDecomposition");
+        let data: Vec<T> = data.into_iter().collect();
+        span.record_len(data.len());
+        let block = data.len().isqrt().max(1);
+        let totals = data
+            .chunks(block)
+            .map(|chunk| chunk.iter().fold(T::zero(), |acc, &value| acc + value))
+            .collect();
+
+        Self {
+            block,
+            data,
+            totals,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(sqrt(n))
+    pub fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len());
+        let sum = |range: &[T]| range.iter().fold(T::zero(), |acc, &value| acc + value);
+        let (first, last) = (start / self.block, end / self.block);
+        if first == last {
+            return sum(&self.data[start..=end]);
+        }
+        sum(&self.data[start..(first + 1) * self.block])
+            + sum(&self.totals[first + 1..last])
+            + sum(&self.data[last * self.block..=end])
+    }
+
+    /// Current element at `index`
+    pub fn get(&self, index: usize) -> T {
+        self.data[index]
+    }
+
+    /// Replace the element at `index` with `value`
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn update(&mut self, index: usize, value: T) {
+        let total = &mut self.totals[index / self.block];
+        *total = *total - self.data[index] + value;
+        self.data[index] = value;
+    }
+}
+
+impl<T> RangeQuery<T> for SqrtDecomposition<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> UpdatableRangeQuery<T> for SqrtDecomposition<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+}
+
+impl<T> MemoryUsage for SqrtDecomposition<T> {
+    fn heap_bytes(&self) -> usize {
+        self.data.heap_bytes() + self.totals.heap_bytes()
+    }
+}
+
+/// Expected workload of an [`AdaptiveRangeQuery`]
+///
+/// `reads` and `writes` only matter relative to each other, e.g. 9 and 1
+/// for nine queries per update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WorkloadHints {
+    reads: u32,
+    writes: u32,
+    clustered: bool,
+}
+
+impl Default for WorkloadHints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WorkloadHints {
+    /// As many reads as writes, spread over the whole data
+    pub fn new() -> Self {
+        Self {
+            reads: 1,
+            writes: 1,
+            clustered: false,
+        }
+    }
+
+    /// Relative number of range queries
+    pub fn reads(mut self, reads: u32) -> Self {
+        self.reads = reads;
+        self
+    }
+
+    /// Relative number of updates, 0 for read only data
+    pub fn writes(mut self, writes: u32) -> Self {
+        self.writes = writes;
+        self
+    }
+
+    /// Whether updates come in batches of nearby indices
+    pub fn clustered(mut self, clustered: bool) -> Self {
+        self.clustered = clustered;
+        self
+    }
+
+    /// Structure suiting this workload over `len` elements
+    ///
+    /// Read only data gets O(1) prefix sums. Clustered batches go to a
+    /// segment tree, whose batch update recomputes the shared ancestors
+    /// once. Writes outnumbering reads by more than `log2(n)` favour the
+    /// O(1) updates of a square root decomposition, anything else a
+    /// Fenwick tree.
+    pub fn backend(&self, len: usize) -> Backend {
+        let log = u64::from(len.max(2).ilog2());
+        if self.writes == 0 {
+            Backend::PrefixSums
+        } else if self.clustered {
+            Backend::SegmentTree
+        } else if u64::from(self.writes) > u64::from(self.reads) * log {
+            Backend::SqrtDecomposition
+        } else {
+            Backend::Fenwick
+        }
+    }
+}
+
+/// Structure backing an [`AdaptiveRangeQuery`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// [`SumQuery`], O(1) queries and O(n) updates
+    PrefixSums,
+    /// [`SqrtDecomposition`], O(sqrt(n)) queries and O(1) updates
+    SqrtDecomposition,
+    /// [`Fenwick`], O(log n) queries and updates
+    Fenwick,
+    /// [`SegmentTree`] with [`Sum`], O(log n) queries and updates and
+    /// cheap batches of nearby updates
+    SegmentTree,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Inner<T> {
+    PrefixSums(SumQuery<Vec<T>>),
+    SqrtDecomposition(SqrtDecomposition<T>),
+    Fenwick(Fenwick<T>),
+    SegmentTree(SegmentTree<T, Sum>),
+}
+
+/// Range sums over a structure picked from [`WorkloadHints`]
+///
+/// ```
+/// use kuehree::adaptive::{AdaptiveRangeQuery, Backend, WorkloadHints};
+/// use kuehree::RangeQuery;
+///
+/// let hints = WorkloadHints::new().reads(100).writes(1);
+/// let sums = AdaptiveRangeQuery::new([1, 3, 4, 8], hints);
+/// assert_eq!(sums.backend(), Backend::Fenwick);
+/// assert_eq!(sums.query(1, 2), 7);
+/// ```
+///
+/// Prefix sums can't be updated efficiently, updating data hinted to be
+/// read only moves it to a Fenwick tree first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AdaptiveRangeQuery<T> {
+    inner: Inner<T>,
+}
+
+impl<T> AdaptiveRangeQuery<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    /// Construct `Self` over the backend [`WorkloadHints::backend`] picks
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>, hints: WorkloadHints) -> Self {
+        let data: Vec<T> = data.into_iter().collect();
+        let backend = hints.backend(data.len());
+        Self::with_backend(data, backend)
+    }
+
+    /// Construct `Self` over a chosen backend
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn with_backend(data: impl IntoIterator<Item = T>, backend: Backend) -> Self {
+        let inner = match backend {
+            Backend::PrefixSums => Inner::PrefixSums(SumQuery::new(data)),
+            Backend::SqrtDecomposition => Inner::SqrtDecomposition(SqrtDecomposition::new(data)),
+            Backend::Fenwick => Inner::Fenwick(Fenwick::new(data)),
+            Backend::SegmentTree => Inner::SegmentTree(SegmentTree::new(data)),
+        };
+
+        Self { inner }
+    }
+
+    /// Backend in use
+    pub fn backend(&self) -> Backend {
+        match self.inner {
+            Inner::PrefixSums(_) => Backend::PrefixSums,
+            Inner::SqrtDecomposition(_) => Backend::SqrtDecomposition,
+            Inner::Fenwick(_) => Backend::Fenwick,
+            Inner::SegmentTree(_) => Backend::SegmentTree,
+        }
+    }
+
+    fn as_dyn(&self) -> &dyn RangeQuery<T> {
+        match &self.inner {
+            Inner::PrefixSums(inner) => inner,
+            Inner::SqrtDecomposition(inner) => inner,
+            Inner::Fenwick(inner) => inner,
+            Inner::SegmentTree(inner) => inner,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_dyn().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Query between start range and end range
+    pub fn query(&self, start: usize, end: usize) -> T {
+        self.as_dyn().query(start, end)
+    }
+
+    /// Current element at `index`
+    pub fn get(&self, index: usize) -> T {
+        match &self.inner {
+            Inner::PrefixSums(inner) => inner.query(index, index),
+            Inner::SqrtDecomposition(inner) => inner.get(index),
+            Inner::Fenwick(inner) => inner.get(index),
+            Inner::SegmentTree(inner) => inner.get(index),
+        }
+    }
+
+    /// Switch to `backend`, keeping the elements
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn set_backend(&mut self, backend: Backend) {
+        if backend != self.backend() {
+            let data: Vec<T> = (0..self.len()).map(|idx| self.get(idx)).collect();
+            *self = Self::with_backend(data, backend);
+        }
+    }
+
+    /// Replace the element at `index` with `value`
+    pub fn update(&mut self, index: usize, value: T) {
+        if let Inner::PrefixSums(inner) = &self.inner {
+            self.inner = Inner::Fenwick(Fenwick::new(inner.iter()));
+        }
+        match &mut self.inner {
+            Inner::PrefixSums(_) => unreachable!("moved to a Fenwick tree"),
+            Inner::SqrtDecomposition(inner) => inner.update(index, value),
+            Inner::Fenwick(inner) => inner.update(index, value),
+            Inner::SegmentTree(inner) => inner.update(index, value),
+        }
+    }
+}
+
+impl<T> RangeQuery<T> for AdaptiveRangeQuery<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> UpdatableRangeQuery<T> for AdaptiveRangeQuery<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+
+    fn update_batch(&mut self, updates: impl IntoIterator<Item = (usize, T)>) {
+        match &mut self.inner {
+            Inner::SegmentTree(inner) => inner.update_batch(updates),
+            _ => {
+                for (index, value) in updates {
+                    self.update(index, value);
+                }
+            }
+        }
+    }
+}
+
+impl<T> MemoryUsage for AdaptiveRangeQuery<T> {
+    fn heap_bytes(&self) -> usize {
+        match &self.inner {
+            Inner::PrefixSums(inner) => inner.heap_bytes(),
+            Inner::SqrtDecomposition(inner) => inner.heap_bytes(),
+            Inner::Fenwick(inner) => inner.heap_bytes(),
+            Inner::SegmentTree(inner) => inner.heap_bytes(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backend() {
+        let hints = WorkloadHints::new();
+        assert_eq!(hints.writes(0).backend(1000), Backend::PrefixSums);
+        assert_eq!(hints.backend(1000), Backend::Fenwick);
+        assert_eq!(hints.writes(100).backend(1000), Backend::SqrtDecomposition);
+        assert_eq!(
+            hints.writes(100).clustered(true).backend(1000),
+            Backend::SegmentTree
+        );
+    }
+
+    #[test]
+    fn test_adaptive() {
+        let mut arr = [1u32, 3, 4, 8, 6, 1, 4, 2, 9, 0];
+        let backends = [
+            Backend::PrefixSums,
+            Backend::SqrtDecomposition,
+            Backend::Fenwick,
+            Backend::SegmentTree,
+        ];
+        let mut sums: Vec<_> = backends
+            .iter()
+            .map(|&backend| AdaptiveRangeQuery::with_backend(arr, backend))
+            .collect();
+
+        for sum in &mut sums {
+            sum.update_batch([(3, 2), (9, 7)]);
+        }
+        arr[3] = 2;
+        arr[9] = 7;
+        assert_eq!(sums[0].backend(), Backend::Fenwick);
+        for sum in &sums {
+            for start in 0..arr.len() {
+                for end in start..arr.len() {
+                    assert_eq!(sum.query(start, end), arr[start..=end].iter().sum::<u32>());
+                }
+            }
+        }
+
+        let mut sum = AdaptiveRangeQuery::new(arr, WorkloadHints::new().writes(0));
+        sum.set_backend(Backend::SqrtDecomposition);
+        assert_eq!(sum.backend(), Backend::SqrtDecomposition);
+        assert_eq!(sum.query(0, 9), arr.iter().sum::<u32>());
+    }
+}
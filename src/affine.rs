@@ -0,0 +1,184 @@
+//! Affine maps `x -> a * x + b`, composed over ranges or applied to them
+//!
+//! [`Compose`] folds a range of maps into the map applying them in order,
+//! for a segment tree over maps, and [`Affine`] is an [`Action`] on sums,
+//! for a lazy segment tree with range affine updates and range sums.
+//! [`RangeAffineSum`] is that tree over [`Modular`] elements:
+//!
+//! ```
+//! use kuehree::affine::RangeAffineSum;
+//!
+//! let mut tree = RangeAffineSum::<1_000_000_007>::from_values([1, 2, 3]);
+//! tree.affine(0, 1, 2, 1);
+//! assert_eq!(tree.sum(0, 2), 3 + 5 + 3);
+//! ```
+
+use core::ops::{Add, Mul};
+
+#[cfg(feature = "alloc")]
+use crate::{
+    lazy::{Action, LazySegmentTree},
+    rmq::Sum,
+    Modular,
+};
+use crate::{rmq::Operation, One, Zero};
+
+/// Affine map `x -> a * x + b`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Affine<T> {
+    pub a: T,
+    pub b: T,
+}
+
+impl<T> Affine<T> {
+    pub fn new(a: T, b: T) -> Self {
+        Self { a, b }
+    }
+}
+
+impl<T> Affine<T>
+where
+    T: Copy + Add<Output = T> + Mul<Output = T>,
+{
+    /// Value of the map at `x`
+    pub fn eval(self, x: T) -> T {
+        self.a * x + self.b
+    }
+
+    /// Map applying `self` first and then `next`
+    pub fn then(self, next: Self) -> Self {
+        Self {
+            a: next.a * self.a,
+            b: next.a * self.b + next.b,
+        }
+    }
+}
+
+impl<T: Zero + One> Affine<T> {
+    /// Map leaving every value unchanged
+    pub fn identity() -> Self {
+        Self {
+            a: T::one(),
+            b: T::zero(),
+        }
+    }
+}
+
+/// `times` copies of `value` added together, in O(log times) additions
+#[cfg(feature = "alloc")]
+fn repeat_add<T: Copy + Add<Output = T> + Zero>(value: T, mut times: usize) -> T {
+    let (mut acc, mut power) = (T::zero(), value);
+    while times > 0 {
+        if times & 1 == 1 {
+            acc = acc + power;
+        }
+        power = power + power;
+        times >>= 1;
+    }
+    acc
+}
+
+/// Applying a map to every element of a sum scales it by `a` and adds `b`
+/// once per element
+#[cfg(feature = "alloc")]
+impl<T> Action<T> for Affine<T>
+where
+    T: Copy + Add<Output = T> + Mul<Output = T> + Zero,
+{
+    fn apply(self, value: T, len: usize) -> T {
+        self.a * value + repeat_add(self.b, len)
+    }
+
+    fn compose(self, inner: Self) -> Self {
+        inner.then(self)
+    }
+}
+
+/// Range `x -> a * x + b` updates with range sums, modulo `P`
+#[cfg(feature = "alloc")]
+pub type RangeAffineSum<const P: u64> = LazySegmentTree<Modular<P>, Sum, Affine<Modular<P>>>;
+
+#[cfg(feature = "alloc")]
+impl<const P: u64> RangeAffineSum<P> {
+    /// Construct `Self` from `data` reduced modulo `P`
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn from_values(data: impl IntoIterator<Item = u64>) -> Self {
+        Self::new(data.into_iter().map(Modular::new))
+    }
+
+    /// Replace every element `x` between `start` and `end` with
+    /// `a * x + b` modulo `P`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn affine(&mut self, start: usize, end: usize, a: u64, b: u64) {
+        self.apply(start, end, Affine::new(Modular::new(a), Modular::new(b)));
+    }
+
+    /// Sum between `start` and `end` modulo `P`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn sum(&self, start: usize, end: usize) -> u64 {
+        self.query(start, end).value()
+    }
+}
+
+/// Composition of affine maps, folding a range into the map applying them
+/// from left to right
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Compose;
+
+impl<T> Operation<Affine<T>> for Compose
+where
+    T: Copy + Add<Output = T> + Mul<Output = T>,
+{
+    fn combine(a: Affine<T>, b: Affine<T>) -> Affine<T> {
+        a.then(b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{rmq::SegmentTreeFixed, Modular};
+
+    #[test]
+    fn test_compose() {
+        type Mod = Modular<1_000_000_007>;
+
+        let maps: [Affine<Mod>; 6] = core::array::from_fn(|i| {
+            Affine::new(Mod::new(i as u64 * 31 + 2), Mod::new(i as u64 * 7 + 5))
+        });
+        let tree = SegmentTreeFixed::<_, Compose, 6>::new(maps);
+        for start in 0..maps.len() {
+            for end in start..maps.len() {
+                let x = Mod::new(123_456_789);
+                let expected = maps[start..=end].iter().fold(x, |x, map| map.eval(x));
+                assert_eq!(tree.query(start, end).eval(x), expected);
+            }
+        }
+        assert_eq!(Affine::identity().then(maps[2]), maps[2]);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_range_affine_sum() {
+        const P: u64 = 1_000_003;
+        let mut arr: Vec<u64> = (0..23).map(|i| i * 99_991 % P).collect();
+        let mut tree = RangeAffineSum::<P>::from_values(arr.iter().copied());
+        for step in 0..40u64 {
+            let (start, end) = ((step * 5 % 23) as usize, (12 + step % 11) as usize);
+            let (start, end) = (start.min(end), start.max(end));
+            let (a, b) = (step * 7_777 + 3, P + step * 13);
+            for x in &mut arr[start..=end] {
+                *x = (a % P * *x + b) % P;
+            }
+            tree.affine(start, end, a, b);
+            assert_eq!(tree.sum(0, 22), arr.iter().sum::<u64>() % P);
+            assert_eq!(
+                tree.sum(start, end),
+                arr[start..=end].iter().sum::<u64>() % P
+            );
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Folds of the ranges anchored at either end of the data
+
+use core::marker::PhantomData;
+
+use alloc::vec::Vec;
+
+use crate::{
+    rmq::{Max, Min, Operation},
+    trace::BuildSpan,
+    MemoryUsage,
+};
+
+/// Running folds from the front and from the back, answering ranges that
+/// start at the first or end at the last element in O(1)
+///
+/// Needs `2n` elements of memory and no idempotence, against `n log n`
+/// for a [`SparseTable`](crate::rmq::SparseTable) answering any range.
+///
+/// ```
+/// use kuehree::anchored::PrefixMax;
+///
+/// let highs = PrefixMax::new([3, 1, 4, 1, 5, 9, 2, 6]);
+/// assert_eq!(highs.prefix(3), 4);
+/// assert_eq!(highs.suffix(6), 6);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnchoredFold<T, M> {
+    /// `prefixes[i]` folds `[0, i]`
+    prefixes: Vec<T>,
+    /// `suffixes[i]` folds `[i, n)`
+    suffixes: Vec<T>,
+    _phantom: PhantomData<M>,
+}
+
+/// Maximum of every prefix and suffix
+pub type PrefixMax<T> = AnchoredFold<T, Max>;
+
+/// Minimum of every prefix and suffix
+pub type PrefixMin<T> = AnchoredFold<T, Min>;
+
+/// Fold of every prefix of `values`
+fn running<T: Copy, M: Operation<T>>(values: impl Iterator<Item = T>) -> Vec<T> {
+    let mut total = None;
+    values
+        .map(|value| {
+            let folded = total.map_or(value, |total| M::combine(total, value));
+            total = Some(folded);
+            folded
+        })
+        .collect()
+}
+
+impl<T, M> AnchoredFold<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("AnchoredFold");
+        let data: Vec<T> = data.into_iter().collect();
+        span.record_len(data.len());
+        let mut suffixes = running::<T, M>(data.iter().rev().copied());
+        suffixes.reverse();
+        Self {
+            prefixes: running::<T, M>(data.into_iter()),
+            suffixes,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.prefixes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// Fold of the elements from the first to `end`, inclusive
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn prefix(&self, end: usize) -> T {
+        self.prefixes[end]
+    }
+
+    /// Fold of the elements from `start` to the last
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn suffix(&self, start: usize) -> T {
+        self.suffixes[start]
+    }
+}
+
+impl<T: Copy, M: Operation<T>> FromIterator<T> for AnchoredFold<T, M> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+impl<T, M> MemoryUsage for AnchoredFold<T, M> {
+    fn heap_bytes(&self) -> usize {
+        self.prefixes.heap_bytes() + self.suffixes.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmq::Sum;
+
+    #[test]
+    fn test_anchored() {
+        let data = [5i32, -2, 7, 7, 0, -9, 3];
+        let highs: PrefixMax<_> = data.into_iter().collect();
+        let lows = PrefixMin::new(data);
+        let sums = AnchoredFold::<_, Sum>::new(data);
+        for idx in 0..data.len() {
+            assert_eq!(Some(highs.prefix(idx)), data[..=idx].iter().copied().max());
+            assert_eq!(Some(highs.suffix(idx)), data[idx..].iter().copied().max());
+            assert_eq!(Some(lows.prefix(idx)), data[..=idx].iter().copied().min());
+            assert_eq!(Some(lows.suffix(idx)), data[idx..].iter().copied().min());
+            assert_eq!(sums.suffix(idx), data[idx..].iter().sum::<i32>());
+        }
+        assert!(PrefixMax::<u8>::new([]).is_empty());
+    }
+}
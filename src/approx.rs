@@ -0,0 +1,144 @@
+//! Approximate range sums over data too large to preprocess exactly
+
+use crate::trace::BuildSpan;
+
+/// Normal quantile of a two sided 95% confidence interval
+const Z_95: f64 = 1.96;
+
+/// Range sums estimated from a stratified sample
+///
+/// The data is cut in strata of `stratum` elements and `samples` random
+/// elements of every stratum are read, so building touches only a
+/// `samples / stratum` fraction of the data. Strata fully inside a queried
+/// range are estimated from their samples, the at most two strata the
+/// range only partially covers are summed exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApproxSum<'a, T> {
+    data: &'a [T],
+    stratum: usize,
+    /// Estimated totals of the strata, as prefix sums with a leading zero
+    estimates: Vec<f64>,
+    /// Variances of those estimates, as prefix sums with a leading zero
+    variances: Vec<f64>,
+}
+
+/// Xorshift generator, good enough to pick sample positions
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+impl<'a, T> ApproxSum<'a, T>
+where
+    T: Copy + Into<f64>,
+{
+    /// Sample `samples` elements, drawn with replacement, from every
+    /// stratum of `stratum` elements
+    ///
+    /// Algorithmic complexity: O(n * samples / stratum)
+    ///
+    /// # Panics
+    ///
+    /// If `stratum` or `samples` is 0
+    pub fn new(data: &'a [T], stratum: usize, samples: usize) -> Self {
+        assert!(
+            stratum > 0 && samples > 0,
+            "strata and samples can't be empty"
+        );
+        let span = BuildSpan::enter("ApproxSum");
+        span.record_len(data.len());
+
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        let mut estimates = vec![0.0];
+        let mut variances = vec![0.0];
+        for chunk in data.chunks(stratum) {
+            let (mut sum, mut squares) = (0.0, 0.0);
+            for _ in 0..samples {
+                let value: f64 = chunk[rng.below(chunk.len())].into();
+                sum += value;
+                squares += value * value;
+            }
+            let (len, samples) = (chunk.len() as f64, samples as f64);
+            let mean = sum / samples;
+            let sample_variance = if samples > 1.0 {
+                (squares - sum * mean).max(0.0) / (samples - 1.0)
+            } else {
+                0.0
+            };
+            estimates.push(estimates[estimates.len() - 1] + len * mean);
+            variances.push(variances[variances.len() - 1] + len * len * sample_variance / samples);
+        }
+
+        Self {
+            data,
+            stratum,
+            estimates,
+            variances,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn exact(&self, start: usize, end: usize) -> f64 {
+        self.data[start..end]
+            .iter()
+            .map(|&value| value.into())
+            .sum()
+    }
+
+    /// Estimated sum between start range and end range, together with the
+    /// half width of its 95% confidence interval
+    ///
+    /// Algorithmic complexity: O(stratum)
+    pub fn query_approx(&self, start: usize, end: usize) -> (f64, f64) {
+        assert!(end >= start && end < self.len());
+        // Strata fully inside the range
+        let first = start.div_ceil(self.stratum);
+        let last = (end + 1) / self.stratum;
+        if first >= last {
+            return (self.exact(start, end + 1), 0.0);
+        }
+
+        let estimate = self.estimates[last] - self.estimates[first]
+            + self.exact(start, first * self.stratum)
+            + self.exact(last * self.stratum, end + 1);
+        let variance = self.variances[last] - self.variances[first];
+        (estimate, Z_95 * variance.max(0.0).sqrt())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_query_approx() {
+        let data: Vec<u32> = (0..10_000u32).map(|idx| idx * 7919 % 100).collect();
+        let approx = ApproxSum::new(&data, 100, 20);
+
+        for (start, end) in [(0, 9999), (150, 8765), (3, 250)] {
+            let exact: u32 = data[start..=end].iter().sum();
+            let (estimate, bound) = approx.query_approx(start, end);
+            // Twice the 95% interval, so the fixed sample reliably passes
+            assert!((estimate - f64::from(exact)).abs() <= 2.0 * bound);
+            assert!(bound > 0.0);
+        }
+        let exact: u32 = data[120..=180].iter().sum();
+        assert_eq!(approx.query_approx(120, 180), (f64::from(exact), 0.0));
+
+        let constant = ApproxSum::new(&[5u8; 1000], 10, 3);
+        assert_eq!(constant.query_approx(5, 994), (4950.0, 0.0));
+    }
+}
@@ -0,0 +1,96 @@
+//! Building from Apache Arrow primitive arrays
+//!
+//! Missing values are handled by a [`NullPolicy`], as their slots in the
+//! value buffer hold arbitrary data.
+
+use core::ops::{Add, Sub};
+
+use arrow_array::{types::ArrowPrimitiveType, Array, PrimitiveArray};
+
+use crate::{Error, IndexableSumQuery, NullPolicy, PrefixStorage, SumQuery, Zero};
+
+/// Values of `array` with missing ones handled by `nulls`, for building
+/// any structure of this crate
+///
+/// Algorithmic complexity: O(1), O(n) with [`NullPolicy::Reject`]
+///
+/// # Errors
+///
+/// [`Error::Null`] at the first missing value with [`NullPolicy::Reject`]
+pub fn values<A>(
+    array: &PrimitiveArray<A>,
+    nulls: NullPolicy,
+) -> Result<impl ExactSizeIterator<Item = A::Native> + '_, Error>
+where
+    A: ArrowPrimitiveType,
+    A::Native: Zero,
+{
+    if nulls == NullPolicy::Reject {
+        if let Some(index) = array
+            .logical_nulls()
+            .and_then(|mask| mask.iter().position(|valid| !valid))
+        {
+            return Err(Error::Null { index });
+        }
+    }
+    Ok(array
+        .iter()
+        .map(|value| value.unwrap_or_else(A::Native::zero)))
+}
+
+impl<C> SumQuery<C> {
+    /// Construct `Self` from an Arrow array, handling missing values with
+    /// `nulls`
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Null`] at the first missing value with
+    /// [`NullPolicy::Reject`]
+    ///
+    /// ```
+    /// use arrow_array::Int64Array;
+    /// use kuehree::{NullPolicy, RangeQuery, SumQuery};
+    ///
+    /// let column = Int64Array::from(vec![Some(4), None, Some(-1)]);
+    /// let sum = SumQuery::<Vec<i64>>::from_arrow(&column, NullPolicy::Zero).unwrap();
+    /// assert_eq!(sum.query(0, 2), 3);
+    /// ```
+    pub fn from_arrow<A>(array: &PrimitiveArray<A>, nulls: NullPolicy) -> Result<Self, Error>
+    where
+        A: ArrowPrimitiveType,
+        A::Native: Copy + Add<Output = A::Native> + Sub<Output = A::Native> + Zero,
+        C: PrefixStorage<A::Native>,
+    {
+        Ok(Self::new(values(array, nulls)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{fenwick::Fenwick, RangeQuery};
+    use arrow_array::{Float64Array, UInt32Array};
+
+    #[test]
+    fn test_arrow() {
+        let column = UInt32Array::from(vec![Some(3), None, Some(5), None, Some(1)]);
+        let sum = SumQuery::<Box<[u32]>>::from_arrow(&column, NullPolicy::Zero).unwrap();
+        assert_eq!(sum.query(0, 4), 9);
+        assert_eq!(sum.query(1, 1), 0);
+        assert_eq!(
+            SumQuery::<Vec<u32>>::from_arrow(&column, NullPolicy::Reject),
+            Err(Error::Null { index: 1 })
+        );
+
+        // Offsets into a larger buffer are honored
+        let sliced = column.slice(2, 3);
+        let fenwick = Fenwick::new(values(&sliced, NullPolicy::Zero).unwrap());
+        assert_eq!(fenwick.query(0, 2), 6);
+
+        let dense = Float64Array::from(vec![0.5, 1.5]);
+        let sum = SumQuery::<Vec<f64>>::from_arrow(&dense, NullPolicy::Reject).unwrap();
+        assert_eq!(sum.query(0, 1), 2.0);
+    }
+}
@@ -0,0 +1,226 @@
+//! Building structures off the async executor
+//!
+//! [`build_in_background`] returns a future resolving to the finished
+//! [`SumQuery`], computed on a separate thread or on whatever blocking pool
+//! [`BackgroundBuild::spawn_with`] hands the job to, so large builds never
+//! stall an executor thread. Works with any runtime, e.g. with tokio:
+//!
+//! ```ignore
+//! let sum = BackgroundBuild::<Vec<u64>, _>::new(data)
+//!     .spawn_with(|job| drop(tokio::task::spawn_blocking(job)))
+//!     .start()
+//!     .await;
+//! ```
+
+use core::{
+    future::Future,
+    marker::PhantomData,
+    ops::{Add, Sub},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{IndexableSumQuery, PrefixStorage, SumQuery, Zero};
+
+/// Elements processed between two progress reports
+const PROGRESS_STEP: usize = 1 << 16;
+
+/// Job handed to a spawner
+pub type Job = Box<dyn FnOnce() + Send>;
+
+type Progress = Box<dyn Fn(usize, usize) + Send>;
+
+type Spawner = Box<dyn FnOnce(Job)>;
+
+struct State<R> {
+    result: Option<R>,
+    waker: Option<Waker>,
+    finished: bool,
+}
+
+/// Marks the build finished and wakes the task when dropped, also when the
+/// build panics
+struct Finish<R>(Arc<Mutex<State<R>>>);
+
+impl<R> Finish<R> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State<R>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<R> Drop for Finish<R> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = self.lock();
+            state.finished = true;
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Future resolving to a structure built in the background
+///
+/// # Panics
+///
+/// When polled after the build panicked
+#[must_use = "futures do nothing unless polled"]
+pub struct Background<R> {
+    state: Arc<Mutex<State<R>>>,
+}
+
+impl<R> Future for Background<R> {
+    type Output = R;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(result) = state.result.take() {
+            Poll::Ready(result)
+        } else if state.finished {
+            panic!("background build panicked");
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Configures a background build of a [`SumQuery`] backed by `C`
+pub struct BackgroundBuild<C, T> {
+    data: Vec<T>,
+    spawner: Spawner,
+    progress: Option<Progress>,
+    _phantom: PhantomData<C>,
+}
+
+impl<C, T> BackgroundBuild<C, T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero + Send + 'static,
+    C: PrefixStorage<T> + Send + 'static,
+{
+    /// Build over `data` on a new thread, without progress reports
+    pub fn new(data: Vec<T>) -> Self {
+        Self {
+            data,
+            spawner: Box::new(|job| drop(std::thread::spawn(job))),
+            progress: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Run the build through `spawner`, e.g. an async runtime's
+    /// `spawn_blocking`
+    pub fn spawn_with(mut self, spawner: impl FnOnce(Job) + 'static) -> Self {
+        self.spawner = Box::new(spawner);
+        self
+    }
+
+    /// Call `progress(done, total)` from the building thread every 65536
+    /// elements and once finished
+    pub fn progress(mut self, progress: impl Fn(usize, usize) + Send + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Hand the build to the spawner
+    pub fn start(self) -> Background<SumQuery<C>> {
+        let state = Arc::new(Mutex::new(State {
+            result: None,
+            waker: None,
+            finished: false,
+        }));
+        let finish = Finish(Arc::clone(&state));
+        let (data, progress) = (self.data, self.progress);
+        (self.spawner)(Box::new(move || {
+            let total = data.len();
+            let report = |done: usize| {
+                if let Some(progress) = &progress {
+                    progress(done, total);
+                }
+            };
+            let sum = SumQuery::new(data.into_iter().enumerate().map(|(idx, value)| {
+                if idx % PROGRESS_STEP == 0 && idx > 0 {
+                    report(idx);
+                }
+                value
+            }));
+            report(total);
+            finish.lock().result = Some(sum);
+        }));
+
+        Background { state }
+    }
+}
+
+/// Build a [`SumQuery`] over `data` on a new thread
+///
+/// Algorithmic complexity: O(n) on the background thread, O(1) on the
+/// caller's
+pub fn build_in_background<C, T>(data: Vec<T>) -> Background<SumQuery<C>>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero + Send + 'static,
+    C: PrefixStorage<T> + Send + 'static,
+{
+    BackgroundBuild::new(data).start()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RangeQuery;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        task::Wake,
+        thread::Thread,
+    };
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::park();
+        }
+    }
+
+    #[test]
+    fn test_build_in_background() {
+        let sum = block_on(build_in_background::<Vec<u64>, _>((0..1000).collect()));
+        assert_eq!(sum.query(0, 999), 999 * 1000 / 2);
+
+        let reports = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&reports);
+        let build = BackgroundBuild::<Box<[u64]>, _>::new(vec![1; 200_000])
+            .spawn_with(|job| job())
+            .progress(move |done, total| {
+                assert!(done <= total);
+                counter.fetch_add(1, Ordering::Relaxed);
+            })
+            .start();
+        assert_eq!(block_on(build).query(0, 199_999), 200_000);
+        assert_eq!(reports.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "background build panicked")]
+    fn test_panicked_build() {
+        let build = BackgroundBuild::<Vec<u8>, _>::new(vec![255, 1])
+            .progress(|_, _| panic!("progress failed"))
+            .start();
+        block_on(build);
+    }
+}
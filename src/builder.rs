@@ -0,0 +1,214 @@
+//! Builder bundling the construction options of [`SumQuery`]
+
+use core::{
+    marker::PhantomData,
+    ops::{Add, Sub},
+};
+
+#[cfg(feature = "rayon")]
+use alloc::vec::Vec;
+
+use crate::{scan::Running, trace::BuildSpan, CheckedAdd, Error, PrefixStorage, SumQuery, Zero};
+
+/// `Send + Sync` with the `rayon` feature, whose parallel build shares the
+/// elements between threads, and every type without it
+#[cfg(feature = "rayon")]
+pub trait MaybeSendSync: Send + Sync {}
+
+#[cfg(feature = "rayon")]
+impl<T: Send + Sync> MaybeSendSync for T {}
+
+/// `Send + Sync` with the `rayon` feature, whose parallel build shares the
+/// elements between threads, and every type without it
+#[cfg(not(feature = "rayon"))]
+pub trait MaybeSendSync {}
+
+#[cfg(not(feature = "rayon"))]
+impl<T> MaybeSendSync for T {}
+
+/// Configures how a [`SumQuery`] backed by `C` is built
+///
+/// Elements are converted with [`Into`] before being summed, so choosing
+/// a wider element type for `C` widens the accumulator:
+///
+/// ```
+/// use kuehree::{RangeQuery, SumQuery};
+///
+/// let sum = SumQuery::<Vec<i64>>::builder()
+///     .checked(true)
+///     .build([i32::MAX, i32::MAX])
+///     .unwrap();
+/// assert_eq!(sum.query(0, 1), 2 * i64::from(i32::MAX));
+/// ```
+#[derive(Debug)]
+pub struct SumQueryBuilder<C> {
+    checked: bool,
+    #[cfg(feature = "rayon")]
+    parallel: bool,
+    _phantom: PhantomData<C>,
+}
+
+impl<C> Clone for SumQueryBuilder<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C> Copy for SumQueryBuilder<C> {}
+
+impl<C> Default for SumQueryBuilder<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> SumQuery<C> {
+    /// Start building a [`SumQuery`] backed by `C`
+    pub fn builder() -> SumQueryBuilder<C> {
+        SumQueryBuilder::new()
+    }
+}
+
+impl<C> SumQueryBuilder<C> {
+    /// Builder with every option off
+    pub fn new() -> Self {
+        Self {
+            checked: false,
+            #[cfg(feature = "rayon")]
+            parallel: false,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Fail with [`Error::Overflow`] instead of overflowing when a running
+    /// total does not fit in the element type
+    pub fn checked(mut self, checked: bool) -> Self {
+        self.checked = checked;
+        self
+    }
+
+    /// Compute the running totals on the rayon thread pool
+    #[cfg(feature = "rayon")]
+    pub fn parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Switch the backing container to `D`, keeping the other options
+    pub fn storage<D>(self) -> SumQueryBuilder<D> {
+        SumQueryBuilder {
+            checked: self.checked,
+            #[cfg(feature = "rayon")]
+            parallel: self.parallel,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Build the [`SumQuery`]
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Errors
+    ///
+    /// [`Error::Overflow`] if [`Self::checked`] is set and a running total
+    /// overflows
+    pub fn build<T, I>(self, data: impl IntoIterator<Item = I>) -> Result<SumQuery<C>, Error>
+    where
+        I: Into<T>,
+        T: Copy + Sub<Output = T> + Add<Output = T> + Zero + CheckedAdd + MaybeSendSync,
+        C: PrefixStorage<T>,
+    {
+        let span = BuildSpan::enter("SumQuery");
+        let checked = self.checked;
+        let add = move |a: T, b: T| {
+            if checked {
+                a.checked_add(b)
+            } else {
+                Some(a + b)
+            }
+        };
+        let leading_zero = C::LEADING_ZERO.then(T::zero);
+
+        #[cfg(feature = "rayon")]
+        if self.parallel {
+            let mut totals: Vec<T> = data.into_iter().map(Into::into).collect();
+            span.record_len(totals.len());
+            crate::scan::inclusive_scan(&mut totals, &add).ok_or(Error::Overflow)?;
+            return Ok(SumQuery::from_container(C::from_totals(
+                leading_zero.into_iter().chain(totals),
+            )));
+        }
+
+        let mut running = Running::new(None);
+        // Keep counting after an overflow, fixed size storage checks the
+        // number of elements
+        let totals = data.into_iter().map(|d| running.push(d.into(), &add));
+        let prefix_sum_array = C::from_totals(leading_zero.into_iter().chain(totals));
+        span.record_len(prefix_sum_array.len() - usize::from(C::LEADING_ZERO));
+
+        if running.overflowed() {
+            Err(Error::Overflow)
+        } else {
+            Ok(SumQuery::from_container(prefix_sum_array))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RangeQuery;
+
+    #[test]
+    fn test_builder() {
+        let sum = SumQuery::<Vec<u8>>::builder().build([200u8, 50]).unwrap();
+        assert_eq!(sum.query(0, 0), 200);
+
+        let checked = SumQuery::<Vec<u8>>::builder().checked(true);
+        assert_eq!(checked.build([200u8, 100]), Err(Error::Overflow));
+        assert_eq!(
+            checked.storage::<[u16; 2]>().build([200u8, 100]),
+            Ok(SumQuery::from([200u16, 100]))
+        );
+        assert_eq!(
+            SumQuery::<[f32; 2]>::builder()
+                .checked(true)
+                .build([f32::MAX, f32::MAX]),
+            Err(Error::Overflow)
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel() {
+        use crate::IndexableSumQuery;
+
+        let data: Vec<u64> = (0..100_000).map(|i| i * 7 % 13).collect();
+        let parallel = SumQuery::<Vec<u64>>::builder()
+            .parallel(true)
+            .build(data.iter().copied())
+            .unwrap();
+        assert_eq!(parallel, SumQuery::<Vec<_>>::new(data));
+
+        let overflow = SumQuery::<Vec<u8>>::builder()
+            .parallel(true)
+            .checked(true)
+            .build([1u8; 100_000]);
+        assert_eq!(overflow, Err(Error::Overflow));
+
+        // Chunk totals overflow although every prefix fits
+        let mut data = vec![0i64; 1 << 16];
+        data[0] = -i64::MAX;
+        data[(1 << 16) - 2] = 1 << 62;
+        data[(1 << 16) - 1] = 1 << 62;
+        let checked = SumQuery::<Vec<i64>>::builder().checked(true);
+        let sequential = checked.build(data.iter().copied());
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let parallel = pool.install(|| checked.parallel(true).build(data.iter().copied()));
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel.unwrap().query(0, (1 << 16) - 1), 1);
+    }
+}
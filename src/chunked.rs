@@ -0,0 +1,221 @@
+//! Prefix sums split in fixed size chunks, for continuously growing data
+
+use core::ops::{Add, Sub};
+
+use alloc::vec::Vec;
+
+use crate::{trace::BuildSpan, MemoryUsage, RangeQuery, UpdatableRangeQuery, Zero};
+
+/// Default number of elements per chunk
+const CHUNK_LEN: usize = 4096;
+
+/// Prefix sums over fixed size chunks, each with its own running totals,
+/// plus the running totals of the chunks themselves
+///
+/// Appending never touches earlier chunks, so it is O(1) however large
+/// the structure grows, queries stay O(1), and replacing an element only
+/// rebuilds the rest of its chunk and the chunk totals after it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChunkedPrefixSums<T> {
+    chunk_len: usize,
+    /// Running totals restarting at every chunk
+    local: Vec<T>,
+    /// Total of the chunks before each chunk
+    offsets: Vec<T>,
+}
+
+impl<T> Default for ChunkedPrefixSums<T> {
+    fn default() -> Self {
+        Self::with_chunk_len(CHUNK_LEN)
+    }
+}
+
+impl<T> ChunkedPrefixSums<T> {
+    /// Empty `Self` with `chunk_len` elements per chunk
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_len` is 0
+    pub fn with_chunk_len(chunk_len: usize) -> Self {
+        assert!(chunk_len > 0, "chunks need at least one element");
+        Self {
+            chunk_len,
+            local: Vec::new(),
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.local.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.local.is_empty()
+    }
+
+    /// Number of elements per chunk
+    pub fn chunk_len(&self) -> usize {
+        self.chunk_len
+    }
+}
+
+impl<T> ChunkedPrefixSums<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    /// Construct `Self` with the default chunk length
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("ChunkedPrefixSums");
+        let mut sums = Self::default();
+        sums.extend(data);
+        span.record_len(sums.len());
+        sums
+    }
+
+    /// Append `value`
+    ///
+    /// Algorithmic complexity: O(1) amortized
+    pub fn push(&mut self, value: T) {
+        let len = self.local.len();
+        if len.is_multiple_of(self.chunk_len) {
+            let offset = match self.offsets.last() {
+                Some(&offset) => offset + self.local[len - 1],
+                None => T::zero(),
+            };
+            self.offsets.push(offset);
+            self.local.push(value);
+        } else {
+            self.local.push(self.local[len - 1] + value);
+        }
+    }
+
+    /// Sum of the first `count` elements
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn prefix_sum(&self, count: usize) -> T {
+        assert!(count <= self.len());
+        match count.checked_sub(1) {
+            Some(last) => self.offsets[last / self.chunk_len] + self.local[last],
+            None => T::zero(),
+        }
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len());
+        self.prefix_sum(end + 1) - self.prefix_sum(start)
+    }
+
+    /// Current element at `index`
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn get(&self, index: usize) -> T {
+        assert!(index < self.len());
+        if index.is_multiple_of(self.chunk_len) {
+            self.local[index]
+        } else {
+            self.local[index] - self.local[index - 1]
+        }
+    }
+
+    /// Replace the element at `index` with `value`
+    ///
+    /// Algorithmic complexity: O(chunk length + n / chunk length)
+    pub fn update(&mut self, index: usize, value: T) {
+        let old = self.get(index);
+        let chunk = index / self.chunk_len;
+        let chunk_end = self.len().min((chunk + 1) * self.chunk_len);
+        for total in &mut self.local[index..chunk_end] {
+            *total = *total - old + value;
+        }
+        for offset in &mut self.offsets[chunk + 1..] {
+            *offset = *offset - old + value;
+        }
+    }
+}
+
+impl<T> Extend<T> for ChunkedPrefixSums<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<T> FromIterator<T> for ChunkedPrefixSums<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+impl<T> RangeQuery<T> for ChunkedPrefixSums<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> UpdatableRangeQuery<T> for ChunkedPrefixSums<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+}
+
+impl<T> MemoryUsage for ChunkedPrefixSums<T> {
+    fn heap_bytes(&self) -> usize {
+        self.local.heap_bytes() + self.offsets.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_chunked() {
+        let mut arr: Vec<u32> = (0..50).map(|i| i * 7 % 13).collect();
+        let mut sums = ChunkedPrefixSums::with_chunk_len(8);
+        sums.extend(arr.iter().copied());
+        assert_eq!(sums.len(), 50);
+
+        arr[3] = 20;
+        sums.update(3, 20);
+        arr[49] = 0;
+        sums.update(49, 0);
+        for start in 0..arr.len() {
+            assert_eq!(sums.get(start), arr[start]);
+            for end in start..arr.len() {
+                assert_eq!(sums.query(start, end), arr[start..=end].iter().sum::<u32>());
+            }
+        }
+
+        let sums: ChunkedPrefixSums<u64> = (1..=10_000).collect();
+        assert_eq!(sums.prefix_sum(0), 0);
+        assert_eq!(sums.query(0, 9_999), 10_000 * 10_001 / 2);
+        assert_eq!(sums.query(4_095, 4_096), 4_096 + 4_097);
+    }
+}
@@ -0,0 +1,66 @@
+//! Runtime detection of the vector instructions of the host CPU
+//!
+//! Kernels with a vectorized clone pick it at runtime, so a binary built
+//! for the baseline target still uses AVX2 or AVX-512 where the CPU has
+//! it. Without `std` there is no runtime detection and only the target
+//! features enabled at compile time count.
+
+/// Vector instruction set the kernels are dispatched to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SimdLevel {
+    /// No vector extension beyond the compilation target
+    Scalar,
+    /// x86 AVX2
+    Avx2,
+    /// x86 AVX-512 foundation
+    Avx512,
+    /// Arm NEON, part of every aarch64 CPU
+    Neon,
+}
+
+/// Best vector instruction set of the host CPU
+///
+/// The detection is cached by the standard library, calling this in a
+/// loop is cheap.
+pub fn simd_level() -> SimdLevel {
+    #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+    if std::is_x86_feature_detected!("avx512f") {
+        return SimdLevel::Avx512;
+    }
+    #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+    if std::is_x86_feature_detected!("avx2") {
+        return SimdLevel::Avx2;
+    }
+    #[cfg(all(feature = "std", target_arch = "aarch64"))]
+    if std::arch::is_aarch64_feature_detected!("neon") {
+        return SimdLevel::Neon;
+    }
+    if cfg!(target_feature = "avx512f") {
+        SimdLevel::Avx512
+    } else if cfg!(target_feature = "avx2") {
+        SimdLevel::Avx2
+    } else if cfg!(target_feature = "neon") {
+        SimdLevel::Neon
+    } else {
+        SimdLevel::Scalar
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_simd_level() {
+        let level = simd_level();
+        assert_eq!(level, simd_level());
+        if cfg!(target_feature = "avx512f") {
+            assert_eq!(level, SimdLevel::Avx512);
+        } else if cfg!(target_feature = "avx2") {
+            assert!(matches!(level, SimdLevel::Avx2 | SimdLevel::Avx512));
+        }
+        if cfg!(target_arch = "aarch64") {
+            assert_eq!(level, SimdLevel::Neon);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Building from a column of CSV records
+//!
+//! Records are parsed one at a time as the structure is built, so the
+//! column is never collected first. The reader decides on headers and
+//! delimiters, the record numbers in [`Error::Parse`] count data records
+//! only.
+
+use core::{
+    ops::{Add, Sub},
+    str::FromStr,
+};
+use std::io;
+
+use ::csv::Reader;
+
+pub use crate::load::LoadError;
+use crate::{load::stream, Error, PrefixStorage, SumQuery, Zero};
+
+/// Stream field `column` of every record of `reader` into `build`
+///
+/// `build` receives the parsed values as an iterator to construct any
+/// structure from. The iterator ends early on failure, whose error is
+/// returned instead of the structure.
+///
+/// Algorithmic complexity: O(n)
+///
+/// # Errors
+///
+/// - [`LoadError::Csv`] if reading a record fails
+/// - [`Error::OutOfBounds`] if a record has no field `column`
+/// - [`Error::Parse`] if a field does not parse as `T`
+pub fn load<R, T, S>(
+    reader: &mut Reader<R>,
+    column: usize,
+    build: impl FnOnce(&mut dyn Iterator<Item = T>) -> S,
+) -> Result<S, LoadError>
+where
+    R: io::Read,
+    T: FromStr,
+{
+    let mut records = reader.records().enumerate();
+    let next = || {
+        let Some((row, record)) = records.next() else {
+            return Ok(None);
+        };
+        let record = record?;
+        let field = record.get(column).ok_or(Error::OutOfBounds {
+            index: column,
+            len: record.len(),
+        })?;
+        let value = field
+            .trim()
+            .parse()
+            .map_err(|_| Error::Parse { row, column })?;
+        Ok(Some(value))
+    };
+    stream(next, build)
+}
+
+impl<C> SumQuery<C> {
+    /// Construct `Self` from field `column` of every record of `reader`
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Errors
+    ///
+    /// See [`load`], and [`Error::LengthMismatch`] if `C` holds a fixed
+    /// number of totals and the column has another length
+    ///
+    /// ```
+    /// use kuehree::{RangeQuery, SumQuery};
+    ///
+    /// let data = "day,visits\nmon,120\ntue,95\nwed,143\n";
+    /// let mut reader = csv::Reader::from_reader(data.as_bytes());
+    /// let visits = SumQuery::<Vec<u64>>::from_csv_column(&mut reader, 1).unwrap();
+    /// assert_eq!(visits.query(1, 2), 238);
+    /// ```
+    pub fn from_csv_column<R, T>(reader: &mut Reader<R>, column: usize) -> Result<Self, LoadError>
+    where
+        R: io::Read,
+        T: Copy + Add<Output = T> + Sub<Output = T> + Zero + FromStr,
+        C: PrefixStorage<T>,
+    {
+        Ok(load(reader, column, |values| Self::try_collect(values))??)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        rmq::{Max, SparseTable},
+        RangeQuery,
+    };
+    use ::csv::ReaderBuilder;
+
+    #[test]
+    fn test_csv() {
+        let data = "1.5;3\n2.5; 4\n-1;5\n";
+        let reader = || {
+            ReaderBuilder::new()
+                .has_headers(false)
+                .delimiter(b';')
+                .from_reader(data.as_bytes())
+        };
+
+        let sum = SumQuery::<Vec<f64>>::from_csv_column(&mut reader(), 0).unwrap();
+        assert_eq!(sum.query(0, 2), 3.0);
+        let max = load(&mut reader(), 1, |values| {
+            SparseTable::<u8, Max>::new(values)
+        })
+        .unwrap();
+        assert_eq!(max.query(0, 1), 4);
+
+        let parse = SumQuery::<Vec<u8>>::from_csv_column(&mut reader(), 0);
+        assert!(matches!(
+            parse,
+            Err(LoadError::Query(Error::Parse { row: 0, column: 0 }))
+        ));
+        let missing = SumQuery::<Vec<u8>>::from_csv_column(&mut reader(), 2);
+        assert!(matches!(
+            missing,
+            Err(LoadError::Query(Error::OutOfBounds { index: 2, len: 2 }))
+        ));
+        let ragged = "1,2\n3\n";
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(ragged.as_bytes());
+        let result = SumQuery::<Vec<u8>>::from_csv_column(&mut reader, 0);
+        assert!(matches!(result, Err(LoadError::Csv(_))));
+
+        let reader = |data: &'static str| {
+            ReaderBuilder::new()
+                .has_headers(false)
+                .from_reader(data.as_bytes())
+        };
+        let fixed = SumQuery::<[u64; 3]>::from_csv_column(&mut reader("1\nx\n3\n"), 0);
+        assert!(matches!(
+            fixed,
+            Err(LoadError::Query(Error::Parse { row: 1, column: 0 }))
+        ));
+        let short = SumQuery::<[u64; 3]>::from_csv_column(&mut reader("1\n2\n"), 0);
+        assert!(matches!(
+            short,
+            Err(LoadError::Query(Error::LengthMismatch {
+                expected: 3,
+                found: 2
+            }))
+        ));
+        let fixed = SumQuery::<[u64; 3]>::from_csv_column(&mut reader("1\n2\n3\n"), 0).unwrap();
+        assert_eq!(fixed.query(0, 2), 6);
+    }
+}
@@ -0,0 +1,238 @@
+//! Segment tree allocating nodes only for the positions that were set
+
+use core::{marker::PhantomData, mem};
+
+use alloc::boxed::Box;
+
+use crate::{rmq::Operation, MemoryUsage};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Node<T> {
+    value: T,
+    left: Option<Box<Node<T>>>,
+    right: Option<Box<Node<T>>>,
+}
+
+impl<T: Copy> Node<T> {
+    fn leaf(value: T) -> Box<Self> {
+        Box::new(Self {
+            value,
+            left: None,
+            right: None,
+        })
+    }
+
+    /// Recompute the value from the children that exist
+    fn pull<M: Operation<T>>(&mut self) {
+        self.value = match (&self.left, &self.right) {
+            (Some(left), Some(right)) => M::combine(left.value, right.value),
+            (Some(child), None) | (None, Some(child)) => child.value,
+            (None, None) => return,
+        };
+    }
+
+    fn count(&self) -> usize {
+        1 + self.left.as_ref().map_or(0, |left| left.count())
+            + self.right.as_ref().map_or(0, |right| right.count())
+    }
+}
+
+/// Segment tree over the positions `0..len` holding nodes only on the
+/// paths to positions that were set
+///
+/// Unset positions are skipped by queries, so a range without any set
+/// position folds to `None`. Many trees can share a huge domain, e.g. one
+/// tree of value counts per vertex of a tree, and [`Self::merge`] combines
+/// two of them in time proportional to the nodes they have in common.
+/// Merging every vertex into its parent bottom-up costs O(k log len)
+/// overall for `k` set positions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DynamicSegmentTree<T, M> {
+    len: usize,
+    root: Option<Box<Node<T>>>,
+    _phantom: PhantomData<M>,
+}
+
+impl<T, M> DynamicSegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    /// Construct `Self` over `len` positions, none of them set
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            root: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Number of positions
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether no position is set
+    pub fn has_no_values(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Set the position `index` to `value`
+    ///
+    /// Algorithmic complexity: O(log len)
+    pub fn update(&mut self, index: usize, value: T) {
+        assert!(index < self.len);
+        let root = self.root.take();
+        self.root = Some(Self::set(root, 0, self.len - 1, index, value));
+    }
+
+    fn set(
+        node: Option<Box<Node<T>>>,
+        lo: usize,
+        hi: usize,
+        index: usize,
+        value: T,
+    ) -> Box<Node<T>> {
+        if lo == hi {
+            return Node::leaf(value);
+        }
+        let mut node = node.unwrap_or_else(|| Node::leaf(value));
+        let mid = lo + (hi - lo) / 2;
+        if index <= mid {
+            node.left = Some(Self::set(node.left.take(), lo, mid, index, value));
+        } else {
+            node.right = Some(Self::set(node.right.take(), mid + 1, hi, index, value));
+        }
+        node.pull::<M>();
+        node
+    }
+
+    /// Current value at `index`, `None` if it was never set
+    ///
+    /// Algorithmic complexity: O(log len)
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.query(index, index)
+    }
+
+    /// Fold of the set positions between start range and end range
+    ///
+    /// Algorithmic complexity: O(log len)
+    pub fn query(&self, start: usize, end: usize) -> Option<T> {
+        assert!(end >= start && end < self.len);
+        Self::fold(self.root.as_deref(), 0, self.len - 1, start, end)
+    }
+
+    fn fold(node: Option<&Node<T>>, lo: usize, hi: usize, start: usize, end: usize) -> Option<T> {
+        let node = node?;
+        if start <= lo && hi <= end {
+            return Some(node.value);
+        }
+        let mid = lo + (hi - lo) / 2;
+        let left = (start <= mid)
+            .then(|| Self::fold(node.left.as_deref(), lo, mid, start, end))
+            .flatten();
+        let right = (end > mid)
+            .then(|| Self::fold(node.right.as_deref(), mid + 1, hi, start, end))
+            .flatten();
+        match (left, right) {
+            (Some(left), Some(right)) => Some(M::combine(left, right)),
+            (left, right) => left.or(right),
+        }
+    }
+
+    /// Combine `self` and `other` position by position, positions set in
+    /// both become `combine(self, other)`
+    ///
+    /// Algorithmic complexity: O(nodes in common), a subtree set in only
+    /// one of the trees is moved over whole
+    ///
+    /// # Panics
+    ///
+    /// If the trees cover a different number of positions
+    pub fn merge(mut self, other: Self) -> Self {
+        assert_eq!(self.len, other.len, "merged trees differ in length");
+        self.root = Self::merge_nodes(self.root.take(), other.root, 0, self.len.saturating_sub(1));
+        self
+    }
+
+    fn merge_nodes(
+        node: Option<Box<Node<T>>>,
+        other: Option<Box<Node<T>>>,
+        lo: usize,
+        hi: usize,
+    ) -> Option<Box<Node<T>>> {
+        let (mut node, other) = match (node, other) {
+            (Some(node), Some(other)) => (node, other),
+            (node, other) => return node.or(other),
+        };
+        if lo == hi {
+            node.value = M::combine(node.value, other.value);
+            return Some(node);
+        }
+        let other = *other;
+        let mid = lo + (hi - lo) / 2;
+        node.left = Self::merge_nodes(node.left.take(), other.left, lo, mid);
+        node.right = Self::merge_nodes(node.right.take(), other.right, mid + 1, hi);
+        node.pull::<M>();
+        Some(node)
+    }
+}
+
+/// Counts every allocated node
+impl<T: Copy, M> MemoryUsage for DynamicSegmentTree<T, M> {
+    fn heap_bytes(&self) -> usize {
+        self.root.as_ref().map_or(0, |root| root.count()) * mem::size_of::<Node<T>>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmq::{Max, Sum};
+
+    #[test]
+    fn test_dynamic() {
+        // Count of each label below every vertex of a small tree
+        let parents = [usize::MAX, 0, 0, 1, 1, 2, 5];
+        let labels = [3usize, 7, 3, 1_000_000, 7, 3, 42];
+        let mut below: Vec<_> = labels
+            .iter()
+            .map(|&label| {
+                let mut tree = DynamicSegmentTree::<u32, Sum>::new(1 << 20);
+                tree.update(label, 1);
+                Some(tree)
+            })
+            .collect();
+        for vertex in (1..parents.len()).rev() {
+            let child = below[vertex].take().unwrap();
+            let parent = below[parents[vertex]].take().unwrap();
+            below[parents[vertex]] = Some(parent.merge(child.clone()));
+            below[vertex] = Some(child);
+        }
+
+        let root = below[0].as_ref().unwrap();
+        assert_eq!(root.query(0, (1 << 20) - 1), Some(7));
+        assert_eq!(root.get(3), Some(3));
+        assert_eq!(root.get(7), Some(2));
+        assert_eq!(root.query(8, 41), None);
+        assert_eq!(below[2].as_ref().unwrap().query(0, 100), Some(3));
+        assert!(root.heap_bytes() < 64 * 21 * mem::size_of::<Node<u32>>());
+
+        let mut peaks = DynamicSegmentTree::<i32, Max>::new(10);
+        assert!(peaks.has_no_values() && !peaks.is_empty());
+        peaks.update(4, -3);
+        peaks.update(8, 5);
+        peaks.update(8, 2);
+        assert_eq!(peaks.query(0, 7), Some(-3));
+        assert_eq!(peaks.query(0, 9), Some(2));
+        assert_eq!(peaks.query(5, 7), None);
+        assert!(!peaks.has_no_values());
+        assert!(DynamicSegmentTree::<i32, Max>::new(0).is_empty());
+    }
+}
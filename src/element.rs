@@ -0,0 +1,405 @@
+use core::{
+    num::Wrapping,
+    ops::{Add, Mul, Sub},
+    time::Duration,
+};
+
+/// Additive identity of an element type
+///
+/// Prefix sums start from this value. It is implemented for the primitive
+/// numbers and [`Duration`], implement it for your own `Add + Sub` types to
+/// use them with [`SumQuery`](crate::SumQuery).
+pub trait Zero {
+    fn zero() -> Self;
+}
+
+macro_rules! impl_zero {
+    ($zero:literal => $($ty:ty),*) => {
+        $(
+            impl Zero for $ty {
+                fn zero() -> Self {
+                    $zero
+                }
+            }
+        )*
+    };
+}
+
+impl_zero!(0 => u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+impl_zero!(0.0 => f32, f64);
+
+/// Multiplicative identity of an element type
+///
+/// Needed by the products of [`matrix`](crate::matrix), implemented for
+/// the primitive numbers.
+pub trait One {
+    fn one() -> Self;
+}
+
+macro_rules! impl_one {
+    ($one:literal => $($ty:ty),*) => {
+        $(
+            impl One for $ty {
+                fn one() -> Self {
+                    $one
+                }
+            }
+        )*
+    };
+}
+
+impl_one!(1 => u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+impl_one!(1.0 => f32, f64);
+
+impl<T: One> One for Wrapping<T> {
+    fn one() -> Self {
+        Wrapping(T::one())
+    }
+}
+
+impl<T: Zero> Zero for Wrapping<T> {
+    fn zero() -> Self {
+        Wrapping(T::zero())
+    }
+}
+
+impl Zero for Duration {
+    fn zero() -> Self {
+        Duration::ZERO
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl Zero for chrono::TimeDelta {
+    fn zero() -> Self {
+        chrono::TimeDelta::zero()
+    }
+}
+
+#[cfg(feature = "complex")]
+impl<T: Zero> Zero for num_complex::Complex<T> {
+    fn zero() -> Self {
+        num_complex::Complex::new(T::zero(), T::zero())
+    }
+}
+
+#[cfg(feature = "uom")]
+impl<D, U, V> Zero for uom::si::Quantity<D, U, V>
+where
+    D: uom::si::Dimension + ?Sized,
+    U: uom::si::Units<V> + ?Sized,
+    V: uom::num_traits::Num + uom::Conversion<V>,
+{
+    fn zero() -> Self {
+        Self {
+            dimension: core::marker::PhantomData,
+            units: core::marker::PhantomData,
+            value: V::zero(),
+        }
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl Zero for rust_decimal::Decimal {
+    fn zero() -> Self {
+        rust_decimal::Decimal::ZERO
+    }
+}
+
+/// Integer modulo `P`, for sums and products wrapping around a modulus
+///
+/// ```
+/// use kuehree::Modular;
+///
+/// type Mod = Modular<7>;
+///
+/// assert_eq!(Mod::new(5) + Mod::new(4), Mod::new(2));
+/// assert_eq!((Mod::new(3) - Mod::new(5)).value(), 5);
+/// assert_eq!(Mod::new(3).inverse(), Some(Mod::new(5)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Modular<const P: u64>(u64);
+
+impl<const P: u64> Modular<P> {
+    /// `value` modulo `P`
+    pub const fn new(value: u64) -> Self {
+        Self(value % P)
+    }
+
+    /// Representative in `0..P`
+    pub const fn value(self) -> u64 {
+        self.0
+    }
+
+    /// `self` raised to `exp`
+    ///
+    /// Algorithmic complexity: O(log exp)
+    pub fn pow(self, mut exp: u64) -> Self {
+        let (mut base, mut result) = (self, Self::one());
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            exp >>= 1;
+        }
+        result
+    }
+
+    /// Multiplicative inverse, `None` for zero, `P` has to be prime
+    ///
+    /// Algorithmic complexity: O(log P)
+    pub fn inverse(self) -> Option<Self> {
+        (self.0 != 0).then(|| self.pow(P - 2))
+    }
+}
+
+impl<const P: u64> Add for Modular<P> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        let (sum, overflow) = self.0.overflowing_add(rhs.0);
+        Self(if overflow || sum >= P {
+            sum.wrapping_sub(P)
+        } else {
+            sum
+        })
+    }
+}
+
+impl<const P: u64> Sub for Modular<P> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        let (difference, overflow) = self.0.overflowing_sub(rhs.0);
+        Self(if overflow {
+            difference.wrapping_add(P)
+        } else {
+            difference
+        })
+    }
+}
+
+impl<const P: u64> Mul for Modular<P> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self((u128::from(self.0) * u128::from(rhs.0) % u128::from(P)) as u64)
+    }
+}
+
+impl<const P: u64> Zero for Modular<P> {
+    fn zero() -> Self {
+        Self(0)
+    }
+}
+
+impl<const P: u64> One for Modular<P> {
+    fn one() -> Self {
+        Self::new(1)
+    }
+}
+
+/// Addition reporting overflow, used by
+/// [`SumQueryBuilder::checked`](crate::SumQueryBuilder::checked)
+///
+/// Floating point additions overflow when a finite sum becomes infinite.
+pub trait CheckedAdd: Sized {
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_checked_add_int {
+    ($($ty:ty),*) => {
+        $(
+            impl CheckedAdd for $ty {
+                fn checked_add(self, rhs: Self) -> Option<Self> {
+                    <$ty>::checked_add(self, rhs)
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_checked_add_float {
+    ($($ty:ty),*) => {
+        $(
+            impl CheckedAdd for $ty {
+                fn checked_add(self, rhs: Self) -> Option<Self> {
+                    let sum = self + rhs;
+                    (sum.is_finite() || !self.is_finite() || !rhs.is_finite()).then_some(sum)
+                }
+            }
+        )*
+    };
+}
+
+impl_checked_add_int!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+impl_checked_add_float!(f32, f64);
+
+/// Wrapping arithmetic never overflows
+impl<T> CheckedAdd for Wrapping<T>
+where
+    Wrapping<T>: Add<Output = Wrapping<T>>,
+{
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        Some(self + rhs)
+    }
+}
+
+impl CheckedAdd for Duration {
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        Duration::checked_add(self, rhs)
+    }
+}
+
+/// Modular arithmetic never overflows
+impl<const P: u64> CheckedAdd for Modular<P> {
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        Some(self + rhs)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl CheckedAdd for chrono::TimeDelta {
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        chrono::TimeDelta::checked_add(&self, &rhs)
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl CheckedAdd for rust_decimal::Decimal {
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        rust_decimal::Decimal::checked_add(self, rhs)
+    }
+}
+
+/// Overflows when either part does
+#[cfg(feature = "complex")]
+impl<T: CheckedAdd> CheckedAdd for num_complex::Complex<T> {
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        Some(num_complex::Complex::new(
+            self.re.checked_add(rhs.re)?,
+            self.im.checked_add(rhs.im)?,
+        ))
+    }
+}
+
+#[cfg(feature = "uom")]
+impl<D, U, V> CheckedAdd for uom::si::Quantity<D, U, V>
+where
+    D: uom::si::Dimension + ?Sized,
+    U: uom::si::Units<V> + ?Sized,
+    V: uom::num_traits::Num + uom::Conversion<V> + CheckedAdd,
+{
+    fn checked_add(self, rhs: Self) -> Option<Self> {
+        Some(Self {
+            dimension: core::marker::PhantomData,
+            units: core::marker::PhantomData,
+            value: self.value.checked_add(rhs.value)?,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::{fenwick::Fenwick, Error, IndexableSumQuery, RangeQuery, SumQuery};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_duration() {
+        let latencies: Vec<Duration> = [3, 15, 7, 1].map(Duration::from_millis).to_vec();
+        let sum = SumQuery::<Vec<_>>::new(latencies.iter().copied());
+        assert_eq!(sum.query(1, 2), Duration::from_millis(22));
+        let mut fenwick = Fenwick::new(latencies);
+        fenwick.update(0, Duration::from_secs(1));
+        assert_eq!(fenwick.query(0, 1), Duration::from_millis(1_015));
+
+        let overflow = SumQuery::<Vec<Duration>>::builder()
+            .checked(true)
+            .build([Duration::MAX, Duration::from_nanos(1)]);
+        assert_eq!(overflow, Err(Error::Overflow));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::TimeDelta;
+
+        let deltas = [
+            TimeDelta::seconds(5),
+            TimeDelta::seconds(-2),
+            TimeDelta::minutes(1),
+        ];
+        let sum = SumQuery::<Vec<_>>::new(deltas);
+        assert_eq!(sum.query(0, 2), TimeDelta::seconds(63));
+        assert_eq!(sum.query(1, 1), TimeDelta::seconds(-2));
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_decimal() {
+        use rust_decimal::Decimal;
+
+        // 0.1 + 0.2 is exactly 0.3, unlike with floats
+        let prices = [
+            Decimal::new(1, 1),
+            Decimal::new(2, 1),
+            Decimal::new(1999, 2),
+        ];
+        let sum = SumQuery::<Vec<_>>::new(prices);
+        assert_eq!(sum.query(0, 1), Decimal::new(3, 1));
+        let mut fenwick = Fenwick::new(prices);
+        fenwick.add(2, Decimal::new(1, 2));
+        assert_eq!(fenwick.query(1, 2), Decimal::new(2020, 2));
+
+        let overflow = SumQuery::<Vec<Decimal>>::builder()
+            .checked(true)
+            .build([Decimal::MAX, Decimal::ONE]);
+        assert_eq!(overflow, Err(Error::Overflow));
+    }
+
+    #[cfg(feature = "complex")]
+    #[test]
+    fn test_complex() {
+        use crate::rmq::{SegmentTree, Sum};
+        use num_complex::Complex;
+
+        let signal: Vec<Complex<f64>> = (0..8)
+            .map(|i| Complex::new(f64::from(i), -f64::from(i)))
+            .collect();
+        let sum = SumQuery::<Vec<_>>::new(signal.iter().copied());
+        assert_eq!(sum.query(2, 4), Complex::new(9.0, -9.0));
+        let mut tree = SegmentTree::<_, Sum>::new(signal);
+        tree.update(3, Complex::new(0.0, 1.0));
+        assert_eq!(tree.query(2, 4), Complex::new(6.0, -5.0));
+
+        let overflow = SumQuery::<Vec<Complex<f32>>>::builder()
+            .checked(true)
+            .build([Complex::new(0.0, f32::MAX), Complex::new(0.0, f32::MAX)]);
+        assert_eq!(overflow, Err(Error::Overflow));
+    }
+
+    #[cfg(feature = "uom")]
+    #[test]
+    fn test_uom() {
+        use uom::si::{
+            f64::Length,
+            length::{kilometer, meter},
+        };
+
+        let legs = [
+            Length::new::<meter>(400.0),
+            Length::new::<kilometer>(1.5),
+            Length::new::<meter>(100.0),
+        ];
+        let sum = SumQuery::<Vec<_>>::new(legs);
+        assert_eq!(sum.query(0, 2), Length::new::<kilometer>(2.0));
+        assert_eq!(sum.query(1, 2).get::<meter>(), 1_600.0);
+
+        let overflow = SumQuery::<Vec<Length>>::builder()
+            .checked(true)
+            .build([Length::new::<meter>(f64::MAX); 2]);
+        assert_eq!(overflow, Err(Error::Overflow));
+    }
+}
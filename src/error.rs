@@ -0,0 +1,86 @@
+use core::fmt;
+
+/// Errors returned by the fallible constructors and queries of this crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Error {
+    /// `start` is greater than `end`
+    InvertedRange { start: usize, end: usize },
+    /// `index` is outside of a structure holding `len` elements
+    OutOfBounds { index: usize, len: usize },
+    /// A result does not fit in the element type
+    Overflow,
+    /// The structure holds no elements
+    EmptyInput,
+    /// A fixed size sparse table was given fewer levels than it needs
+    InsufficientLevels { required: usize, levels: usize },
+    /// A fixed size structure was given `found` elements instead of `expected`
+    LengthMismatch { expected: usize, found: usize },
+    /// The input holds a missing value at `index` and [`NullPolicy::Reject`]
+    /// was chosen
+    Null { index: usize },
+    /// A column does not hold the requested element type
+    DataType,
+    /// Field `column` of record `row` does not parse as the element type
+    Parse { row: usize, column: usize },
+    /// The element at `index` has no inverse, e.g. a zero in a product
+    NotInvertible { index: usize },
+}
+
+/// How missing values of a nullable column are handled when building from
+/// it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum NullPolicy {
+    /// Missing values count as zero
+    #[default]
+    Zero,
+    /// Fail with [`Error::Null`] at the first missing value
+    Reject,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvertedRange { start, end } => {
+                write!(f, "range start {start} is greater than end {end}")
+            }
+            Self::OutOfBounds { index, len } => {
+                write!(f, "index {index} is out of bounds for length {len}")
+            }
+            Self::Overflow => f.write_str("arithmetic overflow"),
+            Self::EmptyInput => f.write_str("structure holds no elements"),
+            Self::InsufficientLevels { required, levels } => {
+                write!(f, "sparse table needs {required} levels but has {levels}")
+            }
+            Self::LengthMismatch { expected, found } => {
+                write!(f, "expected {expected} elements but found {found}")
+            }
+            Self::Null { index } => write!(f, "missing value at index {index}"),
+            Self::DataType => f.write_str("column holds another element type"),
+            Self::Parse { row, column } => {
+                write!(f, "field {column} of record {row} does not parse")
+            }
+            Self::NotInvertible { index } => {
+                write!(f, "element at index {index} has no inverse")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Checks that `[start, end]` is a valid range over `len` elements
+pub(crate) fn check_range(start: usize, end: usize, len: usize) -> Result<(), Error> {
+    let result = if len == 0 {
+        Err(Error::EmptyInput)
+    } else if start > end {
+        Err(Error::InvertedRange { start, end })
+    } else if end >= len {
+        Err(Error::OutOfBounds { index: end, len })
+    } else {
+        Ok(())
+    };
+    crate::trace::checked_query(start, end, &result);
+    result
+}
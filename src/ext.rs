@@ -0,0 +1,148 @@
+//! Extension traits building structures straight from iterators and slices
+
+use core::ops::{Add, Sub};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::rmq::{Max, Min, SparseTable};
+use crate::{IndexableSumQuery, PrefixStorage, SumQuery, Zero};
+
+/// Lazy running totals of an iterator, see [`IteratorExt::prefix_sums`]
+#[derive(Debug, Clone)]
+pub struct PrefixSums<I, T> {
+    iter: I,
+    total: T,
+}
+
+impl<I, T> Iterator for PrefixSums<I, T>
+where
+    I: Iterator<Item = T>,
+    T: Copy + Add<Output = T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let d = self.iter.next()?;
+        self.total = self.total + d;
+        Some(self.total)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<I, T> ExactSizeIterator for PrefixSums<I, T>
+where
+    I: ExactSizeIterator<Item = T>,
+    T: Copy + Add<Output = T>,
+{
+}
+
+/// Prefix sum adaptors for any iterator
+pub trait IteratorExt<T>: Iterator<Item = T> + Sized {
+    /// Lazily yield the running totals, item `i` is the sum of the first
+    /// `i + 1` items
+    fn prefix_sums(self) -> PrefixSums<Self, T>
+    where
+        T: Copy + Add<Output = T> + Zero,
+    {
+        PrefixSums {
+            iter: self,
+            total: T::zero(),
+        }
+    }
+
+    /// Build a [`SumQuery`] backed by `C` without an intermediate collection
+    ///
+    /// Algorithmic complexity: O(n)
+    fn collect_sum_query<C>(self) -> SumQuery<C>
+    where
+        T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+        C: PrefixStorage<T>,
+    {
+        SumQuery::new(self)
+    }
+}
+
+impl<I: Iterator<Item = T>, T> IteratorExt<T> for I {}
+
+/// Build the structures of this crate straight from a slice
+#[cfg(feature = "alloc")]
+pub trait SliceExt<T> {
+    /// Build a [`SumQuery`] over the slice
+    ///
+    /// Algorithmic complexity: O(n)
+    fn sum_query(&self) -> SumQuery<Vec<T>>
+    where
+        T: Copy + Sub<Output = T> + Add<Output = T> + Zero;
+
+    /// Build a range minimum [`SparseTable`] over the slice
+    ///
+    /// Algorithmic complexity: O(n log n)
+    fn rmq_min(&self) -> SparseTable<T, Min>
+    where
+        T: Copy + PartialOrd;
+
+    /// Build a range maximum [`SparseTable`] over the slice
+    ///
+    /// Algorithmic complexity: O(n log n)
+    fn rmq_max(&self) -> SparseTable<T, Max>
+    where
+        T: Copy + PartialOrd;
+}
+
+#[cfg(feature = "alloc")]
+impl<T> SliceExt<T> for [T] {
+    fn sum_query(&self) -> SumQuery<Vec<T>>
+    where
+        T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+    {
+        SumQuery::new(self.iter().copied())
+    }
+
+    fn rmq_min(&self) -> SparseTable<T, Min>
+    where
+        T: Copy + PartialOrd,
+    {
+        SparseTable::new(self.iter().copied())
+    }
+
+    fn rmq_max(&self) -> SparseTable<T, Max>
+    where
+        T: Copy + PartialOrd,
+    {
+        SparseTable::new(self.iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RangeQuery;
+
+    #[test]
+    fn test_iterator_ext() {
+        let data = [1u32, 3, 4, 8, 6, 1, 4, 2];
+        assert!(data
+            .into_iter()
+            .prefix_sums()
+            .eq([1, 4, 8, 16, 22, 23, 27, 29]));
+        assert_eq!(data.iter().copied().prefix_sums().len(), 8);
+
+        let sum = data.into_iter().collect_sum_query::<Vec<_>>();
+        assert_eq!(sum.query(3, 6), 19);
+        let fixed = (1..=8u32).collect_sum_query::<[_; 8]>();
+        assert_eq!(fixed.query(0, 7), 36);
+    }
+
+    #[test]
+    fn test_slice_ext() {
+        let data: &[u32] = &[1, 3, 4, 8, 6, 1, 4, 2];
+        assert_eq!(data.sum_query().query(3, 6), 19);
+        assert_eq!(data.rmq_min().query(3, 6), 1);
+        assert_eq!(data[..4].rmq_max().query(0, 3), 8);
+    }
+}
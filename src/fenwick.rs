@@ -0,0 +1,642 @@
+//! Fenwick tree, also known as binary indexed tree
+
+use core::ops::{Add, Sub};
+
+#[cfg(target_has_atomic = "64")]
+use core::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::{
+    snapshot::{Shared, Snapshot},
+    trace::BuildSpan,
+    MemoryUsage, RangeQuery, UpdatableRangeQuery, Zero,
+};
+
+/// Prefix sums supporting point updates, both in O(log n)
+///
+/// Needs only `n + 1` elements of memory, against `2n` for a
+/// [`SegmentTree`](crate::rmq::SegmentTree) with [`Sum`](crate::rmq::Sum).
+/// The nodes are shared with the tree's snapshots, see [`Self::snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Fenwick<T> {
+    /// Node `i` holds the sum of the `i & -i` elements ending at element
+    /// `i - 1`, node 0 is unused
+    tree: Shared<Vec<T>>,
+}
+
+impl<T: Zero> Default for Fenwick<T> {
+    fn default() -> Self {
+        Self {
+            tree: Shared::new(vec![T::zero()]),
+        }
+    }
+}
+
+impl<T> Fenwick<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("Fenwick");
+        let mut tree = vec![T::zero()];
+        tree.extend(data);
+        span.record_len(tree.len() - 1);
+        for idx in 1..tree.len() {
+            let parent = idx + (idx & idx.wrapping_neg());
+            if parent < tree.len() {
+                tree[parent] = tree[parent] + tree[idx];
+            }
+        }
+
+        Self {
+            tree: Shared::new(tree),
+        }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.tree.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add `delta` to the element at `index`
+    ///
+    /// Algorithmic complexity: O(log n), O(n) for the first update after a
+    /// snapshot
+    pub fn add(&mut self, index: usize, delta: T) {
+        assert!(index < self.len());
+        let tree = Shared::make_mut(&mut self.tree);
+        let mut idx = index + 1;
+        while idx < tree.len() {
+            tree[idx] = tree[idx] + delta;
+            idx += idx & idx.wrapping_neg();
+        }
+    }
+
+    /// Subtract `delta` from the element at `index`, for element types
+    /// without negative values
+    ///
+    /// Algorithmic complexity: O(log n), O(n) for the first update after a
+    /// snapshot
+    pub fn sub(&mut self, index: usize, delta: T) {
+        assert!(index < self.len());
+        let tree = Shared::make_mut(&mut self.tree);
+        let mut idx = index + 1;
+        while idx < tree.len() {
+            tree[idx] = tree[idx] - delta;
+            idx += idx & idx.wrapping_neg();
+        }
+    }
+
+    /// Sum of the first `count` elements
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn prefix_sum(&self, count: usize) -> T {
+        assert!(count <= self.len());
+        let mut sum = T::zero();
+        let mut idx = count;
+        while idx > 0 {
+            sum = sum + self.tree[idx];
+            idx &= idx - 1;
+        }
+        sum
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len());
+        self.prefix_sum(end + 1) - self.prefix_sum(start)
+    }
+
+    /// Current element at `index`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn get(&self, index: usize) -> T {
+        self.query(index, index)
+    }
+
+    /// Replace the element at `index` with `value`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn update(&mut self, index: usize, value: T) {
+        let old = self.get(index);
+        self.sub(index, old);
+        self.add(index, value);
+    }
+
+    /// Frozen view of the current elements
+    ///
+    /// Algorithmic complexity: O(1), the snapshot shares the nodes until
+    /// the next update of `self` copies them
+    pub fn snapshot(&self) -> Snapshot<Self> {
+        Snapshot::new(Self {
+            tree: Shared::clone(&self.tree),
+        })
+    }
+}
+
+impl<T> RangeQuery<T> for Fenwick<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> UpdatableRangeQuery<T> for Fenwick<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+}
+
+impl<T> MemoryUsage for Fenwick<T> {
+    /// Counts the shared allocation holding the nodes, reference counts
+    /// included
+    fn heap_bytes(&self) -> usize {
+        2 * size_of::<usize>() + size_of::<Vec<T>>() + self.tree.heap_bytes()
+    }
+}
+
+/// Fenwick tree over a `D` dimensional box, point updates and box sums in
+/// O(log^D n)
+///
+/// Points are `[usize; D]` coordinates within the shape given at
+/// construction, the elements are stored in row major order. The node of a
+/// point sums the box product of the ranges a one dimensional node would
+/// cover on every axis.
+///
+/// ```
+/// use kuehree::fenwick::FenwickNd;
+///
+/// let mut cube = FenwickNd::<u32, 3>::with_shape([4, 4, 4]);
+/// cube.add([1, 2, 3], 5);
+/// cube.add([3, 0, 0], 2);
+/// assert_eq!(cube.query([0, 0, 0], [3, 3, 3]), 7);
+/// assert_eq!(cube.query([1, 1, 1], [2, 3, 3]), 5);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FenwickNd<T, const D: usize> {
+    shape: [usize; D],
+    /// Node of the 1-based point `p` stored at the row major position of
+    /// `p - 1`
+    tree: Vec<T>,
+}
+
+impl<T, const D: usize> FenwickNd<T, D>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    /// Construct `Self` with every element zero
+    ///
+    /// Algorithmic complexity: O(n) for `n` points in the shape
+    pub fn with_shape(shape: [usize; D]) -> Self {
+        Self {
+            shape,
+            tree: vec![T::zero(); shape.iter().product()],
+        }
+    }
+
+    /// Construct `Self` from `data` in row major order
+    ///
+    /// Algorithmic complexity: O(n D)
+    ///
+    /// # Panics
+    ///
+    /// If `data` does not hold exactly one element per point of `shape`
+    pub fn new(shape: [usize; D], data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("FenwickNd");
+        let mut tree: Vec<T> = data.into_iter().collect();
+        span.record_len(tree.len());
+        assert_eq!(
+            tree.len(),
+            shape.iter().product::<usize>(),
+            "data does not match the shape"
+        );
+
+        // A one dimensional build along every axis in turn
+        let mut stride = tree.len();
+        for &len in &shape {
+            stride /= len.max(1);
+            for pos in 0..tree.len() {
+                let idx = pos / stride % len + 1;
+                let parent = idx + (idx & idx.wrapping_neg());
+                if parent <= len {
+                    let value = tree[pos];
+                    let target = pos + (parent - idx) * stride;
+                    tree[target] = tree[target] + value;
+                }
+            }
+        }
+        Self { shape, tree }
+    }
+
+    /// Size of every axis
+    pub fn shape(&self) -> [usize; D] {
+        self.shape
+    }
+
+    /// Number of points
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Add `delta` to the element at `point`
+    ///
+    /// Algorithmic complexity: O(log^D n)
+    pub fn add(&mut self, point: [usize; D], delta: T) {
+        self.apply(point, &|node| node + delta);
+    }
+
+    /// Subtract `delta` from the element at `point`, for element types
+    /// without negative values
+    ///
+    /// Algorithmic complexity: O(log^D n)
+    pub fn sub(&mut self, point: [usize; D], delta: T) {
+        self.apply(point, &|node| node - delta);
+    }
+
+    /// Map every node covering `point` with `f`
+    fn apply(&mut self, point: [usize; D], f: &impl Fn(T) -> T) {
+        for (&coordinate, &len) in point.iter().zip(&self.shape) {
+            assert!(coordinate < len, "point {point:?} out of bounds");
+        }
+        self.apply_from(0, 0, point, f);
+    }
+
+    fn apply_from(&mut self, axis: usize, offset: usize, point: [usize; D], f: &impl Fn(T) -> T) {
+        if axis == D {
+            self.tree[offset] = f(self.tree[offset]);
+            return;
+        }
+        let len = self.shape[axis];
+        let mut idx = point[axis] + 1;
+        while idx <= len {
+            self.apply_from(axis + 1, offset * len + idx - 1, point, f);
+            idx += idx & idx.wrapping_neg();
+        }
+    }
+
+    /// Sum of the box of the first `counts[axis]` coordinates on every
+    /// axis
+    ///
+    /// Algorithmic complexity: O(log^D n)
+    pub fn prefix_sum(&self, counts: [usize; D]) -> T {
+        for (&count, &len) in counts.iter().zip(&self.shape) {
+            assert!(count <= len);
+        }
+        self.prefix_from(0, 0, counts)
+    }
+
+    fn prefix_from(&self, axis: usize, offset: usize, counts: [usize; D]) -> T {
+        if axis == D {
+            return self.tree[offset];
+        }
+        let mut sum = T::zero();
+        let mut idx = counts[axis];
+        while idx > 0 {
+            sum = sum + self.prefix_from(axis + 1, offset * self.shape[axis] + idx - 1, counts);
+            idx &= idx - 1;
+        }
+        sum
+    }
+
+    /// Sum of the box between the corners `start` and `end`, both
+    /// inclusive
+    ///
+    /// Algorithmic complexity: O(2^D log^D n)
+    pub fn query(&self, start: [usize; D], end: [usize; D]) -> T {
+        for axis in 0..D {
+            assert!(start[axis] <= end[axis] && end[axis] < self.shape[axis]);
+        }
+        // Inclusion-exclusion over the corners, the terms of each sign are
+        // summed apart so unsigned elements never go negative
+        let (mut added, mut removed) = (T::zero(), T::zero());
+        for corner in 0..1usize << D {
+            let counts = core::array::from_fn(|axis| match corner >> axis & 1 {
+                0 => end[axis] + 1,
+                _ => start[axis],
+            });
+            if corner.count_ones() % 2 == 0 {
+                added = added + self.prefix_sum(counts);
+            } else {
+                removed = removed + self.prefix_sum(counts);
+            }
+        }
+        added - removed
+    }
+
+    /// Current element at `point`
+    ///
+    /// Algorithmic complexity: O(2^D log^D n)
+    pub fn get(&self, point: [usize; D]) -> T {
+        self.query(point, point)
+    }
+
+    /// Replace the element at `point` with `value`
+    ///
+    /// Algorithmic complexity: O(2^D log^D n)
+    pub fn update(&mut self, point: [usize; D], value: T) {
+        let old = self.get(point);
+        self.sub(point, old);
+        self.add(point, value);
+    }
+}
+
+impl<T, const D: usize> MemoryUsage for FenwickNd<T, D> {
+    fn heap_bytes(&self) -> usize {
+        self.tree.heap_bytes()
+    }
+}
+
+/// Atomic integers an [`AtomicFenwick`] can be built from
+#[cfg(target_has_atomic = "64")]
+pub trait AtomicInteger: Send + Sync {
+    type Value: Copy;
+
+    fn new(value: Self::Value) -> Self;
+
+    fn load(&self, ordering: Ordering) -> Self::Value;
+
+    fn fetch_add(&self, value: Self::Value, ordering: Ordering);
+
+    fn fetch_sub(&self, value: Self::Value, ordering: Ordering);
+
+    fn wrapping_add(a: Self::Value, b: Self::Value) -> Self::Value;
+
+    fn wrapping_sub(a: Self::Value, b: Self::Value) -> Self::Value;
+}
+
+macro_rules! impl_atomic_integer {
+    ($($atomic:ty => $value:ty),*) => {
+        $(
+            #[cfg(target_has_atomic = "64")]
+            impl AtomicInteger for $atomic {
+                type Value = $value;
+
+                fn new(value: $value) -> Self {
+                    <$atomic>::new(value)
+                }
+
+                fn load(&self, ordering: Ordering) -> $value {
+                    <$atomic>::load(self, ordering)
+                }
+
+                fn fetch_add(&self, value: $value, ordering: Ordering) {
+                    <$atomic>::fetch_add(self, value, ordering);
+                }
+
+                fn fetch_sub(&self, value: $value, ordering: Ordering) {
+                    <$atomic>::fetch_sub(self, value, ordering);
+                }
+
+                fn wrapping_add(a: $value, b: $value) -> $value {
+                    a.wrapping_add(b)
+                }
+
+                fn wrapping_sub(a: $value, b: $value) -> $value {
+                    a.wrapping_sub(b)
+                }
+            }
+        )*
+    };
+}
+
+impl_atomic_integer!(AtomicU64 => u64, AtomicI64 => i64);
+
+/// Fenwick tree of atomic counters, many threads can [`Self::add`] at once
+/// without locking
+///
+/// Every operation is `Relaxed`: a prefix sum read while other threads add
+/// may include some of their additions and not others, but once they are
+/// done every addition is counted. Arithmetic wraps on overflow.
+///
+/// ```
+/// use std::sync::atomic::AtomicU64;
+/// use kuehree::fenwick::AtomicFenwick;
+///
+/// let counters = AtomicFenwick::<AtomicU64>::new(8);
+/// std::thread::scope(|scope| {
+///     for thread in 0..4 {
+///         let counters = &counters;
+///         scope.spawn(move || counters.add(thread, 1));
+///     }
+/// });
+/// assert_eq!(counters.prefix_sum(8), 4);
+/// ```
+#[cfg(target_has_atomic = "64")]
+#[derive(Debug)]
+pub struct AtomicFenwick<A> {
+    /// Laid out like the nodes of [`Fenwick`]
+    tree: Box<[A]>,
+}
+
+#[cfg(target_has_atomic = "64")]
+impl<A: AtomicInteger> AtomicFenwick<A>
+where
+    A::Value: Zero,
+{
+    /// `len` counters, all zero
+    pub fn new(len: usize) -> Self {
+        Self {
+            tree: (0..=len).map(|_| A::new(A::Value::zero())).collect(),
+        }
+    }
+
+    /// Number of counters
+    pub fn len(&self) -> usize {
+        self.tree.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add `delta` to the counter at `index`
+    ///
+    /// Algorithmic complexity: O(log n) atomic additions
+    pub fn add(&self, index: usize, delta: A::Value) {
+        assert!(index < self.len());
+        let mut idx = index + 1;
+        while idx < self.tree.len() {
+            self.tree[idx].fetch_add(delta, Ordering::Relaxed);
+            idx += idx & idx.wrapping_neg();
+        }
+    }
+
+    /// Subtract `delta` from the counter at `index`
+    ///
+    /// Algorithmic complexity: O(log n) atomic subtractions
+    pub fn sub(&self, index: usize, delta: A::Value) {
+        assert!(index < self.len());
+        let mut idx = index + 1;
+        while idx < self.tree.len() {
+            self.tree[idx].fetch_sub(delta, Ordering::Relaxed);
+            idx += idx & idx.wrapping_neg();
+        }
+    }
+
+    /// Sum of the first `count` counters
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn prefix_sum(&self, count: usize) -> A::Value {
+        assert!(count <= self.len());
+        let mut sum = A::Value::zero();
+        let mut idx = count;
+        while idx > 0 {
+            sum = A::wrapping_add(sum, self.tree[idx].load(Ordering::Relaxed));
+            idx &= idx - 1;
+        }
+        sum
+    }
+
+    /// Query between start range and end range
+    ///
+    /// The result is the wrapping difference of two prefix sums read one
+    /// after the other. While other threads add, the second read may see
+    /// additions the first missed, so the difference can fall below zero
+    /// and wrap, e.g. to a value near `u64::MAX` for [`AtomicU64`]. It is
+    /// exact once the additions are done.
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, start: usize, end: usize) -> A::Value {
+        assert!(end >= start && end < self.len());
+        A::wrapping_sub(self.prefix_sum(end + 1), self.prefix_sum(start))
+    }
+}
+
+#[cfg(target_has_atomic = "64")]
+impl<A: AtomicInteger> RangeQuery<A::Value> for AtomicFenwick<A>
+where
+    A::Value: Zero,
+{
+    fn query(&self, start: usize, end: usize) -> A::Value {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(target_has_atomic = "64")]
+impl<A> MemoryUsage for AtomicFenwick<A> {
+    fn heap_bytes(&self) -> usize {
+        self.tree.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fenwick() {
+        let mut arr = [1u32, 3, 4, 8, 6, 1, 4, 2];
+        let mut fenwick = Fenwick::new(arr);
+        fenwick.update(3, 0);
+        fenwick.add(6, 5);
+        fenwick.sub(0, 1);
+        arr[3] = 0;
+        arr[6] += 5;
+        arr[0] -= 1;
+
+        for start in 0..arr.len() {
+            for end in start..arr.len() {
+                assert_eq!(
+                    fenwick.query(start, end),
+                    arr[start..=end].iter().sum::<u32>()
+                );
+            }
+        }
+        assert_eq!(fenwick.prefix_sum(0), 0);
+        assert!(Fenwick::<u8>::default().is_empty());
+    }
+
+    #[test]
+    fn test_fenwick_nd() {
+        let shape = [3, 4, 5];
+        let mut cells: Vec<u64> = (0..60).map(|i| i * 7 % 11).collect();
+        let mut cube = FenwickNd::new(shape, cells.iter().copied());
+        cube.update([2, 1, 3], 40);
+        cube.add([0, 3, 4], 6);
+        cube.sub([1, 0, 0], cells[20]);
+        cells[2 * 20 + 5 + 3] = 40;
+        cells[3 * 5 + 4] += 6;
+        cells[20] = 0;
+
+        let brute = |start: [usize; 3], end: [usize; 3]| {
+            let mut sum = 0;
+            for x in start[0]..=end[0] {
+                for y in start[1]..=end[1] {
+                    for z in start[2]..=end[2] {
+                        sum += cells[x * 20 + y * 5 + z];
+                    }
+                }
+            }
+            sum
+        };
+        for start in [[0, 0, 0], [1, 1, 2], [2, 3, 4], [0, 2, 1]] {
+            for end in [[2, 3, 4], [1, 3, 2], [2, 3, 4], [0, 2, 1]] {
+                if (0..3).all(|axis| start[axis] <= end[axis]) {
+                    assert_eq!(cube.query(start, end), brute(start, end));
+                }
+            }
+        }
+        assert_eq!(cube.get([2, 1, 3]), 40);
+        assert_eq!(cube.prefix_sum([3, 4, 5]), cells.iter().sum::<u64>());
+        assert_eq!(cube.len(), 60);
+
+        let mut plane = FenwickNd::<i32, 2>::with_shape([2, 3]);
+        plane.add([1, 2], -4);
+        assert_eq!(plane, FenwickNd::new([2, 3], [0, 0, 0, 0, 0, -4]));
+    }
+
+    #[test]
+    fn test_atomic_fenwick() {
+        let counters = AtomicFenwick::<AtomicI64>::new(100);
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let counters = &counters;
+                scope.spawn(move || {
+                    for index in 0..100 {
+                        counters.add(index, index as i64);
+                        counters.sub(index, thread);
+                    }
+                });
+            }
+        });
+        assert_eq!(counters.query(0, 99), 4 * 4950 - 6 * 100);
+        assert_eq!(counters.query(10, 10), 4 * 10 - 6);
+
+        let unsigned = AtomicFenwick::<AtomicU64>::new(3);
+        unsigned.sub(1, 2);
+        assert_eq!(unsigned.query(1, 2), u64::MAX - 1);
+        unsigned.add(2, 5);
+        assert_eq!(unsigned.query(0, 2), 3);
+    }
+}
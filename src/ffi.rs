@@ -0,0 +1,196 @@
+//! C API, enabled with the `ffi` feature
+//!
+//! Every structure is an opaque handle created by a `*_new` function and
+//! released with the matching `*_free`. Queries write their answer through
+//! an out pointer and return `false` instead of panicking when the range is
+//! invalid.
+//!
+//! ```c
+//! double data[] = {1, 3, 4, 8, 6, 1, 4, 2};
+//! KuehreeSumQueryF64 *sum = kuehree_sum_query_f64_new(data, 8);
+//! double out;
+//! if (kuehree_sum_query_f64_query(sum, 3, 6, &out)) {
+//!     printf("%f\n", out);
+//! }
+//! kuehree_sum_query_f64_free(sum);
+//! ```
+
+#![allow(unsafe_code)]
+
+use core::slice;
+
+use crate::{
+    rmq::{Max, Min, SparseTable},
+    IndexableSumQuery, RangeQuery, SumQuery,
+};
+
+/// Borrows `len` elements starting at `data`, accepting null for empty input
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` elements unless `len` is 0
+unsafe fn borrow<'a, T>(data: *const T, len: usize) -> &'a [T] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
+
+macro_rules! ffi_structure {
+    (
+        $handle:ident, $inner:ty, $elem:ty,
+        $new:ident, $query:ident, $len_fn:ident, $free:ident
+    ) => {
+        /// Opaque handle
+        pub struct $handle {
+            inner: $inner,
+        }
+
+        /// Build a new handle from `len` elements starting at `data`
+        ///
+        /// # Safety
+        ///
+        /// `data` must be valid for reads of `len` elements unless `len` is 0
+        #[no_mangle]
+        pub unsafe extern "C" fn $new(data: *const $elem, len: usize) -> *mut $handle {
+            let data = borrow(data, len);
+            Box::into_raw(Box::new($handle {
+                inner: <$inner>::new(data.iter().copied()),
+            }))
+        }
+
+        /// Query between start range and end range, writing the answer to `out`
+        ///
+        /// Returns `false` and leaves `out` untouched if the range is invalid
+        ///
+        /// # Safety
+        ///
+        /// `handle` must come from the matching `*_new` function and not have
+        /// been freed, `out` must be valid for writes
+        #[no_mangle]
+        pub unsafe extern "C" fn $query(
+            handle: *const $handle,
+            start: usize,
+            end: usize,
+            out: *mut $elem,
+        ) -> bool {
+            match (*handle).inner.try_query(start, end) {
+                Ok(answer) => {
+                    *out = answer;
+                    true
+                }
+                Err(_) => false,
+            }
+        }
+
+        /// Number of elements the handle was built from
+        ///
+        /// # Safety
+        ///
+        /// `handle` must come from the matching `*_new` function and not have
+        /// been freed
+        #[no_mangle]
+        pub unsafe extern "C" fn $len_fn(handle: *const $handle) -> usize {
+            (*handle).inner.len()
+        }
+
+        /// Release a handle, passing null is a no-op
+        ///
+        /// # Safety
+        ///
+        /// `handle` must come from the matching `*_new` function and not have
+        /// been freed
+        #[no_mangle]
+        pub unsafe extern "C" fn $free(handle: *mut $handle) {
+            if !handle.is_null() {
+                drop(Box::from_raw(handle));
+            }
+        }
+    };
+}
+
+ffi_structure!(
+    KuehreeSumQueryF64,
+    SumQuery<Vec<f64>>,
+    f64,
+    kuehree_sum_query_f64_new,
+    kuehree_sum_query_f64_query,
+    kuehree_sum_query_f64_len,
+    kuehree_sum_query_f64_free
+);
+ffi_structure!(
+    KuehreeSumQueryI64,
+    SumQuery<Vec<i64>>,
+    i64,
+    kuehree_sum_query_i64_new,
+    kuehree_sum_query_i64_query,
+    kuehree_sum_query_i64_len,
+    kuehree_sum_query_i64_free
+);
+ffi_structure!(
+    KuehreeRangeMinF64,
+    SparseTable<f64, Min>,
+    f64,
+    kuehree_range_min_f64_new,
+    kuehree_range_min_f64_query,
+    kuehree_range_min_f64_len,
+    kuehree_range_min_f64_free
+);
+ffi_structure!(
+    KuehreeRangeMaxF64,
+    SparseTable<f64, Max>,
+    f64,
+    kuehree_range_max_f64_new,
+    kuehree_range_max_f64_query,
+    kuehree_range_max_f64_len,
+    kuehree_range_max_f64_free
+);
+ffi_structure!(
+    KuehreeRangeMinI64,
+    SparseTable<i64, Min>,
+    i64,
+    kuehree_range_min_i64_new,
+    kuehree_range_min_i64_query,
+    kuehree_range_min_i64_len,
+    kuehree_range_min_i64_free
+);
+ffi_structure!(
+    KuehreeRangeMaxI64,
+    SparseTable<i64, Max>,
+    i64,
+    kuehree_range_max_i64_new,
+    kuehree_range_max_i64_query,
+    kuehree_range_max_i64_len,
+    kuehree_range_max_i64_free
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data = [1i64, 3, 4, 8, 6, 1, 4, 2];
+        let mut out = 0;
+
+        unsafe {
+            let sum = kuehree_sum_query_i64_new(data.as_ptr(), data.len());
+            assert_eq!(kuehree_sum_query_i64_len(sum), 8);
+            assert!(kuehree_sum_query_i64_query(sum, 3, 6, &mut out));
+            assert_eq!(out, 19);
+            assert!(!kuehree_sum_query_i64_query(sum, 3, 8, &mut out));
+            assert!(!kuehree_sum_query_i64_query(sum, 6, 3, &mut out));
+            kuehree_sum_query_i64_free(sum);
+
+            let max = kuehree_range_max_i64_new(data.as_ptr(), data.len());
+            assert!(kuehree_range_max_i64_query(max, 0, 2, &mut out));
+            assert_eq!(out, 4);
+            kuehree_range_max_i64_free(max);
+
+            let empty = kuehree_range_min_f64_new(core::ptr::null(), 0);
+            assert_eq!(kuehree_range_min_f64_len(empty), 0);
+            kuehree_range_min_f64_free(empty);
+        }
+    }
+}
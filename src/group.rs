@@ -0,0 +1,343 @@
+//! Invertible operations, answered in O(1) from prefix totals
+//!
+//! Removing a prefix from a running total needs an inverse, so only
+//! [`Group`] operations get a [`PrefixQuery`]. [`Backend`] names the
+//! structure each operation is best answered with, letting [`build`] route
+//! groups to prefix totals and the others to sparse tables or segment
+//! trees.
+
+use core::ops::{Add, BitXor, Sub};
+#[cfg(feature = "alloc")]
+use core::{
+    marker::PhantomData,
+    ops::{BitAnd, BitOr, Mul, Rem},
+};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::{
+    rmq::{And, Gcd, Idempotent, Lcm, LcmValue, Max, Min, Or, SegmentTree, SparseTable},
+    Error, MemoryUsage, RangeQuery,
+};
+use crate::{
+    rmq::{Operation, Prod, Sum, Xor},
+    Modular, One, Zero,
+};
+
+/// Associative operation with an identity and an inverse for every
+/// element
+pub trait Group<T>: Operation<T> {
+    fn identity() -> T;
+
+    fn inverse(a: T) -> T;
+
+    /// Whether `a` has an inverse, every element of a true group does
+    fn is_invertible(_a: &T) -> bool {
+        true
+    }
+
+    /// `total` with `prefix` removed from its front, i.e. the `x` with
+    /// `combine(prefix, x) == total`
+    fn difference(total: T, prefix: T) -> T {
+        Self::combine(Self::inverse(prefix), total)
+    }
+}
+
+impl<T> Group<T> for Sum
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn identity() -> T {
+        T::zero()
+    }
+
+    fn inverse(a: T) -> T {
+        T::zero() - a
+    }
+
+    fn difference(total: T, prefix: T) -> T {
+        total - prefix
+    }
+}
+
+/// Every element is its own inverse
+impl<T: BitXor<Output = T> + Zero> Group<T> for Xor {
+    fn identity() -> T {
+        T::zero()
+    }
+
+    fn inverse(a: T) -> T {
+        a
+    }
+}
+
+/// Products modulo a prime, which excludes zero elements, so a
+/// [`PrefixQuery`] rejects them and [`build`] routes [`Prod`] to a
+/// [`SegmentTree`] instead
+impl<const P: u64> Group<Modular<P>> for Prod {
+    fn identity() -> Modular<P> {
+        Modular::one()
+    }
+
+    /// # Panics
+    ///
+    /// If `a` is zero
+    fn inverse(a: Modular<P>) -> Modular<P> {
+        a.inverse().expect("zero has no multiplicative inverse")
+    }
+
+    fn is_invertible(a: &Modular<P>) -> bool {
+        *a != Modular::zero()
+    }
+}
+
+/// Running totals of a [`Group`] operation, any range is the difference
+/// of two of them
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PrefixQuery<T, G> {
+    /// Starts with the identity, so `totals[i]` covers the first `i`
+    /// elements
+    totals: Vec<T>,
+    _phantom: PhantomData<G>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy, G: Group<T>> PrefixQuery<T, G> {
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Panics
+    ///
+    /// If an element has no inverse, e.g. a zero in a product modulo a
+    /// prime, as the ranges after it could not be answered
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        match Self::try_new(data) {
+            Ok(query) => query,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Construct `Self`, checking that every element has an inverse
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Errors
+    ///
+    /// [`Error::NotInvertible`] at the first element without an inverse,
+    /// use a [`SegmentTree`] for such data
+    pub fn try_new(data: impl IntoIterator<Item = T>) -> Result<Self, Error> {
+        let data = data.into_iter();
+        let mut totals = Vec::with_capacity(data.size_hint().0 + 1);
+        let mut total = G::identity();
+        totals.push(total);
+        for (index, value) in data.enumerate() {
+            if !G::is_invertible(&value) {
+                return Err(Error::NotInvertible { index });
+            }
+            total = G::combine(total, value);
+            totals.push(total);
+        }
+        Ok(Self {
+            totals,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.totals.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len());
+        G::difference(self.totals[end + 1], self.totals[start])
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy, G: Group<T>> FromIterator<T> for PrefixQuery<T, G> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy, G: Group<T>> RangeQuery<T> for PrefixQuery<T, G> {
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, G> MemoryUsage for PrefixQuery<T, G> {
+    fn heap_bytes(&self) -> usize {
+        self.totals.heap_bytes()
+    }
+}
+
+/// Structure answering the range queries of an operation
+///
+/// Groups use a [`PrefixQuery`], the idempotent [`Max`], [`Min`],
+/// [`Gcd`], [`Lcm`], [`And`] and [`Or`] a [`SparseTable`] and [`Prod`],
+/// which is only a group modulo a prime, a [`SegmentTree`].
+/// Implement it for your own operations to use them with [`build`].
+#[cfg(feature = "alloc")]
+pub trait Backend<T>: Operation<T> {
+    type Table: RangeQuery<T>;
+
+    fn build(data: impl IntoIterator<Item = T>) -> Self::Table;
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Backend<T> for Sum
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    type Table = PrefixQuery<T, Self>;
+
+    fn build(data: impl IntoIterator<Item = T>) -> Self::Table {
+        PrefixQuery::new(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy + BitXor<Output = T> + Zero> Backend<T> for Xor {
+    type Table = PrefixQuery<T, Self>;
+
+    fn build(data: impl IntoIterator<Item = T>) -> Self::Table {
+        PrefixQuery::new(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy + PartialOrd> Backend<T> for Max {
+    type Table = SparseTable<T, Self>;
+
+    fn build(data: impl IntoIterator<Item = T>) -> Self::Table {
+        SparseTable::new(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy + PartialOrd> Backend<T> for Min {
+    type Table = SparseTable<T, Self>;
+
+    fn build(data: impl IntoIterator<Item = T>) -> Self::Table {
+        SparseTable::new(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Backend<T> for Gcd
+where
+    T: Copy + PartialEq + Rem<Output = T> + Zero,
+{
+    type Table = SparseTable<T, Self>;
+
+    fn build(data: impl IntoIterator<Item = T>) -> Self::Table {
+        SparseTable::new(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy + BitAnd<Output = T>> Backend<T> for And {
+    type Table = SparseTable<T, Self>;
+
+    fn build(data: impl IntoIterator<Item = T>) -> Self::Table {
+        SparseTable::new(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy + BitOr<Output = T>> Backend<T> for Or {
+    type Table = SparseTable<T, Self>;
+
+    fn build(data: impl IntoIterator<Item = T>) -> Self::Table {
+        SparseTable::new(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy> Backend<LcmValue<T>> for Lcm
+where
+    Self: Idempotent<LcmValue<T>>,
+{
+    type Table = SparseTable<LcmValue<T>, Self>;
+
+    fn build(data: impl IntoIterator<Item = LcmValue<T>>) -> Self::Table {
+        SparseTable::new(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy + Mul<Output = T>> Backend<T> for Prod {
+    type Table = SegmentTree<T, Self>;
+
+    fn build(data: impl IntoIterator<Item = T>) -> Self::Table {
+        SegmentTree::new(data)
+    }
+}
+
+/// Build the structure [`Backend`] picks for `M` over `data`
+///
+/// Algorithmic complexity: that of the chosen structure
+///
+/// ```
+/// use kuehree::{group, rmq::{Max, Xor}, RangeQuery};
+///
+/// let flags = group::build::<_, Xor>([0b0110u8, 0b0011, 0b1000]);
+/// assert_eq!(flags.query(0, 1), 0b0101);
+/// let peak = group::build::<_, Max>([3, 9, 4]);
+/// assert_eq!(peak.query(1, 2), 9);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn build<T, M: Backend<T>>(data: impl IntoIterator<Item = T>) -> M::Table {
+    M::build(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_group() {
+        let data = [3i32, -1, 4, 1, -5, 9];
+        let sums = PrefixQuery::<_, Sum>::new(data);
+        let xors = build::<_, Xor>(data);
+        for start in 0..data.len() {
+            for end in start..data.len() {
+                let range = &data[start..=end];
+                assert_eq!(sums.query(start, end), range.iter().sum::<i32>());
+                assert_eq!(xors.query(start, end), range.iter().fold(0, |a, &b| a ^ b));
+            }
+        }
+        assert_eq!(<Sum as Group<u8>>::difference(7, 3), 4);
+
+        type Mod = Modular<1_000_000_007>;
+        let factors = [2, 3, 5, 7, 11].map(Mod::new);
+        let products = PrefixQuery::<_, Prod>::new(factors);
+        assert_eq!(products.query(1, 3), Mod::new(105));
+        assert_eq!(products.query(4, 4), Mod::new(11));
+        assert_eq!(
+            PrefixQuery::<_, Prod>::try_new([2, 0, 3].map(Mod::new)),
+            Err(Error::NotInvertible { index: 1 })
+        );
+        assert_eq!(build::<_, Prod>([2u64, 0, 3]).query(2, 2), 3);
+        assert_eq!(build::<_, Gcd>([4u8, 6, 9]).query(0, 1), 2);
+    }
+}
@@ -0,0 +1,214 @@
+//! Convex hull trick, the best of a set of lines at a point
+
+use core::{
+    marker::PhantomData,
+    ops::{Add, Mul, Sub},
+};
+
+use alloc::vec::Vec;
+
+use crate::{rmq::Operation, MemoryUsage};
+
+/// Lines `y = slope * x + intercept` added in sorted slope order, answering
+/// the [`Max`](crate::rmq::Max) or [`Min`](crate::rmq::Min) of their values
+/// at a point
+///
+/// For `Max` slopes must increase and for `Min` decrease, so every new
+/// line wins for large enough `x`. Lines that are never the best are
+/// dropped as they are overtaken, so adding is amortized O(1). Points
+/// queried in increasing order walk the hull with a cursor in amortized
+/// O(1), arbitrary points binary search it in O(log n).
+///
+/// Elements must be signed integers or floats, use `i128` if products of
+/// slopes and intercepts can overflow.
+///
+/// ```
+/// use kuehree::{hull::MonotoneHull, rmq::Max};
+///
+/// let mut hull = MonotoneHull::<i64, Max>::new();
+/// hull.push(-1, 10);
+/// hull.push(0, 4);
+/// hull.push(2, -6);
+/// assert_eq!(hull.query(0), Some(10));
+/// assert_eq!(hull.query(5), Some(5));
+/// assert_eq!(hull.query(9), Some(12));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MonotoneHull<T, M> {
+    /// `(slope, intercept)` of the lines of the hull
+    lines: Vec<(T, T)>,
+    /// Best line at the last point given to [`Self::query_monotone`]
+    cursor: usize,
+    _phantom: PhantomData<M>,
+}
+
+impl<T, M> MonotoneHull<T, M> {
+    /// Construct an empty `Self`
+    pub fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            cursor: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Number of lines on the hull, lines never the best are not counted
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// `(slope, intercept)` of the lines on the hull, in insertion order
+    pub fn lines(&self) -> &[(T, T)] {
+        &self.lines
+    }
+}
+
+impl<T, M> MonotoneHull<T, M>
+where
+    T: Copy + PartialOrd + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+    M: Operation<T>,
+{
+    fn value((slope, intercept): (T, T), x: T) -> T {
+        slope * x + intercept
+    }
+
+    /// Whether `M` picks `b` over `a`, ties included
+    fn prefers(a: T, b: T) -> bool {
+        M::combine(a, b) == b
+    }
+
+    /// Add the line `slope * x + intercept`
+    ///
+    /// Algorithmic complexity: amortized O(1)
+    ///
+    /// # Panics
+    ///
+    /// If `slope` is not sorted after the slopes added so far
+    pub fn push(&mut self, slope: T, intercept: T) {
+        if let Some(&(last_slope, last_intercept)) = self.lines.last() {
+            if slope == last_slope {
+                // Parallel lines, only the better intercept matters
+                if !Self::prefers(last_intercept, intercept) {
+                    return;
+                }
+                self.lines.pop();
+            } else {
+                assert!(
+                    Self::prefers(last_slope, slope),
+                    "slopes must be added in sorted order"
+                );
+            }
+        }
+        // The last line is useless once the new one overtakes the line
+        // before it no later than the last line does
+        while let [.., first, middle] = self.lines[..] {
+            let overtaken_by_new = (intercept - first.1) * (first.0 - middle.0);
+            let overtaken_by_middle = (middle.1 - first.1) * (first.0 - slope);
+            if overtaken_by_new > overtaken_by_middle {
+                break;
+            }
+            self.lines.pop();
+        }
+        self.lines.push((slope, intercept));
+        self.cursor = self.cursor.min(self.lines.len() - 1);
+    }
+
+    /// Best value of the lines at `x`, `None` if there are no lines
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, x: T) -> Option<T> {
+        // Lines are the best on consecutive intervals of `x`, find the
+        // first one its successor does not beat
+        let (mut lo, mut hi) = (0, self.lines.len().checked_sub(1)?);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let (line, next) = (self.lines[mid], self.lines[mid + 1]);
+            if Self::prefers(Self::value(line, x), Self::value(next, x)) {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        Some(Self::value(self.lines[lo], x))
+    }
+
+    /// Best value of the lines at `x`, for `x` never smaller than at the
+    /// previous call
+    ///
+    /// Algorithmic complexity: amortized O(1)
+    pub fn query_monotone(&mut self, x: T) -> Option<T> {
+        while let [line, next, ..] = self.lines[self.cursor..] {
+            if !Self::prefers(Self::value(line, x), Self::value(next, x)) {
+                break;
+            }
+            self.cursor += 1;
+        }
+        self.lines
+            .get(self.cursor)
+            .map(|&line| Self::value(line, x))
+    }
+}
+
+impl<T, M> Default for MonotoneHull<T, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, M> MemoryUsage for MonotoneHull<T, M> {
+    fn heap_bytes(&self) -> usize {
+        self.lines.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmq::{Max, Min};
+
+    #[test]
+    fn test_hull() {
+        let lines: Vec<(i64, i64)> = (-20..20)
+            .map(|slope| (slope, (slope * 37 % 23) * 9 - slope * slope))
+            .collect();
+        let mut upper = MonotoneHull::<_, Max>::new();
+        let mut lower = MonotoneHull::<_, Min>::new();
+        for (added, &(slope, intercept)) in lines.iter().enumerate() {
+            upper.push(slope, intercept);
+            upper.push(slope, intercept - 1);
+            let (slope, intercept) = lines[lines.len() - 1 - added];
+            lower.push(slope, intercept);
+        }
+        assert!(upper.len() < lines.len());
+
+        let at = |x: i64| {
+            lines
+                .iter()
+                .map(move |&(slope, intercept)| slope * x + intercept)
+        };
+        for x in -30..30 {
+            assert_eq!(upper.query(x), at(x).max());
+            assert_eq!(upper.query_monotone(x), at(x).max());
+            assert_eq!(lower.query(x), at(x).min());
+            assert_eq!(lower.query_monotone(x), at(x).min());
+        }
+
+        let mut floats = MonotoneHull::<f64, Min>::default();
+        assert_eq!(floats.query(1.0), None);
+        floats.push(0.5, 1.0);
+        floats.push(-0.5, 2.0);
+        assert_eq!(floats.query(3.0), Some(0.5));
+    }
+
+    #[test]
+    #[should_panic(expected = "sorted order")]
+    fn test_unsorted_slopes() {
+        let mut hull = MonotoneHull::<i32, Max>::new();
+        hull.push(3, 0);
+        hull.push(1, 0);
+    }
+}
@@ -0,0 +1,140 @@
+//! Query counters and build timings, enabled with the `instrument` feature
+
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::{MemoryUsage, RangeQuery};
+
+/// Number of range length buckets, bucket `k` counts lengths in
+/// `[2^k, 2^(k + 1))`
+pub const BUCKETS: usize = usize::BITS as usize;
+
+/// Snapshot of the statistics recorded by an [`Instrumented`] structure
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Stats {
+    /// Number of queries answered
+    pub queries: u64,
+    /// Histogram of range lengths, see [`BUCKETS`]
+    pub range_lengths: [u64; BUCKETS],
+    /// Time taken by the build closure, `None` when wrapped with
+    /// [`Instrumented::new`]
+    pub build_time: Option<Duration>,
+}
+
+/// Wraps a range query structure, recording every query it answers
+///
+/// Counters are atomics, so the wrapper stays `Sync` and can be shared
+/// across threads.
+#[derive(Debug)]
+pub struct Instrumented<Q> {
+    inner: Q,
+    queries: AtomicU64,
+    range_lengths: [AtomicU64; BUCKETS],
+    build_time: Option<Duration>,
+}
+
+impl<Q> Instrumented<Q> {
+    /// Wrap an already built structure
+    pub fn new(inner: Q) -> Self {
+        Self {
+            inner,
+            queries: AtomicU64::new(0),
+            range_lengths: [const { AtomicU64::new(0) }; BUCKETS],
+            build_time: None,
+        }
+    }
+
+    /// Build the structure with `build`, timing it
+    pub fn build(build: impl FnOnce() -> Q) -> Self {
+        let started = Instant::now();
+        let inner = build();
+        Self {
+            build_time: Some(started.elapsed()),
+            ..Self::new(inner)
+        }
+    }
+
+    /// Statistics recorded so far
+    pub fn stats(&self) -> Stats {
+        Stats {
+            queries: self.queries.load(Ordering::Relaxed),
+            range_lengths: core::array::from_fn(|k| self.range_lengths[k].load(Ordering::Relaxed)),
+            build_time: self.build_time,
+        }
+    }
+
+    /// Clear the query counters, keeping the build time
+    pub fn reset(&self) {
+        self.queries.store(0, Ordering::Relaxed);
+        for bucket in &self.range_lengths {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn inner(&self) -> &Q {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> Q {
+        self.inner
+    }
+
+    fn record(&self, start: usize, end: usize) {
+        let len = end.saturating_sub(start) + 1;
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        self.range_lengths[len.ilog2() as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl<T, Q: RangeQuery<T>> RangeQuery<T> for Instrumented<Q> {
+    /// Counts the query once the inner query returns, a query that panics
+    /// is not counted
+    fn query(&self, start: usize, end: usize) -> T {
+        let answer = self.inner.query(start, end);
+        self.record(start, end);
+        answer
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<Q: MemoryUsage> MemoryUsage for Instrumented<Q> {
+    fn heap_bytes(&self) -> usize {
+        self.inner.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{rmq::Max, rmq::SparseTable, IndexableSumQuery, SumQuery};
+
+    #[test]
+    fn test_stats() {
+        let sum = Instrumented::build(|| SumQuery::<Vec<_>>::new([1u32, 3, 4, 8, 6, 1, 4, 2]));
+        assert_eq!(sum.query(3, 6), 19);
+        assert_eq!(sum.query(0, 0), 1);
+        assert_eq!(sum.query(0, 7), 29);
+        assert!(sum.try_query(4, 8).is_err());
+
+        let stats = sum.stats();
+        assert_eq!(stats.queries, 3);
+        assert_eq!(stats.range_lengths[..4], [1, 0, 1, 1]);
+        assert!(stats.build_time.is_some());
+
+        sum.reset();
+        assert_eq!(sum.stats().queries, 0);
+
+        let table = Instrumented::new(SparseTable::<u8, Max>::new([1, 2]));
+        assert_eq!(table.query(0, 1), 2);
+        assert_eq!(table.stats().build_time, None);
+
+        let panicked = std::panic::catch_unwind(|| table.query(1, 5));
+        assert!(panicked.is_err());
+        assert_eq!(table.stats().queries, 1);
+    }
+}
@@ -0,0 +1,349 @@
+//! Segment tree with lazy propagation, for updates over whole ranges
+
+use core::{marker::PhantomData, ops::Add};
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    rmq::{Max, Min, Operation},
+    trace::BuildSpan,
+    MemoryUsage, RangeQuery, UpdatableRangeQuery,
+};
+
+/// Update applied to every element of a range, and to the fold of a range
+/// in one step
+///
+/// Applying an action to the fold of `len` elements has to give the fold
+/// of the updated elements, e.g. adding `x` to each element adds `len * x`
+/// to their sum.
+pub trait Action<T>: Copy {
+    /// Fold of `len` elements after applying `self` to each of them, given
+    /// their fold `value` before
+    fn apply(self, value: T, len: usize) -> T;
+
+    /// Action applying `inner` first and then `self`
+    fn compose(self, inner: Self) -> Self;
+}
+
+/// Adds the same amount to every element of a range
+///
+/// The minimum or maximum of a range moves by that amount whatever its
+/// length, so this is the action of [`RangeAddMin`] and [`RangeAddMax`].
+/// It does not fit sums, use [`Affine`](crate::affine::Affine) with `a = 1`
+/// for those.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Shift<T>(pub T);
+
+impl<T: Copy + Add<Output = T>> Action<T> for Shift<T> {
+    fn apply(self, value: T, _len: usize) -> T {
+        value + self.0
+    }
+
+    fn compose(self, inner: Self) -> Self {
+        Self(inner.0 + self.0)
+    }
+}
+
+/// Range add updates with range minimum queries
+///
+/// ```
+/// use kuehree::lazy::{RangeAddMin, Shift};
+///
+/// let mut tree = RangeAddMin::new([5, 3, 8, 6]);
+/// tree.apply(0, 1, Shift(4));
+/// assert_eq!(tree.query(0, 3), 6);
+/// ```
+pub type RangeAddMin<T> = LazySegmentTree<T, Min, Shift<T>>;
+
+/// Range add updates with range maximum queries
+pub type RangeAddMax<T> = LazySegmentTree<T, Max, Shift<T>>;
+
+/// Segment tree folding ranges with `M` and applying `A` to whole ranges,
+/// both in O(log n)
+///
+/// Nodes hold the fold of their range with every action at or above them
+/// applied, while actions not yet pushed to the children wait in the node.
+#[derive(Debug, Clone)]
+pub struct LazySegmentTree<T, M, A> {
+    len: usize,
+    /// Node `i` has children `2i` and `2i + 1`, the root is node 1
+    tree: Vec<T>,
+    pending: Vec<Option<A>>,
+    _phantom: PhantomData<M>,
+}
+
+impl<T, M, A> Default for LazySegmentTree<T, M, A> {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            tree: Vec::new(),
+            pending: Vec::new(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, M, A> LazySegmentTree<T, M, A>
+where
+    T: Copy,
+    M: Operation<T>,
+    A: Action<T>,
+{
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("LazySegmentTree");
+        let data: Vec<T> = data.into_iter().collect();
+        span.record_len(data.len());
+        let Some(&first) = data.first() else {
+            return Self::default();
+        };
+
+        let mut tree = Self {
+            len: data.len(),
+            tree: vec![first; 4 * data.len()],
+            pending: vec![None; 4 * data.len()],
+            _phantom: PhantomData,
+        };
+        tree.build(1, 0, data.len() - 1, &data);
+        tree
+    }
+
+    fn build(&mut self, node: usize, lo: usize, hi: usize, data: &[T]) {
+        if lo == hi {
+            self.tree[node] = data[lo];
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        self.build(2 * node, lo, mid, data);
+        self.build(2 * node + 1, mid + 1, hi, data);
+        self.pull(node);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn pull(&mut self, node: usize) {
+        self.tree[node] = M::combine(self.tree[2 * node], self.tree[2 * node + 1]);
+    }
+
+    fn apply_node(&mut self, node: usize, action: A, len: usize) {
+        self.tree[node] = action.apply(self.tree[node], len);
+        self.pending[node] = Some(match self.pending[node] {
+            Some(inner) => action.compose(inner),
+            None => action,
+        });
+    }
+
+    fn push(&mut self, node: usize, lo: usize, mid: usize, hi: usize) {
+        if let Some(action) = self.pending[node].take() {
+            self.apply_node(2 * node, action, mid + 1 - lo);
+            self.apply_node(2 * node + 1, action, hi - mid);
+        }
+    }
+
+    fn fold(&self, node: usize, lo: usize, hi: usize, start: usize, end: usize) -> T {
+        if start <= lo && hi <= end {
+            return self.tree[node];
+        }
+        let mid = lo + (hi - lo) / 2;
+        let (from, to) = (start.max(lo), end.min(hi));
+        let folded = if to <= mid {
+            self.fold(2 * node, lo, mid, start, end)
+        } else if from > mid {
+            self.fold(2 * node + 1, mid + 1, hi, start, end)
+        } else {
+            M::combine(
+                self.fold(2 * node, lo, mid, start, end),
+                self.fold(2 * node + 1, mid + 1, hi, start, end),
+            )
+        };
+        // Actions waiting here have not reached the children yet
+        match self.pending[node] {
+            Some(action) => action.apply(folded, to + 1 - from),
+            None => folded,
+        }
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len);
+        self.fold(1, 0, self.len - 1, start, end)
+    }
+
+    /// Current element at `index`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn get(&self, index: usize) -> T {
+        self.query(index, index)
+    }
+
+    fn apply_rec(
+        &mut self,
+        node: usize,
+        lo: usize,
+        hi: usize,
+        start: usize,
+        end: usize,
+        action: A,
+    ) {
+        if start <= lo && hi <= end {
+            self.apply_node(node, action, hi + 1 - lo);
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        self.push(node, lo, mid, hi);
+        if start <= mid {
+            self.apply_rec(2 * node, lo, mid, start, end, action);
+        }
+        if end > mid {
+            self.apply_rec(2 * node + 1, mid + 1, hi, start, end, action);
+        }
+        self.pull(node);
+    }
+
+    /// Apply `action` to every element between `start` and `end`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn apply(&mut self, start: usize, end: usize, action: A) {
+        assert!(end >= start && end < self.len);
+        self.apply_rec(1, 0, self.len - 1, start, end, action);
+    }
+
+    fn set(&mut self, node: usize, lo: usize, hi: usize, index: usize, value: T) {
+        if lo == hi {
+            self.tree[node] = value;
+            self.pending[node] = None;
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        self.push(node, lo, mid, hi);
+        if index <= mid {
+            self.set(2 * node, lo, mid, index, value);
+        } else {
+            self.set(2 * node + 1, mid + 1, hi, index, value);
+        }
+        self.pull(node);
+    }
+
+    /// Replace the element at `index` with `value`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn update(&mut self, index: usize, value: T) {
+        assert!(index < self.len);
+        self.set(1, 0, self.len - 1, index, value);
+    }
+}
+
+impl<T, M, A> RangeQuery<T> for LazySegmentTree<T, M, A>
+where
+    T: Copy,
+    M: Operation<T>,
+    A: Action<T>,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T, M, A> UpdatableRangeQuery<T> for LazySegmentTree<T, M, A>
+where
+    T: Copy,
+    M: Operation<T>,
+    A: Action<T>,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+}
+
+impl<T, M, A> MemoryUsage for LazySegmentTree<T, M, A> {
+    fn heap_bytes(&self) -> usize {
+        self.tree.heap_bytes() + self.pending.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{affine::Affine, rmq::Sum, Modular};
+
+    type Mod = Modular<998_244_353>;
+
+    #[test]
+    fn test_range_add() {
+        let mut arr: Vec<i64> = (0..29).map(|i| (i * 37 % 23) - 11).collect();
+        let mut min = RangeAddMin::new(arr.iter().copied());
+        let mut max = RangeAddMax::new(arr.iter().copied());
+        let mut seed = 54_321u64;
+        for step in 0..300 {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            let (a, b) = ((seed >> 33) as usize % 29, (seed >> 13) as usize % 29);
+            let (start, end) = (a.min(b), a.max(b));
+            if step % 7 == 6 {
+                arr[start] = (seed >> 50) as i64 - 8_000;
+                min.update(start, arr[start]);
+                max.update(start, arr[start]);
+            } else {
+                let amount = (seed >> 45) as i64 % 100 - 50;
+                arr[start..=end]
+                    .iter_mut()
+                    .for_each(|value| *value += amount);
+                min.apply(start, end, Shift(amount));
+                max.apply(start, end, Shift(amount));
+            }
+            let (a, b) = ((seed >> 40) as usize % 29, (seed >> 20) as usize % 29);
+            let (start, end) = (a.min(b), a.max(b));
+            let range = &arr[start..=end];
+            assert_eq!(min.query(start, end), *range.iter().min().unwrap());
+            assert_eq!(max.query(start, end), *range.iter().max().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_affine_sum() {
+        let mut arr: Vec<Mod> = (0..37u64).map(|i| Mod::new(i * i)).collect();
+        let mut tree = LazySegmentTree::<_, Sum, Affine<Mod>>::new(arr.iter().copied());
+        let mut seed = 12_345u64;
+        for step in 0..300 {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+            let (a, b) = ((seed >> 33) as usize % 37, (seed >> 13) as usize % 37);
+            let (start, end) = (a.min(b), a.max(b));
+            if step % 5 == 4 {
+                arr[start] = Mod::new(seed);
+                tree.update(start, arr[start]);
+            } else {
+                let map = Affine::new(Mod::new(seed >> 7), Mod::new(seed >> 3));
+                for value in &mut arr[start..=end] {
+                    *value = map.eval(*value);
+                }
+                tree.apply(start, end, map);
+            }
+            let (a, b) = ((seed >> 40) as usize % 37, (seed >> 20) as usize % 37);
+            let (start, end) = (a.min(b), a.max(b));
+            let expected = arr[start..=end]
+                .iter()
+                .fold(Mod::new(0), |acc, &value| acc + value);
+            assert_eq!(tree.query(start, end), expected);
+        }
+        for (index, &value) in arr.iter().enumerate() {
+            assert_eq!(tree.get(index), value);
+        }
+    }
+}
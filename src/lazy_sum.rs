@@ -0,0 +1,194 @@
+//! Prefix sums built on first use, block by block
+
+use core::{
+    cell::OnceCell,
+    marker::PhantomData,
+    ops::{Add, Sub},
+};
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{MemoryUsage, RangeQuery, Zero};
+
+/// Default number of elements per block
+const BLOCK_LEN: usize = 1024;
+
+/// Prefix sums over the raw data, materialized one block at a time when a
+/// query first touches the block
+///
+/// Only the blocks a query covers are ever summed, so a huge array of
+/// which a small region is queried costs little more than the data itself.
+/// The data can be borrowed, e.g. `LazySumQuery<u64, &[u64]>`.
+///
+/// Blocks are cached through shared references, so `Self` is not `Sync`.
+#[derive(Debug, Clone)]
+pub struct LazySumQuery<T, C = Vec<T>> {
+    data: C,
+    block_len: usize,
+    /// Running totals of each block, restarting at every block
+    blocks: Box<[OnceCell<Box<[T]>>]>,
+    _element: PhantomData<T>,
+}
+
+impl<T, C: AsRef<[T]>> LazySumQuery<T, C> {
+    /// Construct `Self` with the default block length, nothing is summed
+    /// yet
+    ///
+    /// Algorithmic complexity: O(n / block length)
+    pub fn new(data: C) -> Self {
+        Self::with_block_len(data, BLOCK_LEN)
+    }
+
+    /// Construct `Self` with `block_len` elements per block
+    ///
+    /// Algorithmic complexity: O(n / block length)
+    ///
+    /// # Panics
+    ///
+    /// If `block_len` is 0
+    pub fn with_block_len(data: C, block_len: usize) -> Self {
+        assert!(block_len > 0, "blocks need at least one element");
+        let blocks = data.as_ref().len().div_ceil(block_len);
+        Self {
+            data,
+            block_len,
+            blocks: (0..blocks).map(|_| OnceCell::new()).collect(),
+            _element: PhantomData,
+        }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.data.as_ref().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.as_ref().is_empty()
+    }
+
+    /// Number of elements per block
+    pub fn block_len(&self) -> usize {
+        self.block_len
+    }
+
+    /// Number of blocks whose sums have been materialized
+    pub fn materialized_blocks(&self) -> usize {
+        self.blocks
+            .iter()
+            .filter(|block| block.get().is_some())
+            .count()
+    }
+
+    /// The raw data back, dropping the sums
+    pub fn into_inner(self) -> C {
+        self.data
+    }
+}
+
+impl<T, C> LazySumQuery<T, C>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+    C: AsRef<[T]>,
+{
+    /// Running totals of block `block`, summing it on first use
+    fn block(&self, block: usize) -> &[T] {
+        self.blocks[block].get_or_init(|| {
+            let start = block * self.block_len;
+            let end = self.len().min(start + self.block_len);
+            let mut total = T::zero();
+            self.data.as_ref()[start..end]
+                .iter()
+                .map(|&value| {
+                    total = total + value;
+                    total
+                })
+                .collect()
+        })
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(end / block length - start / block
+    /// length), plus O(block length) for each block touched the first time
+    pub fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len());
+        let (first, last) = (start / self.block_len, end / self.block_len);
+        let offset = start % self.block_len;
+        let before = match offset {
+            0 => T::zero(),
+            _ => self.block(first)[offset - 1],
+        };
+        if first == last {
+            return self.block(first)[end % self.block_len] - before;
+        }
+
+        let head = self.block(first)[self.block_len - 1] - before;
+        let middle = (first + 1..last).fold(T::zero(), |sum, block| {
+            sum + self.block(block)[self.block_len - 1]
+        });
+        head + middle + self.block(last)[end % self.block_len]
+    }
+}
+
+impl<T> FromIterator<T> for LazySumQuery<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+impl<T, C> RangeQuery<T> for LazySumQuery<T, C>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+    C: AsRef<[T]>,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Counts the materialized blocks only
+impl<T, C: MemoryUsage> MemoryUsage for LazySumQuery<T, C> {
+    fn heap_bytes(&self) -> usize {
+        let blocks = self
+            .blocks
+            .iter()
+            .filter_map(OnceCell::get)
+            .map(|block| block.heap_bytes())
+            .sum::<usize>();
+        self.data.heap_bytes() + self.blocks.heap_bytes() + blocks
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lazy_sum() {
+        let arr: Vec<u32> = (0..50).map(|i| i * 7 % 13).collect();
+        let sums = LazySumQuery::with_block_len(arr.as_slice(), 8);
+        assert_eq!(sums.materialized_blocks(), 0);
+        assert_eq!(sums.query(9, 14), arr[9..=14].iter().sum::<u32>());
+        assert_eq!(sums.materialized_blocks(), 1);
+        assert_eq!(sums.query(20, 33), arr[20..=33].iter().sum::<u32>());
+        assert_eq!(sums.materialized_blocks(), 4);
+        for start in 0..arr.len() {
+            for end in start..arr.len() {
+                assert_eq!(sums.query(start, end), arr[start..=end].iter().sum::<u32>());
+            }
+        }
+        assert_eq!(sums.materialized_blocks(), 7);
+
+        let sums: LazySumQuery<u64> = (1..=1_000_000).collect();
+        assert_eq!(
+            sums.query(999_000, 999_999),
+            (999_001..=1_000_000).sum::<u64>()
+        );
+        assert_eq!(sums.materialized_blocks(), 2);
+        assert!(sums.heap_bytes() < 8_000_000 + 1_000 * 8 * 3 + 1_000 * 16);
+    }
+}
@@ -1,3 +1,5 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+#![deny(unsafe_code)]
 // Forbid warnings in release builds:
 #![cfg_attr(not(debug_assertions), deny(warnings))]
 #![warn(
@@ -8,7 +10,7 @@
     clippy::dbg_macro,
     clippy::debug_assert_with_mut_call,
     clippy::doc_markdown,
-    clippy::empty_enum,
+    clippy::empty_enums,
     clippy::enum_glob_use,
     clippy::exit,
     clippy::expl_impl_clone_on_copy,
@@ -19,6 +21,7 @@
     clippy::float_cmp_const,
     clippy::fn_params_excessive_bools,
     clippy::if_let_mutex,
+    clippy::implicit_clone,
     clippy::imprecise_flops,
     clippy::inefficient_to_string,
     clippy::invalid_upcast_comparisons,
@@ -29,11 +32,9 @@
     clippy::macro_use_imports,
     clippy::manual_ok_or,
     clippy::map_flatten,
-    clippy::match_on_vec_items,
     clippy::match_same_arms,
     clippy::match_wildcard_for_single_variants,
     clippy::mem_forget,
-    clippy::mismatched_target_os,
     clippy::missing_errors_doc,
     clippy::missing_safety_doc,
     clippy::mut_mut,
@@ -50,7 +51,6 @@
     clippy::string_add_assign,
     clippy::string_add,
     clippy::string_lit_as_bytes,
-    clippy::string_to_string,
     clippy::todo,
     clippy::trait_duplication_in_bounds,
     clippy::unimplemented,
@@ -64,6 +64,122 @@
     rust_2018_idioms
 )]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+pub mod adaptive;
+pub mod affine;
+#[cfg(feature = "alloc")]
+pub mod anchored;
+#[cfg(feature = "std")]
+pub mod approx;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "std")]
+pub mod background;
+pub mod builder;
+#[cfg(feature = "alloc")]
+pub mod chunked;
+pub mod cpu;
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "alloc")]
+pub mod dynamic;
+mod element;
+mod error;
+pub mod ext;
+#[cfg(feature = "alloc")]
+pub mod fenwick;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod group;
+#[cfg(feature = "alloc")]
+pub mod hull;
+#[cfg(feature = "instrument")]
+pub mod instrument;
+#[cfg(feature = "alloc")]
+pub mod lazy;
+#[cfg(feature = "alloc")]
+pub mod lazy_sum;
+#[cfg(any(feature = "csv", feature = "parquet"))]
+mod load;
+mod macros;
+#[cfg(feature = "alloc")]
+pub mod majority;
+pub mod matrix;
+mod memory;
+#[cfg(feature = "metrics")]
+pub mod metered;
+#[cfg(feature = "alloc")]
+pub mod mo;
+#[cfg(feature = "alloc")]
+pub mod monotonic;
+#[cfg(feature = "nalgebra")]
+pub mod nalgebra;
+#[cfg(feature = "ndarray")]
+pub mod ndarray;
+#[cfg(feature = "alloc")]
+pub mod occurrence;
+#[cfg(feature = "alloc")]
+pub mod packed;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "alloc")]
+pub mod persistent;
+#[cfg(feature = "polars")]
+pub mod polars;
+pub mod prelude;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod range_query;
+#[cfg(feature = "alloc")]
+pub mod retroactive;
 pub mod rmq;
+#[cfg(feature = "alloc")]
+pub mod rollback;
+#[cfg(feature = "alloc")]
+pub mod rope;
+mod scan;
+#[cfg(feature = "std")]
+pub mod sharded;
+#[cfg(feature = "alloc")]
+pub mod sketch;
+#[cfg(feature = "alloc")]
+pub mod snapshot;
+#[cfg(feature = "alloc")]
+pub mod splay;
 pub mod sum_query;
-pub use sum_query::{IndexableSumQuery, SumQuery};
+#[cfg(feature = "alloc")]
+pub mod timeseries;
+mod trace;
+#[cfg(feature = "alloc")]
+pub mod treap;
+#[cfg(feature = "unsafe-fast")]
+mod unchecked;
+#[cfg(feature = "veb")]
+pub mod veb;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "alloc")]
+pub mod wavelet;
+#[cfg(feature = "alloc")]
+pub mod window;
+#[cfg(feature = "std")]
+pub mod yfast;
+pub use builder::SumQueryBuilder;
+pub use element::{CheckedAdd, Modular, One, Zero};
+pub use error::{Error, NullPolicy};
+pub use ext::IteratorExt;
+#[cfg(feature = "alloc")]
+pub use ext::SliceExt;
+pub use memory::MemoryUsage;
+#[cfg(feature = "alloc")]
+pub use range_query::Transaction;
+pub use range_query::{Bounded, BoundsPolicy, FrozenQuery, RangeQuery, UpdatableRangeQuery};
+#[allow(deprecated)]
+pub use sum_query::SumQueryFixed;
+pub use sum_query::{IndexableSumQuery, PrefixStorage, SumQuery};
+#[cfg(feature = "alloc")]
+#[allow(deprecated)]
+pub use sum_query::{SumQuerySlice, SumQueryVec};
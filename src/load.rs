@@ -0,0 +1,86 @@
+//! Error type and streaming shared by the column loaders
+
+use core::fmt;
+
+use crate::Error;
+
+/// Failure while loading a column
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum LoadError {
+    /// Reading a record failed
+    #[cfg(feature = "csv")]
+    Csv(::csv::Error),
+    /// Reading or decoding the file failed
+    #[cfg(feature = "parquet")]
+    Parquet(::parquet::errors::ParquetError),
+    /// The column does not fit the structure, see [`Error`]
+    Query(Error),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "csv")]
+            Self::Csv(err) => write!(f, "csv: {err}"),
+            #[cfg(feature = "parquet")]
+            Self::Parquet(err) => write!(f, "parquet: {err}"),
+            Self::Query(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "csv")]
+            Self::Csv(err) => Some(err),
+            #[cfg(feature = "parquet")]
+            Self::Parquet(err) => Some(err),
+            Self::Query(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(feature = "csv")]
+impl From<::csv::Error> for LoadError {
+    fn from(err: ::csv::Error) -> Self {
+        Self::Csv(err)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<::parquet::errors::ParquetError> for LoadError {
+    fn from(err: ::parquet::errors::ParquetError) -> Self {
+        Self::Parquet(err)
+    }
+}
+
+impl From<Error> for LoadError {
+    fn from(err: Error) -> Self {
+        Self::Query(err)
+    }
+}
+
+/// Hand the values `next` yields to `build` as an iterator
+///
+/// The iterator ends at the first failure of `next`, whose error is then
+/// returned instead of the structure.
+pub(crate) fn stream<T, S>(
+    mut next: impl FnMut() -> Result<Option<T>, LoadError>,
+    build: impl FnOnce(&mut dyn Iterator<Item = T>) -> S,
+) -> Result<S, LoadError> {
+    let mut failure = None;
+    let mut values = core::iter::from_fn(|| {
+        next().unwrap_or_else(|err| {
+            failure = Some(err);
+            None
+        })
+    });
+
+    let structure = build(&mut values);
+    match failure {
+        Some(err) => Err(err),
+        None => Ok(structure),
+    }
+}
@@ -0,0 +1,83 @@
+/// Build a [`SumQuery`](crate::SumQuery) backed by a fixed size array sized
+/// from the number of elements
+///
+/// ```
+/// use kuehree::{sum_query, RangeQuery};
+///
+/// let sum = sum_query![1, 3, 4, 8, 6, 1, 4, 2];
+/// assert_eq!(sum.query(3, 6), 19);
+/// ```
+#[macro_export]
+macro_rules! sum_query {
+    ($($x:expr),* $(,)?) => {{
+        const __KUEHREE_LEN: usize = <[&str]>::len(&[$(stringify!($x)),*]);
+        <$crate::SumQuery<[_; __KUEHREE_LEN]> as $crate::IndexableSumQuery<_>>::new([$($x),*])
+    }};
+}
+
+/// Build a range minimum [`SparseTableFixed`](crate::rmq::SparseTableFixed)
+/// sized from the number of elements
+///
+/// ```
+/// use kuehree::rmq_min;
+///
+/// let min = rmq_min![5, 2, 9, 7];
+/// assert_eq!(min.query(2, 3), 7);
+/// ```
+#[macro_export]
+macro_rules! rmq_min {
+    ($($x:expr),* $(,)?) => {
+        $crate::__rmq_fixed!($crate::rmq::Min; $($x),*)
+    };
+}
+
+/// Build a range maximum [`SparseTableFixed`](crate::rmq::SparseTableFixed)
+/// sized from the number of elements
+///
+/// ```
+/// use kuehree::rmq_max;
+///
+/// let max = rmq_max![5, 2, 9, 7];
+/// assert_eq!(max.query(0, 1), 5);
+/// ```
+#[macro_export]
+macro_rules! rmq_max {
+    ($($x:expr),* $(,)?) => {
+        $crate::__rmq_fixed!($crate::rmq::Max; $($x),*)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __rmq_fixed {
+    ($op:ty; $($x:expr),*) => {{
+        const __KUEHREE_LEN: usize = <[&str]>::len(&[$(stringify!($x)),*]);
+        $crate::rmq::SparseTableFixed::<
+            _,
+            $op,
+            __KUEHREE_LEN,
+            { $crate::rmq::levels(__KUEHREE_LEN) },
+        >::new([$($x),*])
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{rmq::SparseTableFixed, RangeQuery, SumQuery};
+
+    #[test]
+    fn test_macros() {
+        let sum = sum_query![1u32, 3, 4, 8, 6, 1, 4, 2,];
+        let _: &SumQuery<[u32; 8]> = &sum;
+        assert_eq!(sum.query(3, 6), 19);
+
+        let len = 3;
+        let min = rmq_min![len * 2, 2, 9, len];
+        let _: &SparseTableFixed<usize, _, 4, 3> = &min;
+        assert_eq!(min.query(2, 3), 3);
+        assert_eq!(rmq_max![5, 2, 9, 7].query(0, 2), 9);
+
+        let empty = sum_query![];
+        let _: &SumQuery<[u8; 0]> = &empty;
+    }
+}
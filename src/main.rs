@@ -0,0 +1,289 @@
+//! `kuehree` command line companion, built with the `cli` feature
+//!
+//! Loads one numeric column of a CSV or JSON lines file into the chosen
+//! structure, then answers the inclusive `start end` range queries read
+//! from stdin, one per line.
+
+use std::{
+    env,
+    error::Error,
+    fs::File,
+    io::{self, BufRead, BufReader, IsTerminal, Write},
+    process::ExitCode,
+};
+
+use kuehree::{
+    rmq::{Max, Min, SparseTable},
+    IndexableSumQuery, RangeQuery, SumQuery,
+};
+
+const USAGE: &str = "\
+usage: kuehree [options] FILE
+
+Reads `start end` pairs from stdin, indices are 0-based and inclusive.
+
+options:
+    -s, --structure sum|min|max   structure to build, default sum
+    -f, --format csv|jsonl        input format, default from the extension
+    -c, --column COLUMN           CSV header or index, JSON object key
+        --no-headers              the CSV file has no header record
+    -h, --help                    print this message";
+
+#[derive(Debug, Clone, Copy)]
+enum Structure {
+    Sum,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Debug)]
+struct Options {
+    structure: Structure,
+    format: Option<Format>,
+    column: Option<String>,
+    headers: bool,
+    path: String,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut structure = Structure::Sum;
+        let mut format = None;
+        let mut column = None;
+        let mut headers = true;
+        let mut path = None;
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "-s" | "--structure" => {
+                    structure = match value()?.as_str() {
+                        "sum" => Structure::Sum,
+                        "min" => Structure::Min,
+                        "max" => Structure::Max,
+                        other => return Err(format!("unknown structure `{other}`")),
+                    }
+                }
+                "-f" | "--format" => {
+                    format = Some(match value()?.as_str() {
+                        "csv" => Format::Csv,
+                        "jsonl" => Format::JsonLines,
+                        other => return Err(format!("unknown format `{other}`")),
+                    });
+                }
+                "-c" | "--column" => column = Some(value()?),
+                "--no-headers" => headers = false,
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ if path.is_some() => return Err(format!("unexpected argument `{arg}`")),
+                _ => path = Some(arg),
+            }
+        }
+        let path = path.ok_or("missing FILE")?;
+        Ok(Some(Self {
+            structure,
+            format,
+            column,
+            headers,
+            path,
+        }))
+    }
+
+    fn format(&self) -> Result<Format, String> {
+        if let Some(format) = self.format {
+            return Ok(format);
+        }
+        match self.path.rsplit_once('.').map(|(_, extension)| extension) {
+            Some("csv") => Ok(Format::Csv),
+            Some("jsonl" | "ndjson") => Ok(Format::JsonLines),
+            _ => Err(format!(
+                "cannot tell the format of {}, pass --format",
+                self.path
+            )),
+        }
+    }
+}
+
+fn build(structure: Structure, values: &mut dyn Iterator<Item = f64>) -> Box<dyn RangeQuery<f64>> {
+    match structure {
+        Structure::Sum => Box::new(SumQuery::<Vec<f64>>::new(values)),
+        Structure::Min => Box::new(SparseTable::<f64, Min>::new(values)),
+        Structure::Max => Box::new(SparseTable::<f64, Max>::new(values)),
+    }
+}
+
+fn load_csv(options: &Options) -> Result<Box<dyn RangeQuery<f64>>, Box<dyn Error>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(options.headers)
+        .from_path(&options.path)?;
+    let column = match options.column.as_deref() {
+        None => 0,
+        Some(column) => match column.parse() {
+            Ok(index) => index,
+            Err(_) if options.headers => reader
+                .headers()?
+                .iter()
+                .position(|header| header == column)
+                .ok_or(format!("no column `{column}`"))?,
+            Err(_) => return Err(format!("no column `{column}` without headers").into()),
+        },
+    };
+    Ok(kuehree::csv::load(&mut reader, column, |values| {
+        build(options.structure, values)
+    })?)
+}
+
+fn load_json_lines(options: &Options) -> Result<Box<dyn RangeQuery<f64>>, Box<dyn Error>> {
+    let mut values = Vec::new();
+    for (line, text) in BufReader::new(File::open(&options.path)?)
+        .lines()
+        .enumerate()
+    {
+        let text = text?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let record: serde_json::Value = serde_json::from_str(&text)?;
+        let value = match options.column.as_deref() {
+            Some(key) => record.get(key),
+            None => Some(&record),
+        };
+        let value = value
+            .and_then(serde_json::Value::as_f64)
+            .ok_or(format!("line {}: not a number", line + 1))?;
+        values.push(value);
+    }
+    Ok(build(options.structure, &mut values.into_iter()))
+}
+
+/// Parse an inclusive `start end` pair
+fn parse_range(line: &str) -> Result<(usize, usize), String> {
+    let mut bounds = line.split_whitespace().map(str::parse::<usize>);
+    match (bounds.next(), bounds.next(), bounds.next()) {
+        (Some(Ok(start)), Some(Ok(end)), None) => Ok((start, end)),
+        _ => Err(format!("expected `start end`, got `{line}`")),
+    }
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn Error>> {
+    let structure = match options.format()? {
+        Format::Csv => load_csv(options)?,
+        Format::JsonLines => load_json_lines(options)?,
+    };
+
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    let mut stdout = io::stdout().lock();
+    if interactive {
+        eprintln!(
+            "{} values loaded, enter `start end` per line",
+            structure.len()
+        );
+    }
+    let mut lines = stdin.lock().lines();
+    loop {
+        if interactive {
+            eprint!("> ");
+        }
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(());
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_range(&line).and_then(|(start, end)| {
+            structure
+                .try_query(start, end)
+                .map_err(|err| err.to_string())
+        }) {
+            Ok(result) => writeln!(stdout, "{result}")?,
+            Err(err) if interactive => eprintln!("error: {err}"),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("kuehree: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("kuehree: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_options() {
+        let options = parse(&["-s", "max", "--column", "price", "--no-headers", "a.txt"])
+            .unwrap()
+            .unwrap();
+        assert!(matches!(options.structure, Structure::Max));
+        assert_eq!(options.column.as_deref(), Some("price"));
+        assert!(!options.headers);
+        assert_eq!(options.path, "a.txt");
+        assert!(options.format().is_err());
+
+        let options = parse(&["data.ndjson"]).unwrap().unwrap();
+        assert!(matches!(options.structure, Structure::Sum));
+        assert!(options.headers);
+        assert!(matches!(options.format(), Ok(Format::JsonLines)));
+        let options = parse(&["-f", "csv", "data.jsonl"]).unwrap().unwrap();
+        assert!(matches!(options.format(), Ok(Format::Csv)));
+
+        assert!(parse(&["data.csv", "--help"]).unwrap().is_none());
+        assert_eq!(parse(&[]).unwrap_err(), "missing FILE");
+        assert_eq!(parse(&["-s"]).unwrap_err(), "-s needs a value");
+        assert_eq!(
+            parse(&["-s", "median", "data.csv"]).unwrap_err(),
+            "unknown structure `median`"
+        );
+        assert_eq!(
+            parse(&["--format", "xml", "data.csv"]).unwrap_err(),
+            "unknown format `xml`"
+        );
+        assert_eq!(parse(&["-x"]).unwrap_err(), "unknown option `-x`");
+        assert_eq!(
+            parse(&["a.csv", "b.csv"]).unwrap_err(),
+            "unexpected argument `b.csv`"
+        );
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("3 7"), Ok((3, 7)));
+        assert_eq!(parse_range("  0\t12 "), Ok((0, 12)));
+        // Inverted ranges parse, the structure rejects them
+        assert_eq!(parse_range("7 3"), Ok((7, 3)));
+        for line in ["", "3", "3 7 9", "-1 4", "a b", "3,7", "1.5 2"] {
+            assert_eq!(
+                parse_range(line),
+                Err(format!("expected `start end`, got `{line}`"))
+            );
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Range majority, the element filling more than half of a range
+
+use alloc::vec::Vec;
+
+use crate::{
+    occurrence::Occurrences,
+    rmq::{Operation, SegmentTree},
+    trace::BuildSpan,
+    MemoryUsage,
+};
+
+/// State of a Boyer–Moore majority vote over a range
+///
+/// If the range has a majority element it is `candidate`, but the
+/// candidate of a range without one is arbitrary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Vote<T> {
+    pub candidate: T,
+    /// Votes of the candidate not cancelled by other elements
+    pub lead: usize,
+}
+
+impl<T> From<T> for Vote<T> {
+    fn from(candidate: T) -> Self {
+        Self { candidate, lead: 1 }
+    }
+}
+
+/// Boyer–Moore majority vote, cancelling the leads of different
+/// candidates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BoyerMoore;
+
+impl<T: PartialEq> Operation<Vote<T>> for BoyerMoore {
+    fn combine(a: Vote<T>, b: Vote<T>) -> Vote<T> {
+        if a.candidate == b.candidate {
+            Vote {
+                candidate: a.candidate,
+                lead: a.lead + b.lead,
+            }
+        } else if a.lead >= b.lead {
+            Vote {
+                candidate: a.candidate,
+                lead: a.lead - b.lead,
+            }
+        } else {
+            Vote {
+                candidate: b.candidate,
+                lead: b.lead - a.lead,
+            }
+        }
+    }
+}
+
+/// Majority element of any range
+///
+/// A [`SegmentTree`] of [`Vote`]s names the only possible majority of a
+/// range, and the [`Occurrences`] of that candidate confirm it.
+///
+/// ```
+/// use kuehree::majority::RangeMajority;
+///
+/// let votes = RangeMajority::new([2, 7, 7, 1, 7, 2, 2]);
+/// assert_eq!(votes.query(0, 4), Some(7));
+/// assert_eq!(votes.query(3, 6), None);
+/// assert_eq!(votes.query(5, 6), Some(2));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RangeMajority<T> {
+    votes: SegmentTree<Vote<T>, BoyerMoore>,
+    occurrences: Occurrences<T>,
+}
+
+impl<T: Copy + Ord> RangeMajority<T> {
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n log n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("RangeMajority");
+        let data: Vec<T> = data.into_iter().collect();
+        span.record_len(data.len());
+        Self {
+            votes: SegmentTree::new(data.iter().copied().map(Vote::from)),
+            occurrences: Occurrences::new(data),
+        }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.occurrences.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.occurrences.is_empty()
+    }
+
+    /// Element filling more than half of the range between start range
+    /// and end range
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, start: usize, end: usize) -> Option<T> {
+        let Vote { candidate, .. } = self.votes.query(start, end);
+        let count = self.occurrences.count_in_range(start, end, &candidate);
+        (2 * count > end - start + 1).then_some(candidate)
+    }
+}
+
+impl<T: Copy + Ord> FromIterator<T> for RangeMajority<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+impl<T> MemoryUsage for RangeMajority<T> {
+    fn heap_bytes(&self) -> usize {
+        self.votes.heap_bytes() + self.occurrences.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sketch::splitmix;
+
+    #[test]
+    fn test_majority() {
+        let data: Vec<u8> = (0..60)
+            .map(|i| match splitmix(i) % 5 {
+                0..=2 => 4,
+                value => value as u8,
+            })
+            .collect();
+        let majority: RangeMajority<u8> = data.iter().copied().collect();
+        for start in 0..data.len() {
+            for end in start..data.len() {
+                let range = &data[start..=end];
+                let expected = range.iter().copied().find(|&value| {
+                    range.iter().filter(|&&other| other == value).count() * 2 > range.len()
+                });
+                assert_eq!(majority.query(start, end), expected);
+            }
+        }
+        assert_eq!(majority.len(), 60);
+    }
+}
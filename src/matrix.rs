@@ -0,0 +1,107 @@
+//! Small square matrices and their product as a range operation
+//!
+//! Composing dynamic programming transitions over a range is a product of
+//! transition matrices, answered by a segment tree with [`MatMul`]:
+//!
+//! ```
+//! use kuehree::{matrix::{MatMul, Matrix}, rmq::SegmentTree};
+//!
+//! // Fibonacci step, the product of k steps holds F(k + 1), F(k), F(k - 1)
+//! let step = Matrix([[1u64, 1], [1, 0]]);
+//! let mut steps = SegmentTree::<_, MatMul>::new([step; 10]);
+//! assert_eq!(steps.query(0, 9).0[0][1], 55);
+//!
+//! steps.update(4, Matrix::identity());
+//! assert_eq!(steps.query(0, 9).0[0][1], 34);
+//! ```
+
+use core::ops::{Add, Mul};
+
+use crate::{rmq::Operation, One, Zero};
+
+/// `N` by `N` matrix, rows first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Matrix<T, const N: usize>(pub [[T; N]; N]);
+
+impl<T: Copy + Zero + One, const N: usize> Matrix<T, N> {
+    /// Identity matrix
+    pub fn identity() -> Self {
+        let mut rows = [[T::zero(); N]; N];
+        for (idx, row) in rows.iter_mut().enumerate() {
+            row[idx] = T::one();
+        }
+        Self(rows)
+    }
+}
+
+impl<T: Copy + Zero, const N: usize> Zero for Matrix<T, N> {
+    fn zero() -> Self {
+        Self([[T::zero(); N]; N])
+    }
+}
+
+impl<T: Copy + Zero + One, const N: usize> One for Matrix<T, N> {
+    fn one() -> Self {
+        Self::identity()
+    }
+}
+
+impl<T, const N: usize> Mul for Matrix<T, N>
+where
+    T: Copy + Zero + Add<Output = T> + Mul<Output = T>,
+{
+    type Output = Self;
+
+    /// Algorithmic complexity: O(N³)
+    fn mul(self, rhs: Self) -> Self {
+        let mut product = Self::zero();
+        for row in 0..N {
+            for col in 0..N {
+                product.0[row][col] =
+                    (0..N).fold(T::zero(), |acc, k| acc + self.0[row][k] * rhs.0[k][col]);
+            }
+        }
+        product
+    }
+}
+
+/// Matrix product operation, folding a range left to right
+///
+/// Not commutative, so only usable with the segment trees, which keep the
+/// order of the elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct MatMul;
+
+impl<T, const N: usize> Operation<Matrix<T, N>> for MatMul
+where
+    T: Copy + Zero + Add<Output = T> + Mul<Output = T>,
+{
+    fn combine(a: Matrix<T, N>, b: Matrix<T, N>) -> Matrix<T, N> {
+        a * b
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmq::SegmentTreeFixed;
+    use core::num::Wrapping;
+
+    #[test]
+    fn test_range_products() {
+        let mats: [Matrix<Wrapping<u32>, 3>; 7] = core::array::from_fn(|i| {
+            Matrix(core::array::from_fn(|row| {
+                core::array::from_fn(|col| Wrapping((i * 7 + row * 3 + col) as u32 % 5))
+            }))
+        });
+        let tree = SegmentTreeFixed::<_, MatMul, 7>::new(mats);
+        for start in 0..mats.len() {
+            for end in start..mats.len() {
+                let product = mats[start..=end]
+                    .iter()
+                    .fold(Matrix::identity(), |acc, &m| acc * m);
+                assert_eq!(tree.query(start, end), product);
+            }
+        }
+    }
+}
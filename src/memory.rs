@@ -0,0 +1,58 @@
+use core::mem::size_of_val;
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
+use alloc::{borrow::Cow, boxed::Box, vec::Vec};
+
+/// Memory used by a structure, to compare backends at runtime
+///
+/// Heap memory owned by the elements themselves is not counted.
+pub trait MemoryUsage {
+    /// Bytes allocated on the heap, including unused capacity
+    fn heap_bytes(&self) -> usize;
+
+    /// Inline size of the value plus [`Self::heap_bytes`]
+    fn memory_footprint(&self) -> usize {
+        size_of_val(self) + self.heap_bytes()
+    }
+}
+
+impl<T, const N: usize> MemoryUsage for [T; N] {
+    fn heap_bytes(&self) -> usize {
+        0
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> MemoryUsage for Vec<T> {
+    fn heap_bytes(&self) -> usize {
+        self.capacity() * size_of::<T>()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> MemoryUsage for Box<[T]> {
+    fn heap_bytes(&self) -> usize {
+        size_of_val::<[T]>(self)
+    }
+}
+
+/// Counts the whole shared allocation, reference counts included
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<T> MemoryUsage for Arc<[T]> {
+    fn heap_bytes(&self) -> usize {
+        2 * size_of::<usize>() + size_of_val::<[T]>(self)
+    }
+}
+
+/// Borrowed data is not owned and counts as zero
+#[cfg(feature = "alloc")]
+impl<T: Clone> MemoryUsage for Cow<'_, [T]> {
+    fn heap_bytes(&self) -> usize {
+        match self {
+            Cow::Borrowed(_) => 0,
+            Cow::Owned(owned) => owned.heap_bytes(),
+        }
+    }
+}
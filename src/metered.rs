@@ -0,0 +1,103 @@
+//! Query counters and latencies published through the `metrics` facade,
+//! enabled with the `metrics` feature
+//!
+//! Every constructor also increments `kuehree_builds_total` and records
+//! `kuehree_build_duration_seconds`, labelled with the structure name.
+
+use std::time::Instant;
+
+use metrics::{Counter, Histogram};
+
+use crate::{MemoryUsage, RangeQuery};
+
+/// Wraps a range query structure, publishing `kuehree_queries_total` and
+/// `kuehree_query_duration_seconds` labelled with `name`
+///
+/// The handles are resolved once at construction, so install the recorder
+/// before wrapping.
+#[derive(Debug, Clone)]
+pub struct Metered<Q> {
+    inner: Q,
+    queries: Counter,
+    latency: Histogram,
+}
+
+impl<Q> Metered<Q> {
+    pub fn new(inner: Q, name: &'static str) -> Self {
+        Self {
+            inner,
+            queries: metrics::counter!("kuehree_queries_total", "structure" => name),
+            latency: metrics::histogram!("kuehree_query_duration_seconds", "structure" => name),
+        }
+    }
+
+    pub fn inner(&self) -> &Q {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> Q {
+        self.inner
+    }
+}
+
+impl<T, Q: RangeQuery<T>> RangeQuery<T> for Metered<Q> {
+    fn query(&self, start: usize, end: usize) -> T {
+        let started = Instant::now();
+        let answer = self.inner.query(start, end);
+        self.latency.record(started.elapsed());
+        self.queries.increment(1);
+        answer
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<Q: MemoryUsage> MemoryUsage for Metered<Q> {
+    fn heap_bytes(&self) -> usize {
+        self.inner.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use metrics_util::{
+        debugging::{DebugValue, DebuggingRecorder},
+        MetricKind,
+    };
+
+    use super::*;
+    use crate::{IndexableSumQuery, SumQuery};
+
+    #[test]
+    fn test_metered() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        metrics::with_local_recorder(&recorder, || {
+            let sum = Metered::new(SumQuery::<Vec<_>>::new([1u32, 3, 4, 8]), "sum");
+            assert_eq!(sum.query(1, 2), 7);
+            assert_eq!(sum.query(0, 3), 16);
+        });
+
+        let metrics = snapshotter.snapshot().into_vec();
+        let value = |kind, name: &str| {
+            metrics.iter().find_map(|(key, _, _, value)| {
+                (key.kind() == kind && key.key().name() == name).then_some(value)
+            })
+        };
+        assert_eq!(
+            value(MetricKind::Counter, "kuehree_builds_total"),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            value(MetricKind::Counter, "kuehree_queries_total"),
+            Some(&DebugValue::Counter(2))
+        );
+        assert!(matches!(
+            value(MetricKind::Histogram, "kuehree_query_duration_seconds"),
+            Some(DebugValue::Histogram(latencies)) if latencies.len() == 2
+        ));
+    }
+}
@@ -0,0 +1,204 @@
+//! Mo's algorithm, offline range queries over a sliding window
+
+use alloc::vec::Vec;
+
+/// Order in which [`Mo`] visits the queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MoOrder {
+    /// Sort by block of `sqrt` size of the start, then by end, alternating
+    /// direction from one block to the next
+    #[default]
+    Blocks,
+    /// Sort along a Hilbert curve over the `(start, end)` plane, usually
+    /// fewer window moves than blocks for large inputs and many queries
+    Hilbert,
+}
+
+/// Solver answering many range queries offline by moving a window between
+/// them
+///
+/// The window starts empty and grows or shrinks one element at a time, so
+/// any statistic that can add and remove single elements is supported,
+/// e.g. the number of distinct values.
+///
+/// Algorithmic complexity: O((n + q) sqrt n) window moves for `n`
+/// elements and `q` queries, plus sorting the queries
+///
+/// ```
+/// use kuehree::mo::{Mo, MoOrder};
+///
+/// // Distinct values of each range
+/// let data = [1, 2, 1, 3, 2, 2];
+/// let queries = [(0, 2), (1, 5), (3, 3)];
+/// let distinct = Mo::new(MoOrder::Hilbert).solve(
+///     &mut ([0usize; 4], 0),
+///     &queries,
+///     |(counts, distinct), index| {
+///         counts[data[index]] += 1;
+///         *distinct += usize::from(counts[data[index]] == 1);
+///     },
+///     |(counts, distinct), index| {
+///         counts[data[index]] -= 1;
+///         *distinct -= usize::from(counts[data[index]] == 0);
+///     },
+///     |&(_, distinct)| distinct,
+/// );
+/// assert_eq!(distinct, [2, 3, 1]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Mo {
+    order: MoOrder,
+}
+
+/// Position of `(x, y)` along the Hilbert curve filling a square of side
+/// `side`, a power of two
+fn hilbert(side: u128, x: usize, y: usize) -> u128 {
+    let (mut x, mut y) = (x as u128, y as u128);
+    let mut position = 0;
+    let mut half = side / 2;
+    while half > 0 {
+        let right = u128::from(x & half != 0);
+        let top = u128::from(y & half != 0);
+        position += half * half * ((3 * right) ^ top);
+        // Rotate the quadrant so the curve inside it starts at its corner
+        if top == 0 {
+            if right == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            core::mem::swap(&mut x, &mut y);
+        }
+        half /= 2;
+    }
+    position
+}
+
+impl Mo {
+    pub fn new(order: MoOrder) -> Self {
+        Self { order }
+    }
+
+    pub fn order(&self) -> MoOrder {
+        self.order
+    }
+
+    /// Indices of `queries` in the order they are visited
+    fn schedule(&self, queries: &[(usize, usize)]) -> Vec<usize> {
+        let mut schedule: Vec<usize> = (0..queries.len()).collect();
+        let len = queries.iter().map(|&(_, end)| end + 1).max().unwrap_or(0);
+        match self.order {
+            MoOrder::Blocks => {
+                let block = (len / queries.len().max(1).isqrt()).max(1);
+                schedule.sort_by_key(|&query| {
+                    let (start, end) = queries[query];
+                    let block = start / block;
+                    (
+                        block,
+                        if block.is_multiple_of(2) {
+                            end
+                        } else {
+                            usize::MAX - end
+                        },
+                    )
+                });
+            }
+            MoOrder::Hilbert => {
+                let side = (len as u128).next_power_of_two();
+                schedule.sort_by_cached_key(|&query| {
+                    let (start, end) = queries[query];
+                    hilbert(side, start, end)
+                });
+            }
+        }
+        schedule
+    }
+
+    /// Answer every range between start range and end range of `queries`
+    ///
+    /// `state` starts as the statistic of an empty window, `add` and
+    /// `remove` put an element into or take it out of the window, and
+    /// `answer` reads the statistic of the current window.
+    ///
+    /// # Panics
+    ///
+    /// If a query start is greater than its end
+    pub fn solve<S, A>(
+        &self,
+        state: &mut S,
+        queries: &[(usize, usize)],
+        mut add: impl FnMut(&mut S, usize),
+        mut remove: impl FnMut(&mut S, usize),
+        mut answer: impl FnMut(&S) -> A,
+    ) -> Vec<A> {
+        let mut answers: Vec<Option<A>> = queries.iter().map(|_| None).collect();
+        // Current window, `start..end`
+        let (mut start, mut end) = (0, 0);
+        for query in self.schedule(queries) {
+            let (query_start, query_end) = queries[query];
+            assert!(query_start <= query_end, "query start is greater than end");
+            // Grow before shrinking so the window never turns inside out
+            while end <= query_end {
+                add(state, end);
+                end += 1;
+            }
+            while start > query_start {
+                start -= 1;
+                add(state, start);
+            }
+            while end > query_end + 1 {
+                end -= 1;
+                remove(state, end);
+            }
+            while start < query_start {
+                remove(state, start);
+                start += 1;
+            }
+            answers[query] = Some(answer(state));
+        }
+        answers.into_iter().flatten().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sketch::splitmix;
+
+    #[test]
+    fn test_mo() {
+        let data: Vec<u64> = (0..300).map(|i| splitmix(i) % 50).collect();
+        let queries: Vec<(usize, usize)> = (0..200)
+            .map(|i| {
+                let (a, b) = (splitmix(i + 1_000) % 300, splitmix(i + 2_000) % 300);
+                (a.min(b) as usize, a.max(b) as usize)
+            })
+            .collect();
+        let expected: Vec<u64> = queries
+            .iter()
+            .map(|&(start, end)| data[start..=end].iter().map(|value| value * value).sum())
+            .collect();
+
+        for order in [MoOrder::Blocks, MoOrder::Hilbert] {
+            let mo = Mo::new(order);
+            let schedule = mo.schedule(&queries);
+            let mut sorted = schedule.clone();
+            sorted.sort_unstable();
+            assert!(sorted.into_iter().eq(0..queries.len()));
+
+            let answers = mo.solve(
+                &mut 0u64,
+                &queries,
+                |sum, index| *sum += data[index] * data[index],
+                |sum, index| *sum -= data[index] * data[index],
+                |&sum| sum,
+            );
+            assert_eq!(answers, expected);
+        }
+        assert_eq!(hilbert(4, 0, 0), 0);
+        assert_eq!(hilbert(4, 3, 0), 15);
+        assert_eq!(
+            Mo::default().solve(&mut (), &[], |_, _| {}, |_, _| {}, |_| 0),
+            Vec::<i32>::new()
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Monotonic stack helpers, finding for every element the nearest element
+//! that is strictly greater or smaller on either side
+//!
+//! Each runs in O(n) and returns one `Option<usize>` per element, `None`
+//! when no such element exists.
+
+use alloc::{vec, vec::Vec};
+
+/// Nearest index after every element whose value `beats` it, scanning from
+/// the back with a stack of candidates
+fn scan_next<T>(data: &[T], beats: impl Fn(&T, &T) -> bool) -> Vec<Option<usize>> {
+    let mut result = vec![None; data.len()];
+    let mut stack: Vec<usize> = Vec::new();
+    for (idx, value) in data.iter().enumerate().rev() {
+        while stack.last().is_some_and(|&top| !beats(&data[top], value)) {
+            stack.pop();
+        }
+        result[idx] = stack.last().copied();
+        stack.push(idx);
+    }
+    result
+}
+
+/// Nearest index before every element whose value `beats` it
+fn scan_previous<T>(data: &[T], beats: impl Fn(&T, &T) -> bool) -> Vec<Option<usize>> {
+    let mut result = vec![None; data.len()];
+    let mut stack: Vec<usize> = Vec::new();
+    for (idx, value) in data.iter().enumerate() {
+        while stack.last().is_some_and(|&top| !beats(&data[top], value)) {
+            stack.pop();
+        }
+        result[idx] = stack.last().copied();
+        stack.push(idx);
+    }
+    result
+}
+
+/// Index of the first later element strictly greater than each element
+pub fn next_greater_indices<T: PartialOrd>(data: &[T]) -> Vec<Option<usize>> {
+    scan_next(data, |candidate, value| candidate > value)
+}
+
+/// Index of the first later element strictly smaller than each element
+pub fn next_smaller_indices<T: PartialOrd>(data: &[T]) -> Vec<Option<usize>> {
+    scan_next(data, |candidate, value| candidate < value)
+}
+
+/// Index of the last earlier element strictly greater than each element
+pub fn previous_greater_indices<T: PartialOrd>(data: &[T]) -> Vec<Option<usize>> {
+    scan_previous(data, |candidate, value| candidate > value)
+}
+
+/// Index of the last earlier element strictly smaller than each element
+pub fn previous_smaller_indices<T: PartialOrd>(data: &[T]) -> Vec<Option<usize>> {
+    scan_previous(data, |candidate, value| candidate < value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_monotonic() {
+        let data = [2, 1, 2, 4, 3, 1];
+        assert_eq!(
+            next_greater_indices(&data),
+            [Some(3), Some(2), Some(3), None, None, None]
+        );
+        assert_eq!(
+            next_smaller_indices(&data),
+            [Some(1), None, Some(5), Some(4), Some(5), None]
+        );
+        assert_eq!(
+            previous_greater_indices(&data),
+            [None, Some(0), None, None, Some(3), Some(4)]
+        );
+        assert_eq!(
+            previous_smaller_indices(&data),
+            [None, None, Some(1), Some(2), Some(2), None]
+        );
+        assert!(next_greater_indices::<u8>(&[]).is_empty());
+    }
+}
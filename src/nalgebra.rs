@@ -0,0 +1,91 @@
+//! Building from `nalgebra` vectors and matrices
+//!
+//! Contiguous vectors already convert with [`From`], through their
+//! `AsRef<[T]>`. [`SumQuery::from_nalgebra`] also takes strided views such
+//! as the rows of a column major matrix, and [`summed_area`] gives the two
+//! dimensional prefix table of a matrix whatever its layout.
+
+use core::ops::{Add, Sub};
+
+use ::nalgebra::{DMatrix, Dim, Matrix, RawStorage, Scalar};
+
+use crate::{PrefixStorage, SumQuery, Zero};
+
+impl<C> SumQuery<C> {
+    /// Construct `Self` from a vector or any row or column view, a whole
+    /// matrix is read column by column
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// ```
+    /// use kuehree::{RangeQuery, SumQuery};
+    /// use nalgebra::{DMatrix, DVector};
+    ///
+    /// let vector = SumQuery::<Vec<i32>>::from(DVector::from_vec(vec![1, 2, 3]));
+    /// assert_eq!(vector.query(1, 2), 5);
+    ///
+    /// let matrix = DMatrix::from_row_slice(2, 3, &[1, 2, 3, 4, 5, 6]);
+    /// let row = SumQuery::<Vec<i32>>::from_nalgebra(&matrix.row(1));
+    /// assert_eq!(row.query(0, 1), 9);
+    /// ```
+    pub fn from_nalgebra<T, R, K, S>(data: &Matrix<T, R, K, S>) -> Self
+    where
+        T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+        R: Dim,
+        K: Dim,
+        S: RawStorage<T, R, K>,
+        C: PrefixStorage<T>,
+    {
+        <Self as crate::IndexableSumQuery<T>>::new(data.iter().copied())
+    }
+}
+
+/// Summed area table of `data`, element `(r, c)` holds the sum of the
+/// rectangle from `(0, 0)` to `(r, c)` inclusive
+///
+/// Algorithmic complexity: O(rows * columns)
+pub fn summed_area<T, R, K, S>(data: &Matrix<T, R, K, S>) -> DMatrix<T>
+where
+    T: Scalar + Copy + Add<Output = T>,
+    R: Dim,
+    K: Dim,
+    S: RawStorage<T, R, K>,
+{
+    let (rows, cols) = data.shape();
+    let mut totals = DMatrix::from_fn(rows, cols, |row, col| data[(row, col)]);
+    // Down the columns first, the contiguous direction of `DMatrix`
+    for col in 0..cols {
+        for row in 1..rows {
+            totals[(row, col)] = totals[(row, col)] + totals[(row - 1, col)];
+        }
+    }
+    for row in 0..rows {
+        for col in 1..cols {
+            totals[(row, col)] = totals[(row, col)] + totals[(row, col - 1)];
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RangeQuery;
+    use ::nalgebra::DVector;
+
+    #[test]
+    fn test_nalgebra() {
+        let matrix = DMatrix::from_row_slice(2, 3, &[1u32, 2, 3, 4, 5, 6]);
+        let column = SumQuery::<Box<[u32]>>::from_nalgebra(&matrix.column(2));
+        assert_eq!(column.query(0, 1), 9);
+        let row = SumQuery::<Vec<u32>>::from_nalgebra(&matrix.row(0));
+        assert_eq!(row.query(1, 2), 5);
+        let vector = SumQuery::<Vec<u32>>::from(DVector::from_vec(vec![7, 8, 9]));
+        assert_eq!(vector.query(0, 2), 24);
+
+        assert_eq!(
+            summed_area(&matrix),
+            DMatrix::from_row_slice(2, 3, &[1, 3, 6, 5, 12, 21])
+        );
+    }
+}
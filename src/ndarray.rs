@@ -0,0 +1,95 @@
+//! Building from `ndarray` arrays without copying them into a `Vec` first
+//!
+//! There is no two dimensional query structure yet, [`summed_area`] gives
+//! the table one would build on: the sum of any rectangle is four lookups.
+
+use core::ops::{Add, Sub};
+
+use ::ndarray::{Array, Array2, ArrayBase, Axis, Data, Dimension, Ix1, Ix2};
+
+use crate::{PrefixStorage, SumQuery, Zero};
+
+impl<C> SumQuery<C> {
+    /// Construct `Self` from any one dimensional array or view, also non
+    /// contiguous ones such as a column of a row major matrix
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// ```
+    /// use kuehree::{RangeQuery, SumQuery};
+    /// use ndarray::array;
+    ///
+    /// let matrix = array![[1, 2], [3, 4], [5, 6]];
+    /// let column = SumQuery::<Vec<i32>>::from_ndarray(&matrix.column(1));
+    /// assert_eq!(column.query(1, 2), 10);
+    /// ```
+    pub fn from_ndarray<T, S>(data: &ArrayBase<S, Ix1>) -> Self
+    where
+        S: Data<Elem = T>,
+        T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+        C: PrefixStorage<T>,
+    {
+        <Self as crate::IndexableSumQuery<T>>::new(data.iter().copied())
+    }
+}
+
+/// Running totals of `data` along `axis`, element `i` of every lane holds
+/// the sum of the lane's first `i + 1` elements
+///
+/// Algorithmic complexity: O(n)
+///
+/// # Panics
+///
+/// If `axis` is not an axis of `data`
+pub fn prefix_sums_axis<T, S, D>(data: &ArrayBase<S, D>, axis: Axis) -> Array<T, D>
+where
+    T: Copy + Add<Output = T>,
+    S: Data<Elem = T>,
+    D: Dimension,
+{
+    let mut totals = data.to_owned();
+    totals.accumulate_axis_inplace(axis, |&previous, current| *current = previous + *current);
+    totals
+}
+
+/// Summed area table of `data`, element `(r, c)` holds the sum of the
+/// rectangle from `(0, 0)` to `(r, c)` inclusive
+///
+/// Algorithmic complexity: O(rows * columns)
+pub fn summed_area<T, S>(data: &ArrayBase<S, Ix2>) -> Array2<T>
+where
+    T: Copy + Add<Output = T>,
+    S: Data<Elem = T>,
+{
+    let mut totals = data.to_owned();
+    for axis in [Axis(0), Axis(1)] {
+        totals.accumulate_axis_inplace(axis, |&previous, current| *current = previous + *current);
+    }
+    totals
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RangeQuery;
+    use ::ndarray::array;
+
+    #[test]
+    fn test_ndarray() {
+        let data = array![[1u32, 2, 3], [4, 5, 6]];
+        let row = SumQuery::<Box<[u32]>>::from_ndarray(&data.row(1));
+        assert_eq!(row.query(0, 2), 15);
+        let strided = SumQuery::<Vec<u32>>::from_ndarray(&data.slice(::ndarray::s![.., 2]));
+        assert_eq!(strided.query(0, 1), 9);
+
+        assert_eq!(
+            prefix_sums_axis(&data, Axis(0)),
+            array![[1, 2, 3], [5, 7, 9]]
+        );
+        assert_eq!(
+            prefix_sums_axis(&data, Axis(1)),
+            array![[1, 3, 6], [4, 9, 15]]
+        );
+        assert_eq!(summed_area(&data), array![[1, 3, 6], [5, 12, 21]]);
+    }
+}
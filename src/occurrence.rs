@@ -0,0 +1,132 @@
+//! Positions of every value, counting and selecting occurrences within a
+//! range
+
+use core::borrow::Borrow;
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{trace::BuildSpan, MemoryUsage};
+
+/// Sorted positions of each distinct value of the data
+///
+/// Occurrences of a value within a range form a contiguous run of its
+/// positions, found with two binary searches.
+///
+/// ```
+/// use kuehree::occurrence::Occurrences;
+///
+/// let log = Occurrences::new("abracadabra".chars());
+/// assert_eq!(log.count_in_range(1, 9, &'a'), 3);
+/// assert_eq!(log.select_in_range(1, 9, &'a', 2), Some(7));
+/// assert_eq!(log.select_in_range(1, 9, &'a', 3), None);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Occurrences<T> {
+    positions: BTreeMap<T, Vec<usize>>,
+    len: usize,
+}
+
+impl<T: Ord> Occurrences<T> {
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n log d) for `d` distinct values
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("Occurrences");
+        let mut positions: BTreeMap<T, Vec<usize>> = BTreeMap::new();
+        let mut len = 0;
+        for (index, value) in data.into_iter().enumerate() {
+            positions.entry(value).or_default().push(index);
+            len = index + 1;
+        }
+        span.record_len(len);
+        Self { positions, len }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Positions of `value` between start range and end range
+    fn within<Q>(&self, start: usize, end: usize, value: &Q) -> &[usize]
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        assert!(end >= start && end < self.len);
+        let Some(positions) = self.positions.get(value) else {
+            return &[];
+        };
+        let first = positions.partition_point(|&index| index < start);
+        let last = positions.partition_point(|&index| index <= end);
+        &positions[first..last]
+    }
+
+    /// Number of occurrences of `value` between start range and end range
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn count_in_range<Q>(&self, start: usize, end: usize, value: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.within(start, end, value).len()
+    }
+
+    /// Index of the `k`-th occurrence, counted from 0, of `value` between
+    /// start range and end range, `None` if it occurs `k` times or fewer
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn select_in_range<Q>(&self, start: usize, end: usize, value: &Q, k: usize) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.within(start, end, value).get(k).copied()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for Occurrences<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+/// Counts the position lists, not the map nodes
+impl<T> MemoryUsage for Occurrences<T> {
+    fn heap_bytes(&self) -> usize {
+        self.positions.values().map(Vec::heap_bytes).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_select_in_range() {
+        let words = ["get", "put", "get", "del", "get", "put", "get"];
+        let log: Occurrences<String> = words.iter().map(|word| word.to_string()).collect();
+        for start in 0..words.len() {
+            for end in start..words.len() {
+                for word in ["get", "put", "del", "head"] {
+                    let expected: Vec<usize> = (start..=end)
+                        .filter(|&index| words[index] == word)
+                        .collect();
+                    assert_eq!(log.count_in_range(start, end, word), expected.len());
+                    for k in 0..=expected.len() {
+                        assert_eq!(
+                            log.select_in_range(start, end, word, k),
+                            expected.get(k).copied()
+                        );
+                    }
+                }
+            }
+        }
+        assert!(log.heap_bytes() >= words.len() * core::mem::size_of::<usize>());
+    }
+}
@@ -0,0 +1,487 @@
+//! Compressed prefix storage, frame of reference with bit packing and
+//! Elias-Fano coding
+
+use core::{marker::PhantomData, ops::Range};
+
+use alloc::{vec, vec::Vec};
+
+use crate::{MemoryUsage, PrefixStorage};
+
+/// Number of totals sharing one anchor and bit width
+const BLOCK: usize = 64;
+
+/// Unsigned integers [`BitPacked`] can store
+pub trait Packable: Copy {
+    fn to_u64(self) -> u64;
+
+    /// Inverse of [`Self::to_u64`], only called with values it produced
+    fn from_u64(value: u64) -> Self;
+}
+
+macro_rules! impl_packable {
+    ($($ty:ty),*) => {
+        $(
+            impl Packable for $ty {
+                fn to_u64(self) -> u64 {
+                    self as u64
+                }
+
+                fn from_u64(value: u64) -> Self {
+                    value as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_packable!(u8, u16, u32, u64, usize);
+
+/// Prefix storage splitting the totals in blocks of 64, each stored as
+/// offsets from the smallest total of the block using only as many bits as
+/// the largest offset needs
+///
+/// Large arrays of small elements shrink several fold, at the cost of a
+/// few shifts per total read.
+///
+/// ```
+/// use kuehree::{packed::BitPacked, IndexableSumQuery, RangeQuery, SumQuery};
+///
+/// let sum = SumQuery::<BitPacked<u32>>::new([1, 3, 4, 8, 6, 1, 4, 2]);
+/// assert_eq!(sum.query(3, 6), 19);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitPacked<T> {
+    len: usize,
+    /// Smallest total of every block
+    anchors: Vec<u64>,
+    /// Bits used per offset in every block
+    widths: Vec<u8>,
+    /// Bit position the offsets of every block start at
+    starts: Vec<usize>,
+    words: Vec<u64>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> Default for BitPacked<T> {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            anchors: Vec::new(),
+            widths: Vec::new(),
+            starts: Vec::new(),
+            words: Vec::new(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+fn read_bits(words: &[u64], bit: usize, width: u32) -> u64 {
+    if width == 0 {
+        return 0;
+    }
+    let (word, shift) = (bit / 64, (bit % 64) as u32);
+    let mut value = words[word] >> shift;
+    if shift + width > 64 {
+        value |= words[word + 1] << (64 - shift);
+    }
+    if width < 64 {
+        value & ((1 << width) - 1)
+    } else {
+        value
+    }
+}
+
+fn write_bits(words: &mut [u64], bit: usize, width: u32, value: u64) {
+    if width == 0 {
+        return;
+    }
+    let (word, shift) = (bit / 64, (bit % 64) as u32);
+    words[word] |= value << shift;
+    if shift + width > 64 {
+        words[word + 1] |= value >> (64 - shift);
+    }
+}
+
+impl<T: Packable> BitPacked<T> {
+    fn push_block(&mut self, block: &[u64]) {
+        let anchor = block.iter().copied().min().unwrap_or(0);
+        let spread = block.iter().map(|total| total - anchor).max().unwrap_or(0);
+        let width = u64::BITS - spread.leading_zeros();
+        let start = self.starts.last().map_or(0, |start| {
+            start + BLOCK * usize::from(*self.widths.last().unwrap_or(&0))
+        });
+
+        let end = start + block.len() * width as usize;
+        self.words.resize(end.div_ceil(64), 0);
+        for (idx, total) in block.iter().enumerate() {
+            write_bits(
+                &mut self.words,
+                start + idx * width as usize,
+                width,
+                total - anchor,
+            );
+        }
+        self.anchors.push(anchor);
+        self.widths.push(width as u8);
+        self.starts.push(start);
+        self.len += block.len();
+    }
+}
+
+impl<T: Packable> PrefixStorage<T> for BitPacked<T> {
+    const LEADING_ZERO: bool = true;
+
+    type Iter<'a>
+        = Iter<'a, Self, T>
+    where
+        T: 'a;
+
+    fn from_totals(totals: impl Iterator<Item = T>) -> Self {
+        let mut packed = Self::default();
+        let mut block = Vec::with_capacity(BLOCK);
+        for total in totals {
+            block.push(total.to_u64());
+            if block.len() == BLOCK {
+                packed.push_block(&block);
+                block.clear();
+            }
+        }
+        if !block.is_empty() {
+            packed.push_block(&block);
+        }
+        packed.words.shrink_to_fit();
+        packed
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn total(&self, index: usize) -> T {
+        assert!(index < self.len, "index {index} out of bounds");
+        let block = index / BLOCK;
+        let width = u32::from(self.widths[block]);
+        let bit = self.starts[block] + (index % BLOCK) * width as usize;
+        T::from_u64(self.anchors[block] + read_bits(&self.words, bit, width))
+    }
+
+    fn totals(&self) -> Self::Iter<'_> {
+        Iter::new(self)
+    }
+}
+
+/// Iterator over the totals of a [`BitPacked`] or an [`EliasFano`]
+#[derive(Debug, Clone)]
+pub struct Iter<'a, C, T> {
+    storage: &'a C,
+    indices: Range<usize>,
+    _phantom: PhantomData<T>,
+}
+
+impl<'a, C: PrefixStorage<T>, T> Iter<'a, C, T> {
+    fn new(storage: &'a C) -> Self {
+        Self {
+            storage,
+            indices: 0..storage.len(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<C: PrefixStorage<T>, T> Iterator for Iter<'_, C, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.indices.next().map(|idx| self.storage.total(idx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<C: PrefixStorage<T>, T> ExactSizeIterator for Iter<'_, C, T> {}
+
+impl<T> MemoryUsage for BitPacked<T> {
+    fn heap_bytes(&self) -> usize {
+        self.anchors.heap_bytes()
+            + self.widths.heap_bytes()
+            + self.starts.heap_bytes()
+            + self.words.heap_bytes()
+    }
+}
+
+/// Number of ones in a block of the select directory of an [`EliasFano`]
+const SAMPLE: usize = 256;
+
+/// Ones between two offsets stored for a [`Block::Dense`]
+const SUB_SAMPLE: usize = 32;
+
+/// Bits a block has to span to store the position of each of its ones
+const SPARSE_SPAN: usize = 1 << 16;
+
+/// Select directory entry of [`SAMPLE`] consecutive ones of an
+/// [`EliasFano`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Block {
+    /// Spans fewer than [`SPARSE_SPAN`] bits, stores the position of its
+    /// first one and the offset of every [`SUB_SAMPLE`]th one from it
+    Dense {
+        start: usize,
+        offsets: [u16; SAMPLE / SUB_SAMPLE],
+    },
+    /// Spans at least [`SPARSE_SPAN`] bits, its positions are stored from
+    /// index `first` of [`EliasFano::sparse`]
+    Sparse { first: usize },
+}
+
+/// Prefix storage for non decreasing totals, as the prefix sums of unsigned
+/// data are, close to the information theoretic minimum of space
+///
+/// Every total is split in `low` low bits, stored packed, and the
+/// remaining high bits, stored in unary as gaps in a bit vector. Reading a
+/// total is a select on that bit vector, through a directory in the style
+/// of the darray of Okanohara and Sadakane: the ones are grouped in blocks
+/// of 256, a block spanning 2^16 bits or more stores the position of every
+/// one, and any other block the offset of every 32nd one. A select then
+/// counts the ones of at most 1024 words, a handful when the totals grow
+/// evenly, so reading a total is O(1).
+///
+/// The bit vector holds at most about `3n` bits, so at most `3n / 2^16`
+/// blocks are sparse and the directory adds about a bit per total.
+///
+/// ```
+/// use kuehree::{packed::EliasFano, IndexableSumQuery, RangeQuery, SumQuery};
+///
+/// let sum = SumQuery::<EliasFano<u64>>::new([1, 3, 4, 8, 6, 1, 4, 2]);
+/// assert_eq!(sum.query(3, 6), 19);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EliasFano<T> {
+    len: usize,
+    low: u32,
+    lows: Vec<u64>,
+    highs: Vec<u64>,
+    /// Select directory, one entry per block of 256 ones of `highs`
+    blocks: Vec<Block>,
+    /// Positions of the ones of the sparse blocks
+    sparse: Vec<usize>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> Default for EliasFano<T> {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            low: 0,
+            lows: Vec::new(),
+            highs: Vec::new(),
+            blocks: Vec::new(),
+            sparse: Vec::new(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> EliasFano<T> {
+    /// Bit position of the one with rank `rank` in `highs`
+    ///
+    /// Algorithmic complexity: O(1), at most `SPARSE_SPAN / 64` words
+    fn select(&self, rank: usize) -> usize {
+        let within = rank % SAMPLE;
+        let start = match self.blocks[rank / SAMPLE] {
+            Block::Sparse { first } => return self.sparse[first + within],
+            Block::Dense { start, offsets } => start + usize::from(offsets[within / SUB_SAMPLE]),
+        };
+        let mut rank = (within % SUB_SAMPLE) as u32;
+        let mut word = start / 64;
+        let mut bits = self.highs[word] & (u64::MAX << (start % 64));
+        loop {
+            let ones = bits.count_ones();
+            if rank < ones {
+                for _ in 0..rank {
+                    bits &= bits - 1;
+                }
+                return word * 64 + bits.trailing_zeros() as usize;
+            }
+            rank -= ones;
+            word += 1;
+            bits = self.highs[word];
+        }
+    }
+}
+
+impl<T: Packable> PrefixStorage<T> for EliasFano<T> {
+    const LEADING_ZERO: bool = true;
+
+    type Iter<'a>
+        = Iter<'a, Self, T>
+    where
+        T: 'a;
+
+    /// # Panics
+    ///
+    /// If the totals decrease
+    fn from_totals(totals: impl Iterator<Item = T>) -> Self {
+        let totals: Vec<u64> = totals.map(Packable::to_u64).collect();
+        assert!(
+            totals.windows(2).all(|pair| pair[0] <= pair[1]),
+            "Elias-Fano coding needs non decreasing totals"
+        );
+        let Some(&last) = totals.last() else {
+            return Self::default();
+        };
+
+        let len = totals.len();
+        let low = (last / len as u64).checked_ilog2().unwrap_or(0);
+        let high_bits = len + (last >> low) as usize + 1;
+        let mut ef = Self {
+            len,
+            low,
+            lows: vec![0; (len * low as usize).div_ceil(64)],
+            highs: vec![0; high_bits.div_ceil(64)],
+            blocks: Vec::with_capacity(len.div_ceil(SAMPLE)),
+            sparse: Vec::new(),
+            _phantom: PhantomData,
+        };
+        let mut ones = Vec::with_capacity(len);
+        for (idx, total) in totals.into_iter().enumerate() {
+            let low_bits = if low == 0 {
+                0
+            } else {
+                total & (u64::MAX >> (64 - low))
+            };
+            write_bits(&mut ef.lows, idx * low as usize, low, low_bits);
+            let bit = (total >> low) as usize + idx;
+            ef.highs[bit / 64] |= 1 << (bit % 64);
+            ones.push(bit);
+        }
+        for block in ones.chunks(SAMPLE) {
+            let start = block[0];
+            if block[block.len() - 1] - start >= SPARSE_SPAN {
+                ef.blocks.push(Block::Sparse {
+                    first: ef.sparse.len(),
+                });
+                ef.sparse.extend_from_slice(block);
+            } else {
+                let mut offsets = [0; SAMPLE / SUB_SAMPLE];
+                for (offset, &bit) in offsets.iter_mut().zip(block.iter().step_by(SUB_SAMPLE)) {
+                    *offset = (bit - start) as u16;
+                }
+                ef.blocks.push(Block::Dense { start, offsets });
+            }
+        }
+        ef
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn total(&self, index: usize) -> T {
+        assert!(index < self.len, "index {index} out of bounds");
+        let high = (self.select(index) - index) as u64;
+        let low = read_bits(&self.lows, index * self.low as usize, self.low);
+        T::from_u64((high << self.low) | low)
+    }
+
+    fn totals(&self) -> Self::Iter<'_> {
+        Iter::new(self)
+    }
+}
+
+impl<T> MemoryUsage for EliasFano<T> {
+    fn heap_bytes(&self) -> usize {
+        self.lows.heap_bytes()
+            + self.highs.heap_bytes()
+            + self.blocks.heap_bytes()
+            + self.sparse.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{IndexableSumQuery, RangeQuery, SumQuery};
+
+    #[test]
+    fn test_bit_packed() {
+        let data: Vec<u64> = (0..1000u64).map(|idx| idx * 7 % 13).collect();
+        let packed = SumQuery::<BitPacked<u64>>::new(data.iter().copied());
+        let plain = SumQuery::<Vec<u64>>::new(data.iter().copied());
+
+        assert_eq!(packed.len(), plain.len());
+        for (start, end) in [(0, 999), (0, 0), (63, 64), (100, 700), (999, 999)] {
+            assert_eq!(packed.query(start, end), plain.query(start, end));
+        }
+        assert!(packed
+            .prefix_sum_array()
+            .totals()
+            .eq(plain.prefix_sums().iter().copied()));
+        assert!(packed.heap_bytes() * 3 < plain.heap_bytes());
+    }
+
+    #[test]
+    fn test_wide_offsets() {
+        let data = [u64::MAX / 2, 0, u64::MAX / 2, 1];
+        let packed = SumQuery::<BitPacked<u64>>::new(data);
+        assert_eq!(packed.query(0, 3), u64::MAX);
+        assert_eq!(packed.query(1, 2), u64::MAX / 2);
+        assert_eq!(SumQuery::<BitPacked<u8>>::new([]).len(), 0);
+    }
+
+    #[test]
+    fn test_elias_fano() {
+        let data: Vec<u64> = (0..2000u64).map(|idx| idx * 7 % 13).collect();
+        let ef = SumQuery::<EliasFano<u64>>::new(data.iter().copied());
+        let plain = SumQuery::<Vec<u64>>::new(data.iter().copied());
+
+        assert!(ef
+            .prefix_sum_array()
+            .totals()
+            .eq(plain.prefix_sums().iter().copied()));
+        for (start, end) in [(0, 1999), (0, 0), (255, 256), (100, 1700), (1999, 1999)] {
+            assert_eq!(ef.query(start, end), plain.query(start, end));
+        }
+        assert!(ef.heap_bytes() * 8 < plain.heap_bytes());
+
+        let sparse = SumQuery::<EliasFano<u32>>::new([0, 0, 1 << 30, 0, 5]);
+        assert_eq!(sparse.query(1, 2), 1 << 30);
+        assert_eq!(sparse.query(3, 4), 5);
+        assert_eq!(SumQuery::<EliasFano<u8>>::new([]).len(), 0);
+    }
+
+    #[test]
+    fn test_elias_fano_select() {
+        // The jump spans more than 2^16 bits of `highs`, making its block
+        // sparse
+        let data: Vec<u64> = (0..100_000u64)
+            .map(|idx| if idx == 50_000 { 1 << 40 } else { idx % 3 })
+            .collect();
+        let ef = SumQuery::<EliasFano<u64>>::new(data.iter().copied());
+        let plain = SumQuery::<Vec<u64>>::new(data.iter().copied());
+
+        let blocks = &ef.prefix_sum_array().blocks;
+        assert!(blocks
+            .iter()
+            .any(|block| matches!(block, Block::Sparse { .. })));
+        assert!(blocks
+            .iter()
+            .any(|block| matches!(block, Block::Dense { .. })));
+        assert!(ef
+            .prefix_sum_array()
+            .totals()
+            .eq(plain.prefix_sums().iter().copied()));
+        for start in (0..100_000).step_by(997) {
+            for end in (start..100_000).step_by(1009) {
+                assert_eq!(ef.query(start, end), plain.query(start, end));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "non decreasing")]
+    fn test_elias_fano_decreasing() {
+        EliasFano::<u8>::from_totals([0, 2, 1].into_iter());
+    }
+}
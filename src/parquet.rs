@@ -0,0 +1,261 @@
+//! Streaming a numeric Parquet column into a structure
+//!
+//! The column is decoded one batch at a time and handed to the structure
+//! as an iterator, so the whole column is never held in an intermediate
+//! `Vec`. Missing values are handled by a [`NullPolicy`].
+
+use core::ops::{Add, Sub};
+
+use ::parquet::{
+    column::reader::ColumnReaderImpl,
+    data_type::DataType,
+    file::reader::{ChunkReader, FileReader, SerializedFileReader},
+};
+
+pub use crate::load::LoadError;
+use crate::{load::stream, Error, NullPolicy, PrefixStorage, SumQuery, Zero};
+
+/// Records decoded per batch
+const BATCH: usize = 4096;
+
+/// Values of one column, row group after row group
+struct ColumnValues<'a, R: ChunkReader + 'static, D: DataType> {
+    file: &'a SerializedFileReader<R>,
+    column: usize,
+    max_def_level: i16,
+    nulls: NullPolicy,
+    index: usize,
+    next_row_group: usize,
+    reader: Option<ColumnReaderImpl<D>>,
+    levels: Vec<i16>,
+    values: Vec<D::T>,
+    level_pos: usize,
+    value_pos: usize,
+}
+
+impl<R: ChunkReader + 'static, D: DataType> ColumnValues<'_, R, D> {
+    /// Decode the next batch, `false` once the column is exhausted
+    fn refill(&mut self) -> Result<bool, LoadError> {
+        loop {
+            if let Some(reader) = &mut self.reader {
+                self.levels.clear();
+                self.values.clear();
+                (self.level_pos, self.value_pos) = (0, 0);
+                let levels = (self.max_def_level > 0).then_some(&mut self.levels);
+                let (records, _, _) = reader.read_records(BATCH, levels, None, &mut self.values)?;
+                if records > 0 {
+                    if self.max_def_level == 0 {
+                        self.levels.resize(self.values.len(), 0);
+                    }
+                    return Ok(true);
+                }
+                self.reader = None;
+            }
+
+            if self.next_row_group == self.file.num_row_groups() {
+                return Ok(false);
+            }
+            let row_group = self.file.get_row_group(self.next_row_group)?;
+            self.next_row_group += 1;
+            let reader = D::get_column_reader(row_group.get_column_reader(self.column)?);
+            self.reader = Some(reader.ok_or(Error::DataType)?);
+        }
+    }
+
+    /// The next value, missing ones handled by `nulls`
+    fn next_value(&mut self) -> Result<Option<D::T>, LoadError>
+    where
+        D::T: Zero,
+    {
+        if self.level_pos == self.levels.len() && !self.refill()? {
+            return Ok(None);
+        }
+        let index = self.index;
+        self.index += 1;
+        let level = self.levels[self.level_pos];
+        self.level_pos += 1;
+        if level < self.max_def_level {
+            return match self.nulls {
+                NullPolicy::Zero => Ok(Some(D::T::zero())),
+                NullPolicy::Reject => Err(Error::Null { index }.into()),
+            };
+        }
+        let value = self.values[self.value_pos].clone();
+        self.value_pos += 1;
+        Ok(Some(value))
+    }
+}
+
+/// Stream column `column` of `file` into `build`, handling missing values
+/// with `nulls`
+///
+/// `build` receives the values as an iterator, e.g. `SparseTable::new`.
+/// The iterator ends early on failure, whose error is returned instead of
+/// the structure.
+///
+/// Algorithmic complexity: O(n), holding one batch of values at a time
+///
+/// # Errors
+///
+/// - [`LoadError::Parquet`] if reading the file fails
+/// - [`Error::OutOfBounds`] if `file` has no column `column`
+/// - [`Error::DataType`] if the column does not hold `D` values
+/// - [`Error::Null`] at the first missing value with [`NullPolicy::Reject`]
+pub fn load<R, D, S>(
+    file: &SerializedFileReader<R>,
+    column: usize,
+    nulls: NullPolicy,
+    build: impl FnOnce(&mut dyn Iterator<Item = D::T>) -> S,
+) -> Result<S, LoadError>
+where
+    R: ChunkReader + 'static,
+    D: DataType,
+    D::T: Zero,
+{
+    let schema = file.metadata().file_metadata().schema_descr();
+    if column >= schema.num_columns() {
+        return Err(Error::OutOfBounds {
+            index: column,
+            len: schema.num_columns(),
+        }
+        .into());
+    }
+
+    let mut values = ColumnValues::<R, D> {
+        file,
+        column,
+        max_def_level: schema.column(column).max_def_level(),
+        nulls,
+        index: 0,
+        next_row_group: 0,
+        reader: None,
+        levels: Vec::new(),
+        values: Vec::new(),
+        level_pos: 0,
+        value_pos: 0,
+    };
+    stream(|| values.next_value(), build)
+}
+
+impl<C> SumQuery<C> {
+    /// Construct `Self` from column `column` of a Parquet file, streamed
+    /// in batches and handling missing values with `nulls`
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Errors
+    ///
+    /// See [`load`], and [`Error::LengthMismatch`] if `C` holds a fixed
+    /// number of totals and the column has another length
+    pub fn from_parquet<R, D>(
+        file: &SerializedFileReader<R>,
+        column: usize,
+        nulls: NullPolicy,
+    ) -> Result<Self, LoadError>
+    where
+        R: ChunkReader + 'static,
+        D: DataType,
+        D::T: Copy + Add<Output = D::T> + Sub<Output = D::T> + Zero,
+        C: PrefixStorage<D::T>,
+    {
+        Ok(load::<R, D, _>(file, column, nulls, |values| {
+            Self::try_collect(values)
+        })??)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        rmq::{Min, SparseTable},
+        RangeQuery,
+    };
+    use ::parquet::{
+        data_type::{DoubleType, Int64Type},
+        file::{properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+    use std::{fs::File, sync::Arc};
+
+    /// Two row groups of `len` rows, a required `INT64` column and an
+    /// optional one missing every third value
+    fn write_file(path: &std::path::Path, len: i64) {
+        let schema =
+            parse_message_type("message test { REQUIRED INT64 dense; OPTIONAL INT64 sparse; }")
+                .unwrap();
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut writer =
+            SerializedFileWriter::new(File::create(path).unwrap(), Arc::new(schema), props)
+                .unwrap();
+        for group in 0..2 {
+            let rows: Vec<i64> = (0..len).map(|i| group * len + i).collect();
+            let mut row_group = writer.next_row_group().unwrap();
+
+            let mut column = row_group.next_column().unwrap().unwrap();
+            column
+                .typed::<Int64Type>()
+                .write_batch(&rows, None, None)
+                .unwrap();
+            column.close().unwrap();
+
+            let present: Vec<i64> = rows.iter().copied().filter(|i| i % 3 != 0).collect();
+            let levels: Vec<i16> = rows.iter().map(|i| i16::from(i % 3 != 0)).collect();
+            let mut column = row_group.next_column().unwrap().unwrap();
+            column
+                .typed::<Int64Type>()
+                .write_batch(&present, Some(&levels), None)
+                .unwrap();
+            column.close().unwrap();
+
+            row_group.close().unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_parquet() {
+        let path = std::env::temp_dir().join(format!("kuehree-{}.parquet", std::process::id()));
+        write_file(&path, 5_000);
+        let file = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+
+        let dense =
+            SumQuery::<Vec<i64>>::from_parquet::<_, Int64Type>(&file, 0, NullPolicy::Reject)
+                .unwrap();
+        assert_eq!(dense.query(0, 9_999), 9_999 * 10_000 / 2);
+
+        let sparse =
+            SumQuery::<Vec<i64>>::from_parquet::<_, Int64Type>(&file, 1, NullPolicy::Zero).unwrap();
+        assert_eq!(sparse.query(0, 5), 1 + 2 + 4 + 5);
+        assert_eq!(sparse.query(6_000, 6_002), 6_001 + 6_002);
+        let min = load::<_, Int64Type, _>(&file, 1, NullPolicy::Zero, |values| {
+            SparseTable::<_, Min>::new(values)
+        })
+        .unwrap();
+        assert_eq!(min.query(1, 2), 1);
+
+        let rejected =
+            SumQuery::<Vec<i64>>::from_parquet::<_, Int64Type>(&file, 1, NullPolicy::Reject);
+        assert!(matches!(
+            rejected,
+            Err(LoadError::Query(Error::Null { index: 0 }))
+        ));
+        let fixed =
+            SumQuery::<[i64; 3]>::from_parquet::<_, Int64Type>(&file, 1, NullPolicy::Reject);
+        assert!(matches!(
+            fixed,
+            Err(LoadError::Query(Error::Null { index: 0 }))
+        ));
+        let wrong_type =
+            SumQuery::<Vec<f64>>::from_parquet::<_, DoubleType>(&file, 0, NullPolicy::Zero);
+        assert!(matches!(wrong_type, Err(LoadError::Query(Error::DataType))));
+        let missing =
+            SumQuery::<Vec<i64>>::from_parquet::<_, Int64Type>(&file, 2, NullPolicy::Zero);
+        assert!(matches!(
+            missing,
+            Err(LoadError::Query(Error::OutOfBounds { index: 2, len: 2 }))
+        ));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,579 @@
+//! Partially persistent segment and Fenwick trees, every update creates a
+//! new version and all past versions stay queryable
+
+use core::{
+    marker::PhantomData,
+    ops::{Add, Sub},
+};
+
+use alloc::{vec, vec::Vec};
+
+use crate::{rmq::Operation, trace::BuildSpan, MemoryUsage, RangeQuery, UpdatableRangeQuery, Zero};
+
+/// Node of the tree, leaves have no children
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Node<T> {
+    value: T,
+    children: Option<(usize, usize)>,
+}
+
+/// Last element of the left half of `[lo, hi]`, the split of every node
+/// based segment tree
+pub(crate) fn mid(lo: usize, hi: usize) -> usize {
+    lo + (hi - lo + 1).div_ceil(2) - 1
+}
+
+/// Segment tree recording a version per update through path copying
+///
+/// Version 0 is the tree as built and version `k` the tree after the
+/// `k`-th update. An update copies the O(log n) nodes on the path to the
+/// updated leaf and shares the rest with the previous version.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PersistentSegmentTree<T, M> {
+    nodes: Vec<Node<T>>,
+    roots: Vec<usize>,
+    len: usize,
+    _phantom: PhantomData<M>,
+}
+
+impl<T, M> PersistentSegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    /// Construct `Self`, versions start at 0
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Panics
+    ///
+    /// If `data` is empty
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("PersistentSegmentTree");
+        let leaves: Vec<T> = data.into_iter().collect();
+        let len = leaves.len();
+        span.record_len(len);
+        assert!(
+            len > 0,
+            "persistent segment tree needs at least one element"
+        );
+
+        let mut tree = Self {
+            nodes: Vec::with_capacity(2 * len - 1),
+            roots: Vec::new(),
+            len,
+            _phantom: PhantomData,
+        };
+        let root = tree.build(&leaves);
+        tree.roots.push(root);
+        tree
+    }
+
+    fn build(&mut self, leaves: &[T]) -> usize {
+        if let [value] = leaves {
+            return self.push(*value, None);
+        }
+        let (left, right) = leaves.split_at(leaves.len().div_ceil(2));
+        let left = self.build(left);
+        let right = self.build(right);
+        self.push_parent(left, right)
+    }
+
+    fn push(&mut self, value: T, children: Option<(usize, usize)>) -> usize {
+        self.nodes.push(Node { value, children });
+        self.nodes.len() - 1
+    }
+
+    fn push_parent(&mut self, left: usize, right: usize) -> usize {
+        let value = M::combine(self.nodes[left].value, self.nodes[right].value);
+        self.push(value, Some((left, right)))
+    }
+
+    /// Number of elements in the tree
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Always `false`, the tree is never empty
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Current version, the number of updates made so far
+    pub fn version(&self) -> usize {
+        self.roots.len() - 1
+    }
+
+    /// Query between start range and end range as they were at `version`
+    ///
+    /// Algorithmic complexity: O(log n)
+    ///
+    /// # Panics
+    ///
+    /// If `version` is newer than [`Self::version`] or the range is invalid
+    pub fn query_at(&self, version: usize, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len);
+        assert!(
+            version < self.roots.len(),
+            "version {version} does not exist yet"
+        );
+        self.fold(self.roots[version], 0, self.len - 1, start, end)
+    }
+
+    /// Query between start range and end range in the current version
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        self.query_at(self.version(), start, end)
+    }
+
+    fn fold(&self, idx: usize, lo: usize, hi: usize, start: usize, end: usize) -> T {
+        let node = self.nodes[idx];
+        let Some((left, right)) = node.children else {
+            return node.value;
+        };
+        if start == lo && end == hi {
+            return node.value;
+        }
+        let mid = mid(lo, hi);
+        if end <= mid {
+            self.fold(left, lo, mid, start, end)
+        } else if start > mid {
+            self.fold(right, mid + 1, hi, start, end)
+        } else {
+            M::combine(
+                self.fold(left, lo, mid, start, mid),
+                self.fold(right, mid + 1, hi, mid + 1, end),
+            )
+        }
+    }
+
+    /// Element at `index` as it was at `version`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn get_at(&self, version: usize, index: usize) -> T {
+        self.query_at(version, index, index)
+    }
+
+    /// Current element at `index`
+    pub fn get(&self, index: usize) -> T {
+        self.query(index, index)
+    }
+
+    /// Replace the element at `index` with `value`, creating a new version
+    ///
+    /// Algorithmic complexity: O(log n) time and memory
+    pub fn update(&mut self, index: usize, value: T) {
+        assert!(index < self.len);
+        let root = self.set(self.roots[self.version()], 0, self.len - 1, index, value);
+        self.roots.push(root);
+    }
+
+    fn set(&mut self, idx: usize, lo: usize, hi: usize, index: usize, value: T) -> usize {
+        let Some((left, right)) = self.nodes[idx].children else {
+            return self.push(value, None);
+        };
+        let mid = mid(lo, hi);
+        if index <= mid {
+            let left = self.set(left, lo, mid, index, value);
+            self.push_parent(left, right)
+        } else {
+            let right = self.set(right, mid + 1, hi, index, value);
+            self.push_parent(left, right)
+        }
+    }
+}
+
+impl<T, M> RangeQuery<T> for PersistentSegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T, M> UpdatableRangeQuery<T> for PersistentSegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+}
+
+impl<T, M> MemoryUsage for PersistentSegmentTree<T, M> {
+    fn heap_bytes(&self) -> usize {
+        self.nodes.heap_bytes() + self.roots.heap_bytes()
+    }
+}
+
+/// Fenwick tree recording a version per update
+///
+/// Version 0 is the tree as built and version `k` the tree after the
+/// `k`-th update. Every node keeps the values it held, tagged with the
+/// version that wrote them, so an update appends to the O(log n) nodes it
+/// touches and a read of a past version searches each node it visits.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PersistentFenwick<T> {
+    /// `(version, value)` pairs of each node by increasing version, node 0
+    /// is unused
+    history: Vec<Vec<(usize, T)>>,
+    version: usize,
+}
+
+impl<T> PersistentFenwick<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    /// Construct `Self`, versions start at 0
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("PersistentFenwick");
+        let mut tree = vec![T::zero()];
+        tree.extend(data);
+        span.record_len(tree.len() - 1);
+        for idx in 1..tree.len() {
+            let parent = idx + (idx & idx.wrapping_neg());
+            if parent < tree.len() {
+                tree[parent] = tree[parent] + tree[idx];
+            }
+        }
+
+        let history = tree.into_iter().map(|node| vec![(0, node)]).collect();
+        Self {
+            history,
+            version: 0,
+        }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.history.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Current version, the number of updates made so far
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    fn node_at(&self, idx: usize, version: usize) -> T {
+        let history = &self.history[idx];
+        history[history.partition_point(|&(written, _)| written <= version) - 1].1
+    }
+
+    /// Sum of the first `count` elements as they were at `version`
+    ///
+    /// Algorithmic complexity: O(log n log versions)
+    ///
+    /// # Panics
+    ///
+    /// If `version` is newer than [`Self::version`] or `count` exceeds
+    /// [`Self::len`]
+    pub fn prefix_sum_at(&self, version: usize, count: usize) -> T {
+        assert!(count <= self.len());
+        assert!(
+            version <= self.version,
+            "version {version} does not exist yet"
+        );
+        let (mut sum, mut idx) = (T::zero(), count);
+        while idx > 0 {
+            sum = sum + self.node_at(idx, version);
+            idx &= idx - 1;
+        }
+        sum
+    }
+
+    /// Query between start range and end range as they were at `version`
+    ///
+    /// Algorithmic complexity: O(log n log versions)
+    pub fn query_at(&self, version: usize, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len());
+        self.prefix_sum_at(version, end + 1) - self.prefix_sum_at(version, start)
+    }
+
+    /// Query between start range and end range in the current version
+    ///
+    /// The current version searches the node histories like any other.
+    ///
+    /// Algorithmic complexity: O(log n log versions)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        self.query_at(self.version, start, end)
+    }
+
+    /// Element at `index` as it was at `version`
+    ///
+    /// Algorithmic complexity: O(log n log versions)
+    pub fn get_at(&self, version: usize, index: usize) -> T {
+        self.query_at(version, index, index)
+    }
+
+    /// Current element at `index`
+    pub fn get(&self, index: usize) -> T {
+        self.query(index, index)
+    }
+
+    /// Add `delta` to the element at `index`, creating a new version
+    ///
+    /// Algorithmic complexity: O(log n) time and memory
+    pub fn add(&mut self, index: usize, delta: T) {
+        self.push_version(index, |node| node + delta);
+    }
+
+    /// Replace the element at `index` with `value`, creating a new version
+    ///
+    /// Algorithmic complexity: O(log n) time and memory
+    pub fn update(&mut self, index: usize, value: T) {
+        let old = self.get(index);
+        // Removing the old value first keeps unsigned nodes from wrapping
+        self.push_version(index, |node| node - old + value);
+    }
+
+    /// New version with `apply` mapping every node covering `index`
+    fn push_version(&mut self, index: usize, apply: impl Fn(T) -> T) {
+        assert!(index < self.len());
+        self.version += 1;
+        let mut idx = index + 1;
+        while idx < self.history.len() {
+            let history = &mut self.history[idx];
+            let (_, node) = history[history.len() - 1];
+            history.push((self.version, apply(node)));
+            idx += idx & idx.wrapping_neg();
+        }
+    }
+}
+
+impl<T> PersistentFenwick<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero + PartialOrd,
+{
+    /// Index of the element holding the `k`-th unit, counted from 0, of the
+    /// elements added between version `from` and version `to`, `None` if
+    /// they add up to `k` or less
+    ///
+    /// With counts of compressed values this is the `k`-th smallest value
+    /// inserted between the two versions, see [`KthSmallest`]. Elements
+    /// must not have decreased between the two versions.
+    ///
+    /// Algorithmic complexity: O(log n log versions)
+    pub fn search_between(&self, from: usize, to: usize, mut k: T) -> Option<usize> {
+        assert!(from <= to && to <= self.version, "invalid versions");
+        let mut pos = 0;
+        let mut step = self.len().checked_ilog2().map_or(0, |log| 1 << log);
+        while step > 0 {
+            if pos + step <= self.len() {
+                let count = self.node_at(pos + step, to) - self.node_at(pos + step, from);
+                if count <= k {
+                    pos += step;
+                    k = k - count;
+                }
+            }
+            step >>= 1;
+        }
+        (pos < self.len()).then_some(pos)
+    }
+}
+
+impl<T> RangeQuery<T> for PersistentFenwick<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> UpdatableRangeQuery<T> for PersistentFenwick<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+}
+
+impl<T> MemoryUsage for PersistentFenwick<T> {
+    fn heap_bytes(&self) -> usize {
+        self.history.heap_bytes() + self.history.iter().map(Vec::heap_bytes).sum::<usize>()
+    }
+}
+
+/// `k`-th smallest element of any range, through a [`PersistentFenwick`]
+/// of value counts
+///
+/// Version `i` counts the compressed values of the first `i` elements, so
+/// the counts of a range are the difference of two versions.
+///
+/// ```
+/// use kuehree::persistent::KthSmallest;
+///
+/// let kth = KthSmallest::new([50, 10, 40, 20, 30]);
+/// assert_eq!(kth.kth(1, 3, 0), 10);
+/// assert_eq!(kth.kth(1, 3, 2), 40);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KthSmallest<T> {
+    /// Distinct values in increasing order
+    values: Vec<T>,
+    counts: PersistentFenwick<u32>,
+}
+
+impl<T: Copy + Ord> KthSmallest<T> {
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n log n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let data: Vec<T> = data.into_iter().collect();
+        let mut values = data.clone();
+        values.sort_unstable();
+        values.dedup();
+
+        let mut counts = PersistentFenwick::new(vec![0; values.len()]);
+        for value in data {
+            let rank = values
+                .binary_search(&value)
+                .unwrap_or_else(|_| unreachable!());
+            counts.add(rank, 1);
+        }
+        Self { values, counts }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.counts.version()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `k`-th smallest element between `start` and `end`, counted from 0
+    ///
+    /// Algorithmic complexity: O(log n log versions)
+    ///
+    /// # Panics
+    ///
+    /// If the range is invalid or holds `k` elements or fewer
+    pub fn kth(&self, start: usize, end: usize, k: usize) -> T {
+        assert!(end >= start && end < self.len());
+        assert!(
+            k <= end - start,
+            "range holds fewer than {} elements",
+            k + 1
+        );
+        let k = u32::try_from(k).unwrap_or(u32::MAX);
+        let rank = self.counts.search_between(start, end + 1, k);
+        self.values[rank.unwrap_or_else(|| unreachable!())]
+    }
+}
+
+impl<T> MemoryUsage for KthSmallest<T> {
+    fn heap_bytes(&self) -> usize {
+        self.values.heap_bytes() + self.counts.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmq::{Max, Min};
+
+    #[test]
+    fn test_query_at() {
+        let arr = [1u32, 3, 4, 8, 6, 1, 4];
+        let mut tree = PersistentSegmentTree::<_, Max>::new(arr);
+        assert_eq!(tree.version(), 0);
+        tree.update(3, 2);
+        tree.update(0, 9);
+        tree.update(0, 0);
+        assert_eq!(tree.version(), 3);
+
+        assert_eq!(tree.query_at(0, 0, 6), 8);
+        assert_eq!(tree.query_at(1, 0, 6), 6);
+        assert_eq!(tree.query_at(2, 0, 2), 9);
+        assert_eq!(tree.query(0, 2), 4);
+        assert_eq!(tree.get_at(1, 0), 1);
+        assert_eq!(tree.get(0), 0);
+
+        let mut min = PersistentSegmentTree::<_, Min>::new(arr);
+        for (index, value) in (0..arr.len()).zip(10..) {
+            min.update(index, value);
+            for version in 0..=min.version() {
+                for start in 0..arr.len() {
+                    for end in start..arr.len() {
+                        let expected = (start..=end)
+                            .map(|idx| {
+                                if idx < version {
+                                    10 + idx as u32
+                                } else {
+                                    arr[idx]
+                                }
+                            })
+                            .min();
+                        assert_eq!(Some(min.query_at(version, start, end)), expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_persistent_fenwick() {
+        let arr = [5u32, 0, 2, 7, 1];
+        let mut tree = PersistentFenwick::new(arr);
+        tree.add(1, 4);
+        tree.update(3, 3);
+        tree.update(0, 0);
+        assert_eq!(tree.version(), 3);
+        assert_eq!(tree.query_at(0, 0, 4), 15);
+        assert_eq!(tree.query_at(1, 0, 1), 9);
+        assert_eq!(tree.query_at(2, 2, 3), 5);
+        assert_eq!(tree.query(0, 4), 10);
+        assert_eq!(tree.get_at(2, 0), 5);
+        assert_eq!(tree.get(0), 0);
+        assert_eq!(tree.search_between(0, 1, 3), Some(1));
+        assert_eq!(tree.search_between(0, 1, 4), None);
+
+        let data = [3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5];
+        let kth = KthSmallest::new(data);
+        for start in 0..data.len() {
+            for end in start..data.len() {
+                let mut range = data[start..=end].to_vec();
+                range.sort_unstable();
+                for (k, &expected) in range.iter().enumerate() {
+                    assert_eq!(kth.kth(start, end, k), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "does not exist yet")]
+    fn test_future_version() {
+        let tree = PersistentSegmentTree::<_, Max>::new([1, 2]);
+        tree.query_at(1, 0, 1);
+    }
+}
@@ -0,0 +1,137 @@
+//! Building from Polars series
+//!
+//! Repeated window and range aggregations over a column can then run
+//! outside Polars' own expressions. Missing values are handled by a
+//! [`NullPolicy`], the [`TryFrom`] impls reject them.
+//!
+//! [`SumQuery`] already converts from anything `AsRef<[T]>`, which rules
+//! out a `TryFrom<&Series>` for it, use [`SumQuery::from_series`].
+
+use core::ops::{Add, Sub};
+
+use polars_core::{
+    datatypes::{NumericNative, PolarsNumericType},
+    series::Series,
+};
+
+use crate::{
+    fenwick::Fenwick,
+    rmq::{Idempotent, Operation, SegmentTree, SparseTable},
+    Error, IndexableSumQuery, NullPolicy, PrefixStorage, SumQuery, Zero,
+};
+
+/// Values of `series` with missing ones handled by `nulls`, for building
+/// any structure of this crate
+///
+/// Algorithmic complexity: O(1), O(n) with [`NullPolicy::Reject`]
+///
+/// # Errors
+///
+/// - [`Error::DataType`] if `series` does not hold `T` elements
+/// - [`Error::Null`] at the first missing value with [`NullPolicy::Reject`]
+pub fn values<T>(series: &Series, nulls: NullPolicy) -> Result<impl Iterator<Item = T> + '_, Error>
+where
+    T: NumericNative + Zero,
+    T::PolarsType: PolarsNumericType<Native = T>,
+{
+    let column = series
+        .unpack::<T::PolarsType>()
+        .map_err(|_| Error::DataType)?;
+    if nulls == NullPolicy::Reject && column.null_count() > 0 {
+        if let Some(index) = column.iter().position(|value| value.is_none()) {
+            return Err(Error::Null { index });
+        }
+    }
+    Ok(column
+        .iter()
+        .map(|value| value.unwrap_or_else(<T as Zero>::zero)))
+}
+
+impl<C> SumQuery<C> {
+    /// Construct `Self` from a numeric series, handling missing values
+    /// with `nulls`
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Errors
+    ///
+    /// - [`Error::DataType`] if `series` does not hold `T` elements
+    /// - [`Error::Null`] at the first missing value with
+    ///   [`NullPolicy::Reject`]
+    pub fn from_series<T>(series: &Series, nulls: NullPolicy) -> Result<Self, Error>
+    where
+        T: NumericNative + Zero,
+        T::PolarsType: PolarsNumericType<Native = T>,
+        C: PrefixStorage<T>,
+    {
+        Ok(<Self as IndexableSumQuery<T>>::new(values(series, nulls)?))
+    }
+}
+
+impl<T> TryFrom<&Series> for Fenwick<T>
+where
+    T: NumericNative + Copy + Add<Output = T> + Sub<Output = T> + Zero,
+    T::PolarsType: PolarsNumericType<Native = T>,
+{
+    type Error = Error;
+
+    fn try_from(series: &Series) -> Result<Self, Error> {
+        Ok(Self::new(values(series, NullPolicy::Reject)?))
+    }
+}
+
+impl<T, M> TryFrom<&Series> for SegmentTree<T, M>
+where
+    T: NumericNative + Zero,
+    T::PolarsType: PolarsNumericType<Native = T>,
+    M: Operation<T>,
+{
+    type Error = Error;
+
+    fn try_from(series: &Series) -> Result<Self, Error> {
+        Ok(Self::new(values(series, NullPolicy::Reject)?))
+    }
+}
+
+impl<T, M> TryFrom<&Series> for SparseTable<T, M>
+where
+    T: NumericNative + Zero,
+    T::PolarsType: PolarsNumericType<Native = T>,
+    M: Idempotent<T>,
+{
+    type Error = Error;
+
+    fn try_from(series: &Series) -> Result<Self, Error> {
+        Ok(Self::new(values(series, NullPolicy::Reject)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{rmq::Max, RangeQuery};
+    use polars_core::prelude::NamedFrom;
+
+    #[test]
+    fn test_polars() {
+        let dense = Series::new("price".into(), &[3i64, -1, 4, 1]);
+        let sum = SumQuery::<Vec<i64>>::from_series(&dense, NullPolicy::Reject).unwrap();
+        assert_eq!(sum.query(1, 3), 4);
+        let tree = SegmentTree::<i64, Max>::try_from(&dense).unwrap();
+        assert_eq!(tree.query(0, 3), 4);
+        assert_eq!(
+            SparseTable::<f64, Max>::try_from(&dense).map(|table| table.len()),
+            Err(Error::DataType)
+        );
+
+        let sparse = Series::new("volume".into(), &[Some(2u32), None, Some(7)]);
+        assert_eq!(
+            Fenwick::<u32>::try_from(&sparse),
+            Err(Error::Null { index: 1 })
+        );
+        let sum = SumQuery::<Box<[u32]>>::from_series(&sparse, NullPolicy::Zero).unwrap();
+        assert_eq!(sum.query(0, 2), 9);
+        let fenwick = Fenwick::new(values::<u32>(&sparse, NullPolicy::Zero).unwrap());
+        assert_eq!(fenwick.query(1, 2), 7);
+    }
+}
@@ -0,0 +1,24 @@
+//! One import for the common traits and structures
+//!
+//! ```
+//! use kuehree::prelude::*;
+//!
+//! let sum = SumQuery::<[_; 4]>::new([1, 3, 4, 8]);
+//! assert_eq!(sum.query(1, 2), 7);
+//! ```
+
+pub use crate::{
+    rmq::{
+        And, Gcd, Lcm, LcmValue, Max, Min, Or, Prod, Rmq, SegmentTreeFixed, SparseTableFixed, Sum,
+        Xor,
+    },
+    Error, IndexableSumQuery, IteratorExt, MemoryUsage, RangeQuery, SumQuery, UpdatableRangeQuery,
+    Zero,
+};
+
+#[cfg(feature = "alloc")]
+pub use crate::{
+    fenwick::Fenwick,
+    rmq::{SegmentTree, SparseTable},
+    SliceExt,
+};
@@ -0,0 +1,194 @@
+//! Python bindings, enabled with the `python` feature
+//!
+//! Build the extension module with [maturin](https://github.com/PyO3/maturin),
+//! e.g. `maturin build --release --features python`. Constructors take 1D
+//! `NumPy` arrays of `float64`, `from_values` takes a list, and invalid
+//! ranges and indices raise `IndexError` instead of aborting the
+//! interpreter.
+
+use numpy::{PyArray1, PyArrayMethods};
+use pyo3::{exceptions::PyIndexError, prelude::*};
+
+use crate::{
+    error::check_range,
+    fenwick::Fenwick,
+    rmq::{Max, Min, SegmentTree},
+    Error, IndexableSumQuery, RangeQuery, SumQuery,
+};
+
+fn index_error(err: Error) -> PyErr {
+    PyIndexError::new_err(err.to_string())
+}
+
+fn values(data: &Bound<'_, PyArray1<f64>>) -> Vec<f64> {
+    data.readonly().as_array().iter().copied().collect()
+}
+
+/// Prefix sum range queries
+#[pyclass(name = "SumQuery", frozen)]
+pub struct PySumQuery {
+    inner: SumQuery<Vec<f64>>,
+}
+
+#[pymethods]
+impl PySumQuery {
+    #[new]
+    fn new(data: &Bound<'_, PyArray1<f64>>) -> Self {
+        Self::from_values(values(data))
+    }
+
+    #[staticmethod]
+    fn from_values(data: Vec<f64>) -> Self {
+        Self {
+            inner: SumQuery::new(data),
+        }
+    }
+
+    fn query(&self, start: usize, end: usize) -> PyResult<f64> {
+        self.inner.try_query(start, end).map_err(index_error)
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+macro_rules! py_segment_tree {
+    ($name:ident, $py_name:literal, $op:ty, $doc:literal) => {
+        #[doc = $doc]
+        #[pyclass(name = $py_name)]
+        pub struct $name {
+            inner: SegmentTree<f64, $op>,
+        }
+
+        #[pymethods]
+        impl $name {
+            #[new]
+            fn new(data: &Bound<'_, PyArray1<f64>>) -> Self {
+                Self::from_values(values(data))
+            }
+
+            #[staticmethod]
+            fn from_values(data: Vec<f64>) -> Self {
+                Self {
+                    inner: SegmentTree::new(data),
+                }
+            }
+
+            fn query(&self, start: usize, end: usize) -> PyResult<f64> {
+                self.inner.try_query(start, end).map_err(index_error)
+            }
+
+            fn update(&mut self, index: usize, value: f64) -> PyResult<()> {
+                check_range(index, index, self.inner.len()).map_err(index_error)?;
+                self.inner.update(index, value);
+                Ok(())
+            }
+
+            fn __len__(&self) -> usize {
+                self.inner.len()
+            }
+        }
+    };
+}
+
+py_segment_tree!(
+    PySegmentTreeMin,
+    "SegmentTreeMin",
+    Min,
+    "Range minimum segment tree"
+);
+py_segment_tree!(
+    PySegmentTreeMax,
+    "SegmentTreeMax",
+    Max,
+    "Range maximum segment tree"
+);
+
+/// Fenwick tree, point additions and range sums
+#[pyclass(name = "Fenwick")]
+pub struct PyFenwick {
+    inner: Fenwick<f64>,
+}
+
+#[pymethods]
+impl PyFenwick {
+    #[new]
+    fn new(data: &Bound<'_, PyArray1<f64>>) -> Self {
+        Self::from_values(values(data))
+    }
+
+    #[staticmethod]
+    fn from_values(data: Vec<f64>) -> Self {
+        Self {
+            inner: Fenwick::new(data),
+        }
+    }
+
+    fn add(&mut self, index: usize, delta: f64) -> PyResult<()> {
+        check_range(index, index, self.inner.len()).map_err(index_error)?;
+        self.inner.add(index, delta);
+        Ok(())
+    }
+
+    fn query(&self, start: usize, end: usize) -> PyResult<f64> {
+        self.inner.try_query(start, end).map_err(index_error)
+    }
+
+    fn update(&mut self, index: usize, value: f64) -> PyResult<()> {
+        check_range(index, index, self.inner.len()).map_err(index_error)?;
+        self.inner.update(index, value);
+        Ok(())
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+#[pymodule]
+fn kuehree(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PySumQuery>()?;
+    m.add_class::<PySegmentTreeMin>()?;
+    m.add_class::<PySegmentTreeMax>()?;
+    m.add_class::<PyFenwick>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn is_index_error(err: &PyErr) -> bool {
+        Python::initialize();
+        Python::attach(|py| err.is_instance_of::<PyIndexError>(py))
+    }
+
+    #[test]
+    fn test_python() {
+        let sum = PySumQuery::from_values(vec![1.0, 3.0, 4.0, 8.0]);
+        assert_eq!(sum.query(1, 2).unwrap(), 7.0);
+        assert_eq!(sum.__len__(), 4);
+        assert!(is_index_error(&sum.query(2, 4).unwrap_err()));
+        assert!(is_index_error(&sum.query(3, 1).unwrap_err()));
+
+        let mut min = PySegmentTreeMin::from_values(vec![5.0, 2.0, 9.0]);
+        min.update(1, 7.0).unwrap();
+        assert_eq!(min.query(0, 2).unwrap(), 5.0);
+        assert!(is_index_error(&min.update(3, 0.0).unwrap_err()));
+        let max = PySegmentTreeMax::from_values(vec![5.0, 2.0, 9.0]);
+        assert_eq!(max.query(0, 1).unwrap(), 5.0);
+
+        let mut fenwick = PyFenwick::from_values(vec![1.0, 3.0, 4.0, 8.0]);
+        fenwick.add(0, 2.5).unwrap();
+        fenwick.update(3, 1.0).unwrap();
+        assert_eq!(fenwick.query(0, 3).unwrap(), 11.5);
+        assert_eq!(fenwick.__len__(), 4);
+        assert!(is_index_error(&fenwick.add(4, 1.0).unwrap_err()));
+        assert!(is_index_error(&fenwick.update(9, 1.0).unwrap_err()));
+        assert!(is_index_error(&fenwick.query(1, 0).unwrap_err()));
+        assert!(is_index_error(
+            &PyFenwick::from_values(vec![]).query(0, 0).unwrap_err()
+        ));
+    }
+}
@@ -0,0 +1,395 @@
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{error::check_range, Error, MemoryUsage};
+
+/// Interface shared by every range query structure in the crate
+///
+/// Generic code written against this trait can switch between prefix sums,
+/// sparse tables and segment trees without changing any call sites.
+pub trait RangeQuery<T> {
+    /// Query between start range and end range, both inclusive
+    fn query(&self, start: usize, end: usize) -> T;
+
+    /// Number of elements the structure was built from
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Query between start range and end range, returning an error instead
+    /// of panicking when the range is invalid
+    ///
+    /// # Errors
+    ///
+    /// [`Error::EmptyInput`] if there are no elements,
+    /// [`Error::InvertedRange`] if `start > end` and [`Error::OutOfBounds`]
+    /// if `end` is past the last element
+    fn try_query(&self, start: usize, end: usize) -> Result<T, Error> {
+        check_range(start, end, self.len())?;
+        Ok(self.query(start, end))
+    }
+
+    /// Query between start range and end range, returning `None` when the
+    /// range is invalid
+    fn query_opt(&self, start: usize, end: usize) -> Option<T> {
+        self.try_query(start, end).ok()
+    }
+
+    /// Query between start range and end range, handling an out of range
+    /// `end` according to `policy`
+    ///
+    /// # Errors
+    ///
+    /// Same as [`RangeQuery::try_query`], except for an `end` past the last
+    /// element under [`BoundsPolicy::Clamp`]
+    fn query_with(&self, start: usize, end: usize, policy: BoundsPolicy) -> Result<T, Error> {
+        match policy {
+            BoundsPolicy::Panic => Ok(self.query(start, end)),
+            BoundsPolicy::Clamp => self.try_query(start, end.min(self.len().saturating_sub(1))),
+            BoundsPolicy::Error => self.try_query(start, end),
+        }
+    }
+}
+
+/// Range query structure whose elements can be replaced one at a time
+pub trait UpdatableRangeQuery<T>: RangeQuery<T> {
+    /// Current element at `index`
+    fn get(&self, index: usize) -> T;
+
+    /// Replace the element at `index` with `value`
+    fn update(&mut self, index: usize, value: T);
+
+    /// Apply many updates at once, later ones win when an index repeats
+    ///
+    /// Structures override this when a batch is cheaper than one update
+    /// at a time.
+    fn update_batch(&mut self, updates: impl IntoIterator<Item = (usize, T)>)
+    where
+        Self: Sized,
+    {
+        for (index, value) in updates {
+            self.update(index, value);
+        }
+    }
+
+    /// Stage updates in a [`Transaction`] that only touches `self` once
+    /// committed
+    #[cfg(feature = "alloc")]
+    fn begin(&mut self) -> Transaction<'_, Self, T>
+    where
+        Self: Sized,
+    {
+        Transaction::new(self)
+    }
+}
+
+/// Updates staged against an [`UpdatableRangeQuery`], see
+/// [`UpdatableRangeQuery::begin`]
+///
+/// Nothing is applied until [`Self::commit`], dropping the transaction or
+/// calling [`Self::abort`] discards every staged update.
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+#[must_use = "staged updates are discarded unless committed"]
+pub struct Transaction<'a, S, T> {
+    target: &'a mut S,
+    staged: Vec<(usize, T)>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, S, T> Transaction<'a, S, T>
+where
+    S: UpdatableRangeQuery<T>,
+{
+    fn new(target: &'a mut S) -> Self {
+        Self {
+            target,
+            staged: Vec::new(),
+        }
+    }
+
+    /// Stage replacing the element at `index` with `value`
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds, so that commit can not fail half way
+    pub fn update(&mut self, index: usize, value: T) {
+        assert!(index < self.target.len(), "index {index} out of bounds");
+        self.staged.push((index, value));
+    }
+
+    /// Number of staged updates
+    pub fn len(&self) -> usize {
+        self.staged.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.staged.is_empty()
+    }
+
+    /// Apply every staged update as one batch
+    pub fn commit(self) {
+        self.target.update_batch(self.staged);
+    }
+
+    /// Discard every staged update
+    pub fn abort(self) {}
+}
+
+#[cfg(feature = "alloc")]
+impl<S, T> Transaction<'_, S, T>
+where
+    S: UpdatableRangeQuery<T>,
+    T: Clone,
+{
+    /// Element at `index` as it will be after committing
+    pub fn get(&self, index: usize) -> T {
+        self.staged
+            .iter()
+            .rev()
+            .find(|(staged, _)| *staged == index)
+            .map_or_else(|| self.target.get(index), |(_, value)| value.clone())
+    }
+}
+
+impl<T, Q: RangeQuery<T> + ?Sized> RangeQuery<T> for &Q {
+    fn query(&self, start: usize, end: usize) -> T {
+        (**self).query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, Q: RangeQuery<T> + ?Sized> RangeQuery<T> for Box<Q> {
+    fn query(&self, start: usize, end: usize) -> T {
+        (**self).query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
+/// Lets any structure, e.g. a sparse table, be shared across threads with
+/// O(1) clones
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<T, Q: RangeQuery<T> + ?Sized> RangeQuery<T> for Arc<Q> {
+    fn query(&self, start: usize, end: usize) -> T {
+        (**self).query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
+/// How a query with an `end` past the last element is handled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BoundsPolicy {
+    /// Panic, the behaviour of [`RangeQuery::query`]
+    #[default]
+    Panic,
+    /// Clamp `end` to the last element
+    Clamp,
+    /// Return an [`Error`]
+    Error,
+}
+
+/// Range query structure with a fixed [`BoundsPolicy`]
+///
+/// Lets services choose once, at construction, whether malformed queries
+/// are fatal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bounded<Q> {
+    inner: Q,
+    policy: BoundsPolicy,
+}
+
+impl<Q: MemoryUsage> MemoryUsage for Bounded<Q> {
+    fn heap_bytes(&self) -> usize {
+        self.inner.heap_bytes()
+    }
+}
+
+impl<Q> Bounded<Q> {
+    pub fn new(inner: Q, policy: BoundsPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    pub fn policy(&self) -> BoundsPolicy {
+        self.policy
+    }
+
+    pub fn into_inner(self) -> Q {
+        self.inner
+    }
+
+    /// Query between start range and end range according to the policy
+    ///
+    /// # Errors
+    ///
+    /// See [`RangeQuery::query_with`]
+    pub fn query<T>(&self, start: usize, end: usize) -> Result<T, Error>
+    where
+        Q: RangeQuery<T>,
+    {
+        self.inner.query_with(start, end, self.policy)
+    }
+}
+
+/// Read only handle over a range query structure, shareable across threads
+///
+/// Taking the structure by value and exposing nothing but [`RangeQuery`]
+/// rules out any later change, including through `&self` such as
+/// [`ShardedSegmentTree::update`](crate::sharded::ShardedSegmentTree::update).
+/// It is `Send + Sync` whenever `Q` is, so one instance can be shared
+/// behind an `Arc` by any number of tasks. That holds for the structures
+/// of this crate over `Send + Sync` elements except
+/// [`LazySumQuery`](crate::lazy_sum::LazySumQuery), which fills its cache
+/// through `&self` and is not `Sync`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FrozenQuery<Q> {
+    inner: Q,
+}
+
+impl<Q> FrozenQuery<Q> {
+    pub fn new(inner: Q) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T, Q: RangeQuery<T>> RangeQuery<T> for FrozenQuery<Q> {
+    fn query(&self, start: usize, end: usize) -> T {
+        self.inner.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<Q: MemoryUsage> MemoryUsage for FrozenQuery<Q> {
+    fn heap_bytes(&self) -> usize {
+        self.inner.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Bounded, BoundsPolicy, FrozenQuery, RangeQuery};
+    use crate::{
+        rmq::{Max, Min, Rmq, SegmentTree, SegmentTreeFixed, SparseTable, SparseTableFixed},
+        Error, IndexableSumQuery, SumQuery,
+    };
+
+    const ARR: [u32; 8] = [1, 3, 4, 8, 6, 1, 4, 2];
+
+    fn whole_range<Q: RangeQuery<u32>>(query: &Q) -> u32 {
+        query.query(0, query.len() - 1)
+    }
+
+    #[test]
+    fn test_generic() {
+        assert_eq!(whole_range(&SumQuery::<[_; 8]>::new(ARR)), 29);
+        assert_eq!(whole_range(&SumQuery::<Vec<_>>::new(ARR)), 29);
+        assert_eq!(whole_range(&SparseTableFixed::<_, Max, 8, 4>::new(ARR)), 8);
+        assert_eq!(whole_range(&SparseTable::<_, Min>::new(ARR)), 1);
+        assert_eq!(whole_range(&SegmentTreeFixed::<_, Max, 8>::new(ARR)), 8);
+        assert_eq!(whole_range(&SegmentTree::<_, Min>::new(ARR)), 1);
+        assert_eq!(whole_range(&Rmq::<Vec<_>, _, Max>::new(ARR.to_vec())), 8);
+    }
+
+    #[test]
+    fn test_shared() {
+        let table = std::sync::Arc::new(SparseTable::<_, Max>::new(ARR));
+        let boxed: Box<dyn RangeQuery<u32> + Send + Sync> = Box::new(SumQuery::<Vec<_>>::new(ARR));
+
+        let handle = {
+            let table = table.clone();
+            std::thread::spawn(move || whole_range(&table))
+        };
+        assert_eq!(handle.join().unwrap(), 8);
+        assert_eq!(whole_range(&boxed), 29);
+    }
+
+    #[test]
+    fn test_frozen() {
+        fn assert_send_sync<Q: Send + Sync>(_: &Q) {}
+
+        let frozen = std::sync::Arc::new(FrozenQuery::new(SegmentTree::<_, Max>::new(ARR)));
+        assert_send_sync(&frozen);
+        let handles: Vec<_> = (0..4)
+            .map(|start| {
+                let frozen = frozen.clone();
+                std::thread::spawn(move || -> u32 { frozen.query(start, 7) })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 8);
+        }
+        assert_eq!(
+            frozen.try_query(0, 8),
+            Err(Error::OutOfBounds { index: 8, len: 8 })
+        );
+        assert_eq!(frozen.len(), 8);
+    }
+
+    #[test]
+    fn test_try_query() {
+        let sum = SumQuery::<[_; 8]>::new(ARR);
+        let empty = SumQuery::<[u32; 0]>::new([]);
+
+        assert_eq!(sum.try_query(3, 6), Ok(19));
+        assert_eq!(
+            sum.try_query(6, 3),
+            Err(Error::InvertedRange { start: 6, end: 3 })
+        );
+        assert_eq!(
+            sum.try_query(3, 8),
+            Err(Error::OutOfBounds { index: 8, len: 8 })
+        );
+        assert_eq!(empty.try_query(0, 0), Err(Error::EmptyInput));
+    }
+
+    #[test]
+    fn test_query_opt() {
+        let table = SparseTable::<_, Min>::new(ARR);
+
+        assert_eq!(table.query_opt(0, 7), Some(1));
+        assert_eq!(table.query_opt(1, 4), Some(3));
+        assert_eq!(table.query_opt(4, 1), None);
+        assert_eq!(table.query_opt(1, 8), None);
+    }
+
+    #[test]
+    fn test_bounds_policy() {
+        let sum = SumQuery::<Vec<_>>::new(ARR);
+        let clamp = Bounded::new(sum.clone(), BoundsPolicy::Clamp);
+        let error = Bounded::new(sum, BoundsPolicy::Error);
+
+        assert_eq!(clamp.query(3, 100), Ok(21));
+        assert_eq!(
+            clamp.query(9, 100),
+            Err(Error::InvertedRange { start: 9, end: 7 })
+        );
+        assert_eq!(error.query(3, 6), Ok(19));
+        assert_eq!(
+            error.query(3, 100),
+            Err(Error::OutOfBounds { index: 100, len: 8 })
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_bounds_policy_panic() {
+        let sum = SumQuery::<[_; 8]>::new(ARR);
+        let _ = Bounded::new(sum, BoundsPolicy::default()).query(3, 100);
+    }
+}
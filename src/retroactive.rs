@@ -0,0 +1,156 @@
+//! Prefix sums accepting updates "in the past", for event sourced data
+//! receiving late corrections
+
+use core::ops::{Add, Sub};
+
+use alloc::collections::BTreeMap;
+
+use crate::{fenwick::Fenwick, MemoryUsage, RangeQuery, Zero};
+
+/// Prefix sums over data changed by timestamped additions
+///
+/// An addition can be inserted or removed at any timestamp `K`, also one
+/// older than updates already applied, and queries answer for the present
+/// state with every recorded addition applied. There is at most one
+/// addition per timestamp.
+///
+/// Additions commute, so the present state is a [`Fenwick`] tree with every
+/// recorded addition applied, whatever order they arrived in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Retroactive<K, T> {
+    present: Fenwick<T>,
+    updates: BTreeMap<K, (usize, T)>,
+}
+
+impl<K, T> Retroactive<K, T>
+where
+    K: Ord,
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    /// Construct `Self` from the data before any update
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            present: Fenwick::new(data),
+            updates: BTreeMap::new(),
+        }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.present.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record adding `delta` to the element at `index` at time `time`,
+    /// returning the addition it replaces
+    ///
+    /// Algorithmic complexity: O(log n + log u) for `u` recorded updates
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds
+    pub fn insert(&mut self, time: K, index: usize, delta: T) -> Option<(usize, T)> {
+        assert!(index < self.len(), "index {index} out of bounds");
+        self.present.add(index, delta);
+        let replaced = self.updates.insert(time, (index, delta));
+        if let Some((index, delta)) = replaced {
+            self.present.sub(index, delta);
+        }
+        replaced
+    }
+
+    /// Remove the addition recorded at `time`, as if it never happened
+    ///
+    /// Algorithmic complexity: O(log n + log u) for `u` recorded updates
+    pub fn remove(&mut self, time: &K) -> Option<(usize, T)> {
+        let removed = self.updates.remove(time);
+        if let Some((index, delta)) = removed {
+            self.present.sub(index, delta);
+        }
+        removed
+    }
+
+    /// Recorded additions in time order
+    pub fn updates(&self) -> impl Iterator<Item = (&K, usize, T)> + '_ {
+        self.updates
+            .iter()
+            .map(|(time, &(index, delta))| (time, index, delta))
+    }
+
+    /// Sum of the first `count` elements in the present
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn prefix_sum(&self, count: usize) -> T {
+        self.present.prefix_sum(count)
+    }
+
+    /// Query between start range and end range in the present
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        self.present.query(start, end)
+    }
+}
+
+impl<K, T> RangeQuery<T> for Retroactive<K, T>
+where
+    K: Ord,
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, T> MemoryUsage for Retroactive<K, T> {
+    /// Counts the nodes of the update map but not its internal overhead
+    fn heap_bytes(&self) -> usize {
+        self.present.heap_bytes() + self.updates.len() * core::mem::size_of::<(K, (usize, T))>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_retroactive() {
+        let arr = [1i64, 3, 4, 8, 6, 1, 4];
+        let mut sums = Retroactive::new(arr);
+        assert_eq!(sums.query(0, 6), 27);
+        assert_eq!(sums.query(2, 4), 18);
+
+        sums.insert(10, 3, 5);
+        sums.insert(20, 0, -1);
+        assert_eq!(sums.query(0, 6), 31);
+
+        // A late correction arrives before both updates
+        sums.insert(5, 6, 2);
+        assert_eq!(sums.prefix_sum(7), 33);
+        assert_eq!(sums.remove(&10), Some((3, 5)));
+        assert_eq!(sums.remove(&10), None);
+        assert_eq!(sums.query(3, 6), 21);
+        assert_eq!(sums.insert(20, 1, 4), Some((0, -1)));
+        assert_eq!(sums.query(0, 1), 8);
+
+        let times: Vec<_> = sums.updates().map(|(&time, _, _)| time).collect();
+        assert_eq!(times, [5, 20]);
+        for start in 0..arr.len() {
+            for end in start..arr.len() {
+                let expected: i64 = arr[start..=end].iter().sum::<i64>()
+                    + i64::from(start <= 6 && 6 <= end) * 2
+                    + i64::from(start <= 1 && 1 <= end) * 4;
+                assert_eq!(sums.query(start, end), expected);
+            }
+        }
+    }
+}
@@ -1,66 +1,1970 @@
-use std::marker::PhantomData;
+use core::{
+    fmt,
+    marker::PhantomData,
+    ops::{Add, BitAnd, BitOr, BitXor, Mul, Rem},
+};
 
-use num::Num;
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
 
+#[cfg(feature = "alloc")]
+use crate::snapshot::{Shared, Snapshot};
+use crate::{trace::BuildSpan, Error, MemoryUsage, RangeQuery, UpdatableRangeQuery, Zero};
+
+/// Range maximum operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub struct Max;
+
+/// Range minimum operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub struct Min;
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub struct SparseTableFixed<T, const N: usize, const M: usize> {
-    data: [T; N],
-    answers: [[T; N]; M],
+/// Range sum operation, for segment trees only as it is not idempotent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Sum;
+
+/// Range product operation, for segment trees only as it is not idempotent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Prod;
+
+/// Range bitwise xor operation, not idempotent so sparse tables do not
+/// support it, [`group::build`](crate::group::build) answers it from prefix
+/// totals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Xor;
+
+/// Range greatest common divisor operation, for non-negative integers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Gcd;
+
+/// Range bitwise and operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct And;
+
+/// Range bitwise or operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Or;
+
+/// Range least common multiple operation, over [`LcmValue`] so that a
+/// multiple too large for `T` is reported instead of wrapping
+///
+/// Implemented for the unsigned integers, use `u128` elements to push the
+/// overflow further out. Zero is absorbing, as `lcm(0, x) == 0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Lcm;
+
+/// Result of [`Lcm`], either exact or exceeding the element type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LcmValue<T> {
+    Exact(T),
+    /// The multiple exceeds the largest `T`
+    Overflow,
+}
+
+impl<T> LcmValue<T> {
+    /// The multiple, `None` if it overflowed
+    pub fn exact(self) -> Option<T> {
+        match self {
+            Self::Exact(value) => Some(value),
+            Self::Overflow => None,
+        }
+    }
+}
+
+impl<T> From<T> for LcmValue<T> {
+    fn from(value: T) -> Self {
+        Self::Exact(value)
+    }
+}
+
+/// Binary operation used to fold a range
+///
+/// The operation has to be associative. Sparse tables additionally need it
+/// to be [`Idempotent`].
+pub trait Operation<T> {
+    fn combine(a: T, b: T) -> T;
+}
+
+/// Operation for which `combine(a, a) == a`, so overlapping ranges can be
+/// combined
+///
+/// Sparse tables answer a query from two overlapping ranges and are only
+/// constructible for these operations:
+///
+/// ```compile_fail
+/// use kuehree::rmq::{SparseTable, Sum};
+///
+/// let table = SparseTable::<u32, Sum>::new([1, 2, 3]);
+/// ```
+pub trait Idempotent<T>: Operation<T> {}
+
+impl<T: PartialOrd> Idempotent<T> for Max {}
+
+impl<T: PartialOrd> Idempotent<T> for Min {}
+
+impl<T> Idempotent<T> for Gcd where T: Copy + PartialEq + Rem<Output = T> + Zero {}
+
+impl<T: BitAnd<Output = T>> Idempotent<T> for And {}
+
+impl<T: BitOr<Output = T>> Idempotent<T> for Or {}
+
+impl<T> Idempotent<LcmValue<T>> for Lcm where Self: Operation<LcmValue<T>> {}
+
+impl<T: PartialOrd> Operation<T> for Max {
+    fn combine(a: T, b: T) -> T {
+        if b > a {
+            b
+        } else {
+            a
+        }
+    }
+}
+
+impl<T: PartialOrd> Operation<T> for Min {
+    fn combine(a: T, b: T) -> T {
+        if b < a {
+            b
+        } else {
+            a
+        }
+    }
+}
+
+impl<T: Add<Output = T>> Operation<T> for Sum {
+    fn combine(a: T, b: T) -> T {
+        a + b
+    }
+}
+
+impl<T: Mul<Output = T>> Operation<T> for Prod {
+    fn combine(a: T, b: T) -> T {
+        a * b
+    }
+}
+
+impl<T: BitXor<Output = T>> Operation<T> for Xor {
+    fn combine(a: T, b: T) -> T {
+        a ^ b
+    }
+}
+
+/// Euclid's algorithm, zero is the identity
+impl<T> Operation<T> for Gcd
+where
+    T: Copy + PartialEq + Rem<Output = T> + Zero,
+{
+    fn combine(mut a: T, mut b: T) -> T {
+        while b != T::zero() {
+            (a, b) = (b, a % b);
+        }
+        a
+    }
+}
+
+impl<T: BitAnd<Output = T>> Operation<T> for And {
+    fn combine(a: T, b: T) -> T {
+        a & b
+    }
+}
+
+impl<T: BitOr<Output = T>> Operation<T> for Or {
+    fn combine(a: T, b: T) -> T {
+        a | b
+    }
+}
+
+macro_rules! impl_lcm {
+    ($($ty:ty),*) => {
+        $(
+            /// Zero absorbs everything, overflow included, and overflow
+            /// absorbs the other values
+            impl Operation<LcmValue<$ty>> for Lcm {
+                fn combine(a: LcmValue<$ty>, b: LcmValue<$ty>) -> LcmValue<$ty> {
+                    let (a, b) = match (a, b) {
+                        (LcmValue::Exact(0), _) | (_, LcmValue::Exact(0)) => {
+                            return LcmValue::Exact(0)
+                        }
+                        (LcmValue::Exact(a), LcmValue::Exact(b)) => (a, b),
+                        _ => return LcmValue::Overflow,
+                    };
+                    (a / Gcd::combine(a, b))
+                        .checked_mul(b)
+                        .map_or(LcmValue::Overflow, LcmValue::Exact)
+                }
+            }
+        )*
+    };
+}
+
+impl_lcm!(u8, u16, u32, u64, u128, usize);
+
+/// Number of sparse table levels required for `n` elements
+///
+/// Use this to pick the `L` parameter of [`SparseTableFixed`]
+pub const fn levels(n: usize) -> usize {
+    if n == 0 {
+        0
+    } else {
+        n.ilog2() as usize + 1
+    }
 }
 
-impl<T, const N: usize, const M: usize> SparseTableFixed<T, N, M> {
+/// Sparse table backed by fixed size arrays, requires no allocator
+///
+/// `L` is the number of levels and has to be at least [`levels`]`(N)`
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SparseTableFixed<T, M, const N: usize, const L: usize> {
+    answers: [[T; N]; L],
+    _phantom: PhantomData<M>,
+}
+
+impl<T, M, const N: usize, const L: usize> SparseTableFixed<T, M, N, L>
+where
+    T: Copy,
+    M: Idempotent<T>,
+{
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n log n)
+    ///
+    /// # Panics
+    ///
+    /// If `L` is less than [`levels`]`(N)`
     pub fn new(data: [T; N]) -> Self {
-        todo!()
+        match Self::try_new(data) {
+            Ok(table) => table,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Construct `Self`, checking that `L` is large enough
+    ///
+    /// # Errors
+    ///
+    /// [`Error::InsufficientLevels`] if `L` is less than [`levels`]`(N)`
+    pub fn try_new(data: [T; N]) -> Result<Self, Error> {
+        let span = BuildSpan::enter("SparseTableFixed");
+        span.record_len(N);
+        if L < levels(N) {
+            return Err(Error::InsufficientLevels {
+                required: levels(N),
+                levels: L,
+            });
+        }
+
+        let mut answers = [data; L];
+        for level in 1..levels(N) {
+            let half = 1 << (level - 1);
+            for idx in 0..=(N - (1 << level)) {
+                answers[level][idx] =
+                    M::combine(answers[level - 1][idx], answers[level - 1][idx + half]);
+            }
+        }
+
+        Ok(Self {
+            answers,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Borrow the elements the table was built from
+    pub fn data(&self) -> &[T] {
+        self.answers.first().map_or(&[], |data| data)
+    }
+
+    /// Take back the levels, level `k` holds the answers for ranges of
+    /// length `2^k` starting at each index
+    pub fn into_inner(self) -> [[T; N]; L] {
+        self.answers
     }
 
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < N);
+        let level = (end - start + 1).ilog2() as usize;
+        M::combine(
+            self.answers[level][start],
+            self.answers[level][end + 1 - (1 << level)],
+        )
+    }
 }
 
-pub struct SegmentTree<T> {
-    _phantom: PhantomData<T>,
+#[cfg(feature = "alloc")]
+impl<T: fmt::Display, M, const N: usize, const L: usize> SparseTableFixed<T, M, N, L> {
+    /// Graphviz description of every answer and the range it covers
+    pub fn to_dot(&self) -> String {
+        sparse_dot(N, |level, idx| &self.answers[level][idx])
+    }
 }
 
-impl<T: Num> SegmentTree<T> {
-    pub fn new(data: T) -> Self {
-        todo!()
+/// Lists the answers level by level, keyed by the range each one covers
+impl<T: fmt::Debug, M, const N: usize, const L: usize> fmt::Debug for SparseTableFixed<T, M, N, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let levels = SparseLevels {
+            len: N,
+            get: |level: usize, idx: usize| &self.answers[level][idx],
+        };
+        f.debug_struct("SparseTableFixed")
+            .field("len", &N)
+            .field("levels", &levels)
+            .finish()
+    }
+}
+
+impl<T, M, const N: usize, const L: usize> MemoryUsage for SparseTableFixed<T, M, N, L> {
+    fn heap_bytes(&self) -> usize {
+        0
+    }
+}
+
+impl<T, M, const N: usize, const L: usize> RangeQuery<T> for SparseTableFixed<T, M, N, L>
+where
+    T: Copy,
+    M: Idempotent<T>,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        N
+    }
+}
+
+/// Indexes sharing a block of [`Layout::Blocked`], a cache line of `u64`
+#[cfg(feature = "alloc")]
+const BLOCK: usize = 8;
+
+/// Memory layout of the levels of a [`SparseTable`]
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Layout {
+    /// Each level is stored contiguously, one after another
+    #[default]
+    Levels,
+    /// The answers of every level for a block of eight consecutive indexes
+    /// are stored together, each level's eight answers next to each other
+    ///
+    /// Ranges of any length from the same start read their first answer
+    /// from the same block instead of one row per level. A query still
+    /// reads two blocks and the offset costs a multiplication, so in
+    /// `benches/sparse_table.rs` this was 20-100% slower than
+    /// [`Layout::Levels`] for both random and clustered queries. The last
+    /// block is padded, so the answer array can be longer than the data
+    /// times the levels.
+    Blocked,
+}
+
+/// Sparse table backed by heap allocated levels
+///
+/// The answers are stored in a single allocation, arranged according to
+/// the [`Layout`] chosen at construction.
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SparseTable<T, M> {
+    answers: Vec<T>,
+    len: usize,
+    levels: usize,
+    layout: Layout,
+    _phantom: PhantomData<M>,
+}
+
+/// Slot of the answer for `slot` of a [`Layout::Blocked`] array in the
+/// same answers stored level after level
+#[cfg(feature = "alloc")]
+fn blocked_source(slot: usize, len: usize, levels: usize) -> usize {
+    let (block, within) = (slot / (BLOCK * levels), slot % (BLOCK * levels));
+    // Padding past the end repeats the last answer, it is never read
+    let idx = (block * BLOCK + within % BLOCK).min(len - 1);
+    within / BLOCK * len + idx
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> SparseTable<T, M>
+where
+    T: Copy,
+    M: Idempotent<T>,
+{
+    /// Construct `Self` with the default [`Layout`]
+    ///
+    /// Algorithmic complexity: O(n log n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        Self::with_layout(data, Layout::default())
+    }
+
+    /// Construct `Self` with the given [`Layout`]
+    ///
+    /// Algorithmic complexity: O(n log n)
+    pub fn with_layout(data: impl IntoIterator<Item = T>, layout: Layout) -> Self {
+        let span = BuildSpan::enter("SparseTable");
+        let data: Vec<T> = data.into_iter().collect();
+        let len = data.len();
+        span.record_len(len);
+        let levels = levels(len);
+
+        // Every slot starts out as its level 0 answer, slots past the end of
+        // a level are never read
+        let mut answers = Vec::with_capacity(len * levels);
+        (0..levels).for_each(|_| answers.extend_from_slice(&data));
+
+        for level in 1..levels {
+            let half = 1 << (level - 1);
+            for idx in 0..=(len - (1 << level)) {
+                let previous = (level - 1) * len + idx;
+                answers[level * len + idx] =
+                    M::combine(answers[previous], answers[previous + half]);
+            }
+        }
+        // Built level by level, blocked afterwards if asked for
+        if layout == Layout::Blocked {
+            answers = (0..len.div_ceil(BLOCK) * BLOCK * levels)
+                .map(|slot| answers[blocked_source(slot, len, levels)])
+                .collect();
+        }
+
+        Self {
+            answers,
+            len,
+            levels,
+            layout,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn get(&self, level: usize, idx: usize) -> T {
+        self.answers[self.offset(level, idx)]
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Iterate over the elements the table was built from
+    pub fn data(&self) -> impl ExactSizeIterator<Item = T> + '_ {
+        (0..self.len).map(|idx| self.get(0, idx))
+    }
+
+    /// Borrow the flat answer array, arranged according to [`Self::layout`]
+    pub fn answers(&self) -> &[T] {
+        &self.answers
+    }
+
+    /// Take back the flat answer array, arranged according to
+    /// [`Self::layout`]
+    pub fn into_answers(self) -> Vec<T> {
+        self.answers
+    }
+
+    /// Number of elements in the table
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len());
+        let level = (end - start + 1).ilog2() as usize;
+        M::combine(
+            self.get(level, start),
+            self.get(level, end + 1 - (1 << level)),
+        )
+    }
+}
+
+/// Answers computed per rayon task when building a level in parallel
+#[cfg(feature = "rayon")]
+const PARALLEL_CHUNK: usize = 1 << 12;
+
+#[cfg(feature = "rayon")]
+impl<T, M> SparseTable<T, M>
+where
+    T: Copy + Send + Sync,
+    M: Idempotent<T>,
+{
+    /// Construct `Self` with the default [`Layout`] on the rayon thread
+    /// pool
+    ///
+    /// Algorithmic complexity: O(n log n) work, O(n log n / threads) time
+    pub fn par_new(data: impl IntoIterator<Item = T>) -> Self {
+        Self::par_with_layout(data, Layout::default())
+    }
+
+    /// Construct `Self` with the given [`Layout`] on the rayon thread pool
+    ///
+    /// A level only reads the one before it, so its answers are split in
+    /// chunks computed in parallel. Small inputs are built sequentially.
+    ///
+    /// Algorithmic complexity: O(n log n) work, O(n log n / threads) time
+    pub fn par_with_layout(data: impl IntoIterator<Item = T>, layout: Layout) -> Self {
+        use rayon::prelude::*;
+
+        let data: Vec<T> = data.into_iter().collect();
+        let len = data.len();
+        if len < crate::scan::PARALLEL_THRESHOLD || rayon::current_num_threads() == 1 {
+            return Self::with_layout(data, layout);
+        }
+        let span = BuildSpan::enter("SparseTable");
+        span.record_len(len);
+        let levels = levels(len);
+
+        let mut answers = Vec::with_capacity(len * levels);
+        (0..levels).for_each(|_| answers.extend_from_slice(&data));
+        for level in 1..levels {
+            let half = 1 << (level - 1);
+            let (done, rest) = answers.split_at_mut(level * len);
+            let previous = &done[(level - 1) * len..];
+            rest[..=len - (1 << level)]
+                .par_chunks_mut(PARALLEL_CHUNK)
+                .enumerate()
+                .for_each(|(chunk, answers)| {
+                    let first = chunk * PARALLEL_CHUNK;
+                    for (idx, answer) in (first..).zip(answers) {
+                        *answer = M::combine(previous[idx], previous[idx + half]);
+                    }
+                });
+        }
+        if layout == Layout::Blocked {
+            answers = (0..len.div_ceil(BLOCK) * BLOCK * levels)
+                .into_par_iter()
+                .map(|slot| answers[blocked_source(slot, len, levels)])
+                .collect();
+        }
+
+        Self {
+            answers,
+            len,
+            levels,
+            layout,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> SparseTable<T, M> {
+    fn offset(&self, level: usize, idx: usize) -> usize {
+        match self.layout {
+            Layout::Levels => level * self.len + idx,
+            Layout::Blocked => (idx / BLOCK * self.levels + level) * BLOCK + idx % BLOCK,
+        }
+    }
+
+    /// Graphviz description of every answer and the range it covers
+    pub fn to_dot(&self) -> String
+    where
+        T: fmt::Display,
+    {
+        sparse_dot(self.len, |level, idx| {
+            &self.answers[self.offset(level, idx)]
+        })
+    }
+}
+
+/// Lists the answers level by level, keyed by the range each one covers
+#[cfg(feature = "alloc")]
+impl<T: fmt::Debug, M> fmt::Debug for SparseTable<T, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let levels = SparseLevels {
+            len: self.len,
+            get: |level: usize, idx: usize| &self.answers[self.offset(level, idx)],
+        };
+        f.debug_struct("SparseTable")
+            .field("len", &self.len)
+            .field("layout", &self.layout)
+            .field("levels", &levels)
+            .finish()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> MemoryUsage for SparseTable<T, M> {
+    fn heap_bytes(&self) -> usize {
+        self.answers.heap_bytes()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> RangeQuery<T> for SparseTable<T, M>
+where
+    T: Copy,
+    M: Idempotent<T>,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Folds `[start, end]` of a bottom-up segment tree with `len` leaves, where
+/// `node(i)` returns node `i` and leaves start at index `len`
+fn fold_tree<T, M>(len: usize, start: usize, end: usize, node: impl Fn(usize) -> T) -> T
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    assert!(end >= start && end < len);
+    let (mut l, mut r) = (start + len, end + len + 1);
+    let mut left: Option<T> = None;
+    let mut right: Option<T> = None;
+
+    while l < r {
+        if l & 1 == 1 {
+            left = Some(left.map_or(node(l), |acc| M::combine(acc, node(l))));
+            l += 1;
+        }
+        if r & 1 == 1 {
+            r -= 1;
+            right = Some(right.map_or(node(r), |acc| M::combine(node(r), acc)));
+        }
+        l >>= 1;
+        r >>= 1;
+    }
+
+    match (left, right) {
+        (Some(left), Some(right)) => M::combine(left, right),
+        (Some(acc), None) | (None, Some(acc)) => acc,
+        (None, None) => unreachable!("range is never empty"),
+    }
+}
+
+/// Writes the subtree rooted at `idx` one node per line, indented by depth,
+/// with leaves labelled by their element index
+fn fmt_tree<T: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    len: usize,
+    idx: usize,
+    depth: usize,
+    node: &impl Fn(usize) -> T,
+) -> fmt::Result {
+    if idx != 1 {
+        writeln!(f)?;
+    }
+    write!(f, "{:indent$}", "", indent = 2 * depth)?;
+    if idx >= len {
+        write!(f, "[{}] {}", idx - len, node(idx))
+    } else {
+        write!(f, "{}", node(idx))?;
+        fmt_tree(f, len, 2 * idx, depth + 1, node)?;
+        fmt_tree(f, len, 2 * idx + 1, depth + 1, node)
+    }
+}
+
+/// First and last element covered by a node, debug printed as `[first, last]`
+#[derive(Clone, Copy)]
+struct Span(usize, usize);
+
+impl Span {
+    /// Span of node `idx` in a bottom-up segment tree with `len` leaves
+    fn of_node(len: usize, idx: usize) -> Self {
+        let (mut first, mut last) = (idx, idx);
+        while first < len {
+            first *= 2;
+        }
+        while last < len {
+            last = 2 * last + 1;
+        }
+        Self(first - len, last - len)
+    }
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}, {}]", self.0, self.1)
     }
 }
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Rmq<T, T2, M> {
-    _phantom: PhantomData<T>,
-    _phantom_2: PhantomData<T2>,
-    _phantom_3: PhantomData<M>,
+/// Debug adaptor listing the levels of a sparse table over `len` elements,
+/// each answer keyed by the range it covers
+struct SparseLevels<G> {
+    len: usize,
+    get: G,
+}
+
+impl<T: fmt::Debug, G: Fn(usize, usize) -> T> fmt::Debug for SparseLevels<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = |level: usize| {
+            let width = 1 << level;
+            Entries(move || {
+                (0..=(self.len - width))
+                    .map(move |idx| (Span(idx, idx + width - 1), (self.get)(level, idx)))
+            })
+        };
+        f.debug_list()
+            .entries((0..levels(self.len)).map(level))
+            .finish()
+    }
+}
+
+/// Debug adaptor listing the nodes of a bottom-up segment tree with `len`
+/// leaves one depth at a time, each node keyed by the elements it covers
+struct TreeLevels<G> {
+    len: usize,
+    node: G,
+}
+
+impl<T: fmt::Debug, G: Fn(usize) -> T> fmt::Debug for TreeLevels<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let end = 2 * self.len;
+        let depth = |depth: u32| {
+            let first = 1 << depth;
+            Entries(move || {
+                (first..end.min(2 * first))
+                    .map(move |idx| (Span::of_node(self.len, idx), (self.node)(idx)))
+            })
+        };
+        let depths = if self.len == 0 { 0 } else { end.ilog2() + 1 };
+        f.debug_list().entries((0..depths).map(depth)).finish()
+    }
+}
+
+/// Debug prints the key value pairs yielded by `F` as a map
+struct Entries<F>(F);
+
+impl<F, I, K, V> fmt::Debug for Entries<F>
+where
+    F: Fn() -> I,
+    I: Iterator<Item = (K, V)>,
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries((self.0)()).finish()
+    }
+}
+
+/// Graphviz digraph of a bottom-up segment tree with `len` leaves, every node
+/// labelled with the first and last element it covers and its value
+///
+/// When `len` is not a power of two, some nodes near the root wrap around
+/// from the end of the data to its start
+#[cfg(feature = "alloc")]
+fn tree_dot<T: fmt::Display>(len: usize, node: impl Fn(usize) -> T) -> String {
+    use core::fmt::Write;
+
+    let mut dot = String::from("digraph SegmentTree {\n    node [shape=record];\n");
+    for idx in 1..2 * len {
+        let Span(first, last) = Span::of_node(len, idx);
+        let _ = writeln!(
+            dot,
+            "    n{idx} [label=\"[{first}, {last}] | {}\"];",
+            node(idx)
+        );
+        if idx < len {
+            let _ = writeln!(dot, "    n{idx} -> n{};", 2 * idx);
+            let _ = writeln!(dot, "    n{idx} -> n{};", 2 * idx + 1);
+        }
+    }
+    dot.push('}');
+    dot
+}
+
+/// Graphviz digraph of a sparse table over `len` elements, every answer
+/// pointing at the two answers of the level below it was combined from
+#[cfg(feature = "alloc")]
+fn sparse_dot<T: fmt::Display>(len: usize, get: impl Fn(usize, usize) -> T) -> String {
+    use core::fmt::Write;
+
+    let mut dot = String::from("digraph SparseTable {\n    node [shape=record];\n");
+    for level in 0..levels(len) {
+        let half = (1 << level) >> 1;
+        for idx in 0..=(len - (1 << level)) {
+            let last = idx + (1 << level) - 1;
+            let value = get(level, idx);
+            let _ = writeln!(
+                dot,
+                "    s{level}_{idx} [label=\"[{idx}, {last}] | {value}\"];"
+            );
+            if level > 0 {
+                let below = level - 1;
+                let _ = writeln!(dot, "    s{level}_{idx} -> s{below}_{idx};");
+                let _ = writeln!(dot, "    s{level}_{idx} -> s{below}_{};", idx + half);
+            }
+        }
+    }
+    dot.push('}');
+    dot
+}
+
+/// Segment tree backed by a heap allocated node array
+///
+/// The array is shared with the tree's snapshots, see [`Self::snapshot`].
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SegmentTree<T, M> {
+    tree: Shared<Vec<T>>,
+    _phantom: PhantomData<M>,
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> SegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("SegmentTree");
+        let leaves: Vec<T> = data.into_iter().collect();
+        let len = leaves.len();
+        span.record_len(len);
+        let mut tree = Vec::with_capacity(2 * len);
+        tree.extend_from_slice(&leaves);
+        tree.extend_from_slice(&leaves);
+        for idx in (1..len).rev() {
+            tree[idx] = M::combine(tree[2 * idx], tree[2 * idx + 1]);
+        }
+
+        Self {
+            tree: Shared::new(tree),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Number of elements in the tree
+    pub fn len(&self) -> usize {
+        self.tree.len() / 2
+    }
+
+    /// Borrow the current elements, the leaves of the tree
+    pub fn data(&self) -> &[T] {
+        &self.tree[self.len()..]
+    }
+
+    /// Take back the node array, node `i` has children `2i` and `2i + 1`
+    /// and the leaves fill the second half
+    pub fn into_inner(self) -> Vec<T> {
+        Shared::unwrap_or_clone(self.tree)
+    }
+
+    /// Whether the tree holds no elements
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        fold_tree::<T, M>(self.len(), start, end, |idx| self.tree[idx])
+    }
+
+    /// Current element at `index`
+    pub fn get(&self, index: usize) -> T {
+        assert!(index < self.len());
+        self.tree[self.len() + index]
+    }
+
+    /// Replace the element at `index` with `value`
+    ///
+    /// Algorithmic complexity: O(log n), O(n) for the first update after a
+    /// snapshot
+    pub fn update(&mut self, index: usize, value: T) {
+        let len = self.len();
+        assert!(index < len);
+        let tree = Shared::make_mut(&mut self.tree);
+        let mut idx = index + len;
+        tree[idx] = value;
+        while idx > 1 {
+            idx >>= 1;
+            tree[idx] = M::combine(tree[2 * idx], tree[2 * idx + 1]);
+        }
+    }
+
+    /// Frozen view of the current elements
+    ///
+    /// Algorithmic complexity: O(1), the snapshot shares the node array
+    /// until the next update of `self` copies it
+    pub fn snapshot(&self) -> Snapshot<Self> {
+        Snapshot::new(Self {
+            tree: Shared::clone(&self.tree),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Combine `self` and `other` element by element, e.g. adding up two
+    /// trees of counts over the same positions
+    ///
+    /// Trees over a large domain of which few positions are set merge
+    /// faster as [`DynamicSegmentTree`](crate::dynamic::DynamicSegmentTree)s.
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Panics
+    ///
+    /// If the trees hold a different number of elements
+    pub fn merge(mut self, other: Self) -> Self {
+        let len = self.len();
+        assert_eq!(len, other.len(), "merged trees differ in length");
+        let tree = Shared::make_mut(&mut self.tree);
+        for (leaf, other) in tree[len..]
+            .iter_mut()
+            .zip(other.into_inner().into_iter().skip(len))
+        {
+            *leaf = M::combine(*leaf, other);
+        }
+        for idx in (1..len).rev() {
+            tree[idx] = M::combine(tree[2 * idx], tree[2 * idx + 1]);
+        }
+        self
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> UpdatableRangeQuery<T> for SegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+
+    /// Writes every leaf first and then recomputes each affected internal
+    /// node once, or the whole tree when the batch is large
+    fn update_batch(&mut self, updates: impl IntoIterator<Item = (usize, T)>) {
+        let len = self.len();
+        let tree = Shared::make_mut(&mut self.tree);
+        let mut dirty = Vec::new();
+        for (index, value) in updates {
+            assert!(index < len);
+            tree[len + index] = value;
+            dirty.push((len + index) >> 1);
+        }
+        if dirty.is_empty() {
+            return;
+        }
+
+        if dirty.len() * (len.ilog2() as usize + 1) >= len {
+            for idx in (1..len).rev() {
+                tree[idx] = M::combine(tree[2 * idx], tree[2 * idx + 1]);
+            }
+            return;
+        }
+        // Every round moves each dirty node up by one, deduplicated. When
+        // `n` is not a power of two, leaves sit at two depths and a node
+        // can be recomputed in several rounds, each child pushes it again
+        // after its own update, so the last recompute sees final children
+        while !dirty.is_empty() {
+            dirty.sort_unstable();
+            dirty.dedup();
+            for idx in &mut dirty {
+                tree[*idx] = M::combine(tree[2 * *idx], tree[2 * *idx + 1]);
+                *idx >>= 1;
+            }
+            dirty.retain(|&idx| idx > 0);
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> MemoryUsage for SegmentTree<T, M> {
+    /// Counts the shared allocation holding the array, reference counts
+    /// included
+    fn heap_bytes(&self) -> usize {
+        2 * size_of::<usize>() + size_of::<Vec<T>>() + self.tree.heap_bytes()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> RangeQuery<T> for SegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy, M: Operation<T>> From<Vec<T>> for SegmentTree<T, M> {
+    fn from(data: Vec<T>) -> Self {
+        Self::new(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy, M: Operation<T>> From<&[T]> for SegmentTree<T, M> {
+    fn from(data: &[T]) -> Self {
+        Self::new(data.iter().copied())
+    }
 }
 
-impl<T, T2, M> Rmq<T, T2, M> {
+#[cfg(feature = "alloc")]
+impl<T: Copy, M: Operation<T>> FromIterator<T> for SegmentTree<T, M> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: fmt::Display, M> SegmentTree<T, M> {
+    /// Graphviz description of every node, the elements it covers and its
+    /// value
+    pub fn to_dot(&self) -> String {
+        tree_dot(self.tree.len() / 2, |idx| &self.tree[idx])
+    }
+}
+
+/// Lists the nodes one depth at a time, keyed by the elements each covers
+#[cfg(feature = "alloc")]
+impl<T: fmt::Debug, M> fmt::Debug for SegmentTree<T, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.tree.len() / 2;
+        let levels = TreeLevels {
+            len,
+            node: |idx: usize| &self.tree[idx],
+        };
+        f.debug_struct("SegmentTree")
+            .field("len", &len)
+            .field("levels", &levels)
+            .finish()
+    }
+}
+
+/// Renders the tree one node per line, children indented below their parent
+#[cfg(feature = "alloc")]
+impl<T: fmt::Display, M> fmt::Display for SegmentTree<T, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.tree.len() / 2;
+        if len == 0 {
+            return Ok(());
+        }
+        fmt_tree(f, len, 1, 0, &|idx| &self.tree[idx])
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy> SegmentTree<T, ()> {
+    /// Construct a [`SegmentTreeWith`] combining with the closure `op`,
+    /// for aggregates without an [`Operation`] type
+    ///
+    /// `op` has to be associative with `identity` as its identity.
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// ```
+    /// use kuehree::rmq::SegmentTree;
+    ///
+    /// // Largest element together with how often it occurs
+    /// let data = [3, 7, 7, 2, 7].map(|d| (d, 1));
+    /// let tree = SegmentTree::with_op(data, (i32::MIN, 0), |a, b| match a.0.cmp(&b.0) {
+    ///     std::cmp::Ordering::Less => b,
+    ///     std::cmp::Ordering::Greater => a,
+    ///     std::cmp::Ordering::Equal => (a.0, a.1 + b.1),
+    /// });
+    /// assert_eq!(tree.query(0, 4), (7, 3));
+    /// assert_eq!(tree.query(0, 1), (7, 1));
+    /// ```
+    pub fn with_op<F>(
+        data: impl IntoIterator<Item = T>,
+        identity: T,
+        op: F,
+    ) -> SegmentTreeWith<T, F>
+    where
+        F: Fn(T, T) -> T,
+    {
+        SegmentTreeWith::new(data, identity, op)
+    }
+}
+
+/// Segment tree combining with a closure instead of an [`Operation`]
+/// type, see [`SegmentTree::with_op`]
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
+pub struct SegmentTreeWith<T, F> {
+    tree: Vec<T>,
+    identity: T,
+    op: F,
+}
+
+#[cfg(feature = "alloc")]
+impl<T, F> SegmentTreeWith<T, F>
+where
+    T: Copy,
+    F: Fn(T, T) -> T,
+{
+    /// Construct `Self`, `op` has to be associative with `identity` as its
+    /// identity
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>, identity: T, op: F) -> Self {
+        let span = BuildSpan::enter("SegmentTreeWith");
+        let leaves: Vec<T> = data.into_iter().collect();
+        let len = leaves.len();
+        span.record_len(len);
+        let mut tree = Vec::with_capacity(2 * len);
+        tree.extend_from_slice(&leaves);
+        tree.extend_from_slice(&leaves);
+        for idx in (1..len).rev() {
+            tree[idx] = op(tree[2 * idx], tree[2 * idx + 1]);
+        }
+
+        Self { tree, identity, op }
+    }
+
+    /// Number of elements in the tree
+    pub fn len(&self) -> usize {
+        self.tree.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Borrow the current elements, the leaves of the tree
+    pub fn data(&self) -> &[T] {
+        &self.tree[self.len()..]
+    }
+
+    pub fn identity(&self) -> T {
+        self.identity
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        let len = self.len();
+        assert!(end >= start && end < len);
+        let (mut l, mut r) = (start + len, end + len + 1);
+        let (mut left, mut right) = (self.identity, self.identity);
+        while l < r {
+            if l & 1 == 1 {
+                left = (self.op)(left, self.tree[l]);
+                l += 1;
+            }
+            if r & 1 == 1 {
+                r -= 1;
+                right = (self.op)(self.tree[r], right);
+            }
+            l >>= 1;
+            r >>= 1;
+        }
+        (self.op)(left, right)
+    }
+
+    /// Current element at `index`
+    pub fn get(&self, index: usize) -> T {
+        assert!(index < self.len());
+        self.tree[self.len() + index]
+    }
+
+    /// Replace the element at `index` with `value`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn update(&mut self, index: usize, value: T) {
+        let len = self.len();
+        assert!(index < len);
+        let mut idx = index + len;
+        self.tree[idx] = value;
+        while idx > 1 {
+            idx >>= 1;
+            self.tree[idx] = (self.op)(self.tree[2 * idx], self.tree[2 * idx + 1]);
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, F> UpdatableRangeQuery<T> for SegmentTreeWith<T, F>
+where
+    T: Copy,
+    F: Fn(T, T) -> T,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, F> MemoryUsage for SegmentTreeWith<T, F> {
+    fn heap_bytes(&self) -> usize {
+        self.tree.heap_bytes()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, F> RangeQuery<T> for SegmentTreeWith<T, F>
+where
+    T: Copy,
+    F: Fn(T, T) -> T,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Lists the nodes one depth at a time, keyed by the elements each covers
+#[cfg(feature = "alloc")]
+impl<T: fmt::Debug, F> fmt::Debug for SegmentTreeWith<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.tree.len() / 2;
+        let levels = TreeLevels {
+            len,
+            node: |idx: usize| &self.tree[idx],
+        };
+        f.debug_struct("SegmentTreeWith")
+            .field("len", &len)
+            .field("identity", &self.identity)
+            .field("levels", &levels)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Segment tree backed by fixed size arrays, requires no allocator
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SegmentTreeFixed<T, M, const N: usize> {
+    leaves: [T; N],
+    // `nodes[0]` is unused, the root lives at `nodes[1]`
+    nodes: [T; N],
+    _phantom: PhantomData<M>,
+}
+
+impl<T, M, const N: usize> SegmentTreeFixed<T, M, N>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: [T; N]) -> Self {
+        let span = BuildSpan::enter("SegmentTreeFixed");
+        span.record_len(N);
+        let mut tree = Self {
+            leaves: data,
+            nodes: data,
+            _phantom: PhantomData,
+        };
+        for idx in (1..N).rev() {
+            tree.nodes[idx] = M::combine(tree.node(2 * idx), tree.node(2 * idx + 1));
+        }
+        tree
+    }
+
+    /// Borrow the current elements, the leaves of the tree
+    pub fn data(&self) -> &[T; N] {
+        &self.leaves
+    }
+
+    /// Take back the leaves and the internal nodes, laid out as in
+    /// [`SegmentTree::into_inner`] with `nodes[0]` unused
+    pub fn into_parts(self) -> ([T; N], [T; N]) {
+        (self.leaves, self.nodes)
+    }
+
+    fn node(&self, idx: usize) -> T {
+        if idx >= N {
+            self.leaves[idx - N]
+        } else {
+            self.nodes[idx]
+        }
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        fold_tree::<T, M>(N, start, end, |idx| self.node(idx))
+    }
+
+    /// Current element at `index`
+    pub fn get(&self, index: usize) -> T {
+        self.leaves[index]
+    }
+
+    /// Replace the element at `index` with `value`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn update(&mut self, index: usize, value: T) {
+        assert!(index < N);
+        self.leaves[index] = value;
+        let mut idx = index + N;
+        while idx > 1 {
+            idx >>= 1;
+            self.nodes[idx] = M::combine(self.node(2 * idx), self.node(2 * idx + 1));
+        }
+    }
+}
+
+impl<T, M, const N: usize> UpdatableRangeQuery<T> for SegmentTreeFixed<T, M, N>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+}
+
+impl<T, M, const N: usize> MemoryUsage for SegmentTreeFixed<T, M, N> {
+    fn heap_bytes(&self) -> usize {
+        0
+    }
+}
+
+impl<T, M, const N: usize> RangeQuery<T> for SegmentTreeFixed<T, M, N>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        N
+    }
+}
+
+/// Every element set to `T::default()`
+impl<T, M, const N: usize> Default for SegmentTreeFixed<T, M, N>
+where
+    T: Copy + Default,
+    M: Operation<T>,
+{
+    fn default() -> Self {
+        Self::new([T::default(); N])
+    }
+}
+
+impl<T: Copy, M: Operation<T>, const N: usize> From<[T; N]> for SegmentTreeFixed<T, M, N> {
+    fn from(data: [T; N]) -> Self {
+        Self::new(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: fmt::Display, M, const N: usize> SegmentTreeFixed<T, M, N> {
+    /// Graphviz description of every node, the elements it covers and its
+    /// value
+    pub fn to_dot(&self) -> String {
+        tree_dot(N, |idx| {
+            if idx >= N {
+                &self.leaves[idx - N]
+            } else {
+                &self.nodes[idx]
+            }
+        })
+    }
+}
+
+/// Lists the nodes one depth at a time, keyed by the elements each covers
+impl<T: fmt::Debug, M, const N: usize> fmt::Debug for SegmentTreeFixed<T, M, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let levels = TreeLevels {
+            len: N,
+            node: |idx: usize| {
+                if idx >= N {
+                    &self.leaves[idx - N]
+                } else {
+                    &self.nodes[idx]
+                }
+            },
+        };
+        f.debug_struct("SegmentTreeFixed")
+            .field("len", &N)
+            .field("levels", &levels)
+            .finish()
+    }
+}
+
+/// Renders the tree one node per line, children indented below their parent
+impl<T: fmt::Display, M, const N: usize> fmt::Display for SegmentTreeFixed<T, M, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if N == 0 {
+            return Ok(());
+        }
+        fmt_tree(f, N, 1, 0, &|idx| {
+            if idx >= N {
+                &self.leaves[idx - N]
+            } else {
+                &self.nodes[idx]
+            }
+        })
+    }
+}
+
+/// Containers an [`Rmq`] can be built from
+pub trait RmqBackend<T, M> {
+    /// Structure answering the queries
+    type Table: RangeQuery<T>;
+
+    fn build(self) -> Self::Table;
+}
+
+/// Arrays are backed by [`SegmentTreeFixed`] so that no allocator is needed
+impl<T, M, const N: usize> RmqBackend<T, M> for [T; N]
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    type Table = SegmentTreeFixed<T, M, N>;
+
+    fn build(self) -> Self::Table {
+        SegmentTreeFixed::new(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> RmqBackend<T, M> for Vec<T>
+where
+    T: Copy,
+    M: Idempotent<T>,
+{
+    type Table = SparseTable<T, M>;
+
+    fn build(self) -> Self::Table {
+        SparseTable::new(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, M> RmqBackend<T, M> for SegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    type Table = Self;
+
+    fn build(self) -> Self::Table {
+        self
+    }
+}
+
+/// Range query over container `T` with elements `T2`, folded with `M`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rmq<T: RmqBackend<T2, M>, T2, M> {
+    table: T::Table,
+    _phantom: PhantomData<(T, T2, M)>,
+}
+
+impl<T: RmqBackend<T2, M>, T2, M> Rmq<T, T2, M> {
     pub fn new(data: T) -> Self {
-        todo!()
+        Self {
+            table: data.build(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Query between start range and end range
+    pub fn query(&self, start: usize, end: usize) -> T2 {
+        self.table.query(start, end)
+    }
+
+    /// Borrow the structure answering the queries
+    pub fn table(&self) -> &T::Table {
+        &self.table
+    }
+
+    /// Take back the structure answering the queries
+    pub fn into_inner(self) -> T::Table {
+        self.table
     }
 }
 
-impl<T2: Num, const N: usize> Rmq<[T2; N], T2, Max> {
-    pub fn query() {}
+impl<T: RmqBackend<T2, M>, T2, M> MemoryUsage for Rmq<T, T2, M>
+where
+    T::Table: MemoryUsage,
+{
+    fn heap_bytes(&self) -> usize {
+        self.table.heap_bytes()
+    }
+}
+
+impl<T: RmqBackend<T2, M>, T2, M> RangeQuery<T2> for Rmq<T, T2, M> {
+    fn query(&self, start: usize, end: usize) -> T2 {
+        self.table.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.table.len()
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Max, Min, Rmq, SegmentTree, SparseTableFixed};
+    use super::*;
+
+    const ARR: [u8; 8] = [1, 3, 4, 8, 6, 1, 4, 2];
+
+    fn naive<M: Operation<u8>>(start: usize, end: usize) -> u8 {
+        ARR[start..=end].iter().copied().reduce(M::combine).unwrap()
+    }
 
-    #[ignore]
     #[test]
-    #[allow(unused_variables)]
     fn test() {
-        let arr = [1, 3, 4, 8, 6, 1, 4, 2];
-        let arr_2 = vec![1, 3, 4, 8, 6, 1, 4, 2];
-        let sgtree = SegmentTree::new(10u8);
-
-        let range_min = Rmq::<[u8; 8], u8, Min>::new(arr);
-        let range_max = Rmq::<[u8; 8], u8, Max>::new(arr);
-        let range_max_sgmt_tree = Rmq::<SegmentTree<u8>, u8, Max>::new(sgtree);
-        let range_max_sgmt_tree = Rmq::<Vec<u8>, u8, Max>::new(arr_2);
-        let sparse_table = SparseTableFixed::<u8, 8, 9>::new(arr);
+        let sgtree = SegmentTree::<u8, Max>::new(ARR);
+
+        let range_min = Rmq::<[u8; 8], u8, Min>::new(ARR);
+        let range_max = Rmq::<[u8; 8], u8, Max>::new(ARR);
+        let range_max_sgmt_tree = Rmq::<SegmentTree<u8, Max>, u8, Max>::new(sgtree);
+        let range_max_vec = Rmq::<Vec<u8>, u8, Max>::new(ARR.to_vec());
+        let sparse_table = SparseTableFixed::<u8, Min, 8, { levels(8) }>::new(ARR);
+
+        for start in 0..ARR.len() {
+            for end in start..ARR.len() {
+                assert_eq!(range_min.query(start, end), naive::<Min>(start, end));
+                assert_eq!(range_max.query(start, end), naive::<Max>(start, end));
+                assert_eq!(
+                    range_max_sgmt_tree.query(start, end),
+                    naive::<Max>(start, end)
+                );
+                assert_eq!(range_max_vec.query(start, end), naive::<Max>(start, end));
+                assert_eq!(sparse_table.query(start, end), naive::<Min>(start, end));
+            }
+        }
+    }
+
+    #[test]
+    fn test_odd_len() {
+        let data = [5u32, 2, 9, 7, 3, 8, 1];
+        let fixed = SegmentTreeFixed::<_, Min, 7>::new(data);
+        let table = SparseTable::<_, Max>::new(data);
+
+        for start in 0..data.len() {
+            for end in start..data.len() {
+                let range = &data[start..=end];
+                assert_eq!(fixed.query(start, end), *range.iter().min().unwrap());
+                assert_eq!(table.query(start, end), *range.iter().max().unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn test_operations() {
+        let data = [12u32, 18, 30, 7, 42, 0b1011, 0b0110];
+        let gcd = SparseTable::<_, Gcd>::new(data);
+        let and = SparseTableFixed::<_, And, 7, 3>::new(data);
+        let or = Rmq::<_, _, Or>::new(data.to_vec());
+        let xor = SegmentTree::<_, Xor>::new(data);
+        let prod = SegmentTreeFixed::<_, Prod, 7>::new(data.map(u64::from));
+
+        for start in 0..data.len() {
+            for end in start..data.len() {
+                let range = data[start..=end].iter().copied();
+                assert_eq!(gcd.query(start, end), range.clone().fold(0, Gcd::combine));
+                assert_eq!(and.query(start, end), range.clone().fold(!0, |a, b| a & b));
+                assert_eq!(or.query(start, end), range.clone().fold(0, |a, b| a | b));
+                assert_eq!(xor.query(start, end), range.clone().fold(0, |a, b| a ^ b));
+                assert_eq!(
+                    prod.query(start, end),
+                    range.map(u64::from).product::<u64>()
+                );
+            }
+        }
+        assert_eq!(gcd.query(0, 2), 6);
+    }
+
+    #[test]
+    fn test_lcm() {
+        let data = [4u8, 6, 10, 7, 0, 9];
+        let table = SparseTable::<_, Lcm>::new(data.map(LcmValue::from));
+        assert_eq!(table.query(0, 1), LcmValue::Exact(12));
+        assert_eq!(table.query(0, 2).exact(), Some(60));
+        assert_eq!(table.query(0, 3), LcmValue::Overflow);
+        assert_eq!(table.query(3, 4), LcmValue::Exact(0));
+        assert_eq!(table.query(0, 4), LcmValue::Exact(0));
+        assert_eq!(table.query(0, 5), LcmValue::Exact(0));
+
+        // Any fold order gives the same answer
+        let tree = SegmentTree::<_, Lcm>::new(data.map(LcmValue::from));
+        for start in 0..data.len() {
+            for end in start..data.len() {
+                assert_eq!(tree.query(start, end), table.query(start, end));
+            }
+        }
+
+        let primes = [2u128, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47];
+        let tree = SegmentTree::<_, Lcm>::new(primes.map(LcmValue::from));
+        assert_eq!(
+            tree.query(0, primes.len() - 1).exact(),
+            Some(primes.iter().product::<u128>())
+        );
+    }
+
+    #[test]
+    fn test_with_op() {
+        /// Longest run of equal elements, with the runs touching both ends
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Runs {
+            len: usize,
+            first: u8,
+            last: u8,
+            prefix: usize,
+            suffix: usize,
+            best: usize,
+        }
+
+        let leaf = |d| Runs {
+            len: 1,
+            first: d,
+            last: d,
+            prefix: 1,
+            suffix: 1,
+            best: 1,
+        };
+        let empty = Runs {
+            len: 0,
+            first: 0,
+            last: 0,
+            prefix: 0,
+            suffix: 0,
+            best: 0,
+        };
+        let data = [1u8, 1, 2, 2, 2, 1, 3, 3, 3, 3, 1];
+        let mut tree = SegmentTree::with_op(data.map(leaf), empty, |a: Runs, b: Runs| {
+            if a.len == 0 || b.len == 0 {
+                return if a.len == 0 { b } else { a };
+            }
+            let touching = a.last == b.first;
+            let joined = if touching { a.suffix + b.prefix } else { 0 };
+            Runs {
+                len: a.len + b.len,
+                first: a.first,
+                last: b.last,
+                prefix: if touching && a.prefix == a.len {
+                    a.len + b.prefix
+                } else {
+                    a.prefix
+                },
+                suffix: if touching && b.suffix == b.len {
+                    a.suffix + b.len
+                } else {
+                    b.suffix
+                },
+                best: a.best.max(b.best).max(joined),
+            }
+        });
+
+        let longest = |data: &[u8]| data.chunk_by(|a, b| a == b).map(<[u8]>::len).max().unwrap();
+        for start in 0..data.len() {
+            for end in start..data.len() {
+                assert_eq!(tree.query(start, end).best, longest(&data[start..=end]));
+            }
+        }
+        tree.update(5, leaf(2));
+        assert_eq!(tree.query(0, 10).best, 4);
+        assert_eq!(tree.query(2, 5).best, 4);
+    }
+
+    #[test]
+    fn test_from() {
+        let from_vec = SegmentTree::<_, Sum>::from(ARR.to_vec());
+        let from_slice = SegmentTree::<_, Sum>::from(&ARR[..]);
+        let collected: SegmentTree<_, Sum> = ARR.iter().copied().collect();
+        assert_eq!(from_vec, from_slice);
+        assert_eq!(from_vec, collected);
+        assert_eq!(collected.query(2, 5), 19);
+
+        let fixed = SegmentTreeFixed::<_, Max, 8>::from(ARR);
+        assert_eq!(fixed.query(0, 7), 8);
+        assert_eq!(fixed, SegmentTreeFixed::new(ARR));
+    }
+
+    #[test]
+    fn test_layout() {
+        let data: Vec<u32> = (0..100u32).map(|i| (i * 37) % 101).collect();
+        let levels = SparseTable::<_, Min>::with_layout(data.iter().copied(), Layout::Levels);
+        let blocked = SparseTable::<_, Min>::with_layout(data.iter().copied(), Layout::Blocked);
+        assert_eq!(blocked.answers().len(), 104 * 7);
+
+        for start in 0..data.len() {
+            for end in start..data.len() {
+                let min = *data[start..=end].iter().min().unwrap();
+                assert_eq!(levels.query(start, end), min);
+                assert_eq!(blocked.query(start, end), min);
+            }
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_new() {
+        let data: Vec<u32> = (0..100_000u32)
+            .map(|i| i.wrapping_mul(2_654_435_761))
+            .collect();
+        for layout in [Layout::Levels, Layout::Blocked] {
+            let parallel = SparseTable::<_, Max>::par_with_layout(data.iter().copied(), layout);
+            assert_eq!(
+                parallel,
+                SparseTable::<_, Max>::with_layout(data.iter().copied(), layout)
+            );
+        }
+        let small = SparseTable::<_, Min>::par_new(ARR);
+        assert_eq!(small.query(2, 6), 1);
+    }
+
+    #[test]
+    fn test_update() {
+        let mut fixed = SegmentTreeFixed::<_, Max, 8>::new(ARR);
+        let mut tree = SegmentTree::<_, Max>::new(ARR);
+        fixed.update(2, 10);
+        tree.update(2, 10);
+
+        assert_eq!(fixed.query(0, 7), 10);
+        assert_eq!(tree.query(0, 7), 10);
+        assert_eq!(fixed.query(3, 7), 8);
+        assert_eq!(tree.query(3, 7), 8);
+    }
+
+    #[test]
+    fn test_merge() {
+        let dense = SegmentTree::<_, Sum>::new([1, 2, 3]);
+        let merged = dense.clone().merge(dense);
+        assert_eq!(merged.data(), [2, 4, 6]);
+        assert_eq!(merged.query(0, 2), 12);
+    }
+
+    #[test]
+    #[should_panic(expected = "merged trees differ in length")]
+    fn test_merge_len_mismatch() {
+        SegmentTree::<_, Sum>::new([1, 2, 3]).merge(SegmentTree::new([1, 2]));
+    }
+
+    #[test]
+    fn test_accessors() {
+        let fixed = SparseTableFixed::<u8, Min, 8, 4>::new(ARR);
+        assert_eq!(fixed.data(), ARR);
+        assert_eq!(fixed.into_inner()[1][..7], [1, 3, 4, 6, 1, 1, 2]);
+
+        let table = SparseTable::<_, Max>::new(ARR);
+        assert!(table.data().eq(ARR));
+        assert_eq!(table.answers().len(), 8 * 4);
+        assert_eq!(table.into_answers()[8..15], [3, 4, 8, 8, 6, 4, 4]);
+        let blocked = SparseTable::<_, Max>::with_layout(ARR, Layout::Blocked);
+        assert!(blocked.data().eq(ARR));
+        assert_eq!(blocked.layout(), Layout::Blocked);
+        assert_eq!(blocked.into_answers()[8..15], [3, 4, 8, 8, 6, 4, 4]);
+
+        let mut tree = SegmentTree::<_, Max>::new(ARR);
+        tree.update(0, 5);
+        assert_eq!(tree.data(), [5, 3, 4, 8, 6, 1, 4, 2]);
+        assert_eq!(tree.into_inner()[1], 8);
+
+        let fixed = SegmentTreeFixed::<_, Max, 8>::new(ARR);
+        assert_eq!(fixed.data(), &ARR);
+        assert_eq!(fixed.into_parts().1[1], 8);
+
+        let rmq = Rmq::<Vec<u8>, u8, Min>::new(ARR.to_vec());
+        assert!(rmq.table().data().eq(ARR));
+    }
+
+    #[test]
+    fn test_display() {
+        let expected = "8\n  4\n    [0] 1\n    [1] 4\n  8\n    [2] 8\n    [3] 6";
+        let data = [1u8, 4, 8, 6];
+        assert_eq!(SegmentTree::<_, Max>::new(data).to_string(), expected);
+        assert_eq!(
+            SegmentTreeFixed::<_, Max, 4>::new(data).to_string(),
+            expected
+        );
+
+        let odd = SegmentTreeFixed::<_, Min, 3>::new([5u8, 2, 9]);
+        assert_eq!(odd.to_string(), "2\n  2\n    [1] 2\n    [2] 9\n  [0] 5");
+        assert_eq!(SegmentTree::<u8, Min>::new([]).to_string(), "");
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let tree = "digraph SegmentTree {
+    node [shape=record];
+    n1 [label=\"[1, 0] | 9\"];
+    n1 -> n2;
+    n1 -> n3;
+    n2 [label=\"[1, 2] | 9\"];
+    n2 -> n4;
+    n2 -> n5;
+    n3 [label=\"[0, 0] | 5\"];
+    n4 [label=\"[1, 1] | 2\"];
+    n5 [label=\"[2, 2] | 9\"];
+}";
+        assert_eq!(SegmentTree::<_, Max>::new([5u8, 2, 9]).to_dot(), tree);
+        assert_eq!(
+            SegmentTreeFixed::<_, Max, 3>::new([5u8, 2, 9]).to_dot(),
+            tree
+        );
+
+        let table = "digraph SparseTable {
+    node [shape=record];
+    s0_0 [label=\"[0, 0] | 5\"];
+    s0_1 [label=\"[1, 1] | 2\"];
+    s0_2 [label=\"[2, 2] | 9\"];
+    s1_0 [label=\"[0, 1] | 2\"];
+    s1_0 -> s0_0;
+    s1_0 -> s0_1;
+    s1_1 [label=\"[1, 2] | 2\"];
+    s1_1 -> s0_1;
+    s1_1 -> s0_2;
+}";
+        for layout in [Layout::Levels, Layout::Blocked] {
+            let sparse = SparseTable::<_, Min>::with_layout([5u8, 2, 9], layout);
+            assert_eq!(sparse.to_dot(), table);
+        }
+        let fixed = SparseTableFixed::<_, Min, 3, 3>::new([5u8, 2, 9]);
+        assert_eq!(fixed.to_dot(), table);
+    }
+
+    #[test]
+    fn test_debug() {
+        let data = [5u8, 2, 9];
+        let table = "SparseTable { len: 3, layout: Levels, levels: [\
+            {[0, 0]: 5, [1, 1]: 2, [2, 2]: 9}, {[0, 1]: 2, [1, 2]: 2}] }";
+        assert_eq!(format!("{:?}", SparseTable::<_, Min>::new(data)), table);
+        let fixed = SparseTableFixed::<_, Min, 3, 2>::new(data);
+        assert_eq!(
+            format!("{fixed:?}"),
+            table
+                .replace("SparseTable", "SparseTableFixed")
+                .replace(" layout: Levels,", "")
+        );
+
+        let tree = "SegmentTree { len: 3, levels: [\
+            {[1, 0]: 9}, {[1, 2]: 9, [0, 0]: 5}, {[1, 1]: 2, [2, 2]: 9}] }";
+        assert_eq!(format!("{:?}", SegmentTree::<_, Max>::new(data)), tree);
+        let fixed = SegmentTreeFixed::<_, Max, 3>::new(data);
+        assert_eq!(
+            format!("{fixed:?}"),
+            tree.replace("SegmentTree", "SegmentTreeFixed")
+        );
+        assert_eq!(
+            format!("{:?}", SegmentTree::<u8, Max>::default()),
+            "SegmentTree { len: 0, levels: [] }"
+        );
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let fixed = SparseTableFixed::<u8, Min, 8, 4>::new(ARR);
+        assert_eq!(fixed.heap_bytes(), 0);
+        assert_eq!(fixed.memory_footprint(), 32);
+        assert_eq!(
+            SegmentTreeFixed::<u8, Min, 8>::new(ARR).memory_footprint(),
+            16
+        );
+
+        assert_eq!(SparseTable::<u8, Min>::new(ARR).heap_bytes(), 32);
+        assert_eq!(
+            SegmentTree::<u8, Min>::new(ARR).heap_bytes(),
+            2 * size_of::<usize>() + size_of::<Vec<u8>>() + 16
+        );
+        assert_eq!(Rmq::<Vec<u8>, u8, Min>::new(ARR.to_vec()).heap_bytes(), 32);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut tree = SegmentTree::<_, Max>::new(ARR);
+        let mut tx = tree.begin();
+        tx.update(2, 10);
+        tx.update(5, 12);
+        tx.update(2, 0);
+        assert_eq!(tx.get(2), 0);
+        assert_eq!(tx.len(), 3);
+        tx.abort();
+        assert_eq!(tree.query(0, 7), 8);
+
+        let mut tx = tree.begin();
+        tx.update(2, 10);
+        tx.update(5, 12);
+        tx.update(2, 0);
+        tx.commit();
+
+        let mut expected = ARR;
+        expected[2] = 0;
+        expected[5] = 12;
+        assert_eq!(tree, SegmentTree::new(expected));
+
+        let mut large = SegmentTree::<_, Min>::new(ARR);
+        large.update_batch((0..8).map(|idx| (idx, 7 - idx as u8)));
+        assert_eq!(large.data(), [7, 6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(large.query(0, 6), 1);
+        assert_eq!(large.query(0, 3), 4);
+
+        let mut empty = SegmentTree::<u32, Sum>::new([]);
+        empty.update_batch([]);
+        assert!(empty.is_empty());
+
+        // Leaves at two depths, the sparse batch path
+        let data: Vec<u32> = (0..13).collect();
+        let mut odd = SegmentTree::<_, Sum>::new(data.iter().copied());
+        odd.update_batch([(12, 100), (3, 50)]);
+        let mut expected = data;
+        expected[12] = 100;
+        expected[3] = 50;
+        assert_eq!(odd, SegmentTree::new(expected));
+
+        let mut fixed = SegmentTreeFixed::<_, Max, 8>::new(ARR);
+        let mut tx = fixed.begin();
+        tx.update(0, 9);
+        tx.commit();
+        assert_eq!(fixed.query(0, 7), 9);
+    }
+
+    #[test]
+    fn test_derives() {
+        use std::collections::HashSet;
+
+        let tables: HashSet<_> = [ARR, ARR, [0; 8]]
+            .into_iter()
+            .map(SparseTable::<_, Min>::new)
+            .collect();
+        assert_eq!(tables.len(), 2);
+
+        assert!(SparseTable::<u8, Max>::default().is_empty());
+        assert!(SegmentTree::<u8, Max>::default().is_empty());
+        assert_eq!(SegmentTreeFixed::<u8, Max, 4>::default().query(0, 3), 0);
+        let rmq = Rmq::<[u8; 1], u8, Min>::new([1]);
+        assert!(format!("{rmq:?}").starts_with("Rmq { table: SegmentTreeFixed"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_too_few_levels() {
+        SparseTableFixed::<u8, Min, 8, 3>::new(ARR);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert_eq!(
+            SparseTableFixed::<u8, Min, 8, 3>::try_new(ARR).err(),
+            Some(Error::InsufficientLevels {
+                required: 4,
+                levels: 3
+            })
+        );
+        assert!(SparseTableFixed::<u8, Min, 8, 5>::try_new(ARR).is_ok());
     }
 }
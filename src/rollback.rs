@@ -0,0 +1,288 @@
+//! Undo log for updatable structures, as needed by offline algorithms such
+//! as divide and conquer over time
+
+use alloc::{vec, vec::Vec};
+
+use crate::{MemoryUsage, RangeQuery, UpdatableRangeQuery};
+
+/// Wraps an [`UpdatableRangeQuery`], logging the value every update
+/// overwrites so that updates can be undone in reverse order
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Rollback<S, T> {
+    inner: S,
+    log: Vec<(usize, T)>,
+}
+
+impl<S, T> Rollback<S, T>
+where
+    S: UpdatableRangeQuery<T>,
+{
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            log: Vec::new(),
+        }
+    }
+
+    /// Replace the element at `index` with `value`, remembering the old one
+    pub fn update(&mut self, index: usize, value: T) {
+        self.log.push((index, self.inner.get(index)));
+        self.inner.update(index, value);
+    }
+
+    /// Number of updates that can be undone, pass it to
+    /// [`Self::rollback_to`] to return to the current state
+    pub fn checkpoint(&self) -> usize {
+        self.log.len()
+    }
+
+    /// Undo the last `n` updates
+    ///
+    /// Algorithmic complexity: O(n) times the cost of an update
+    ///
+    /// # Panics
+    ///
+    /// If fewer than `n` updates are logged
+    pub fn rollback(&mut self, n: usize) {
+        assert!(
+            n <= self.log.len(),
+            "only {} updates to roll back",
+            self.log.len()
+        );
+        for _ in 0..n {
+            if let Some((index, value)) = self.log.pop() {
+                self.inner.update(index, value);
+            }
+        }
+    }
+
+    /// Undo every update made since `checkpoint`
+    ///
+    /// # Panics
+    ///
+    /// If `checkpoint` is newer than the current state
+    pub fn rollback_to(&mut self, checkpoint: usize) {
+        self.rollback(self.log.len() - checkpoint);
+    }
+
+    /// Forget the logged updates, making the current state permanent
+    pub fn clear_log(&mut self) {
+        self.log.clear();
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, T> RangeQuery<T> for Rollback<S, T>
+where
+    S: UpdatableRangeQuery<T>,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.inner.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<S, T> UpdatableRangeQuery<T> for Rollback<S, T>
+where
+    S: UpdatableRangeQuery<T>,
+{
+    fn get(&self, index: usize) -> T {
+        self.inner.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+}
+
+impl<S: MemoryUsage, T> MemoryUsage for Rollback<S, T> {
+    fn heap_bytes(&self) -> usize {
+        self.inner.heap_bytes() + self.log.heap_bytes()
+    }
+}
+
+/// For every query, the smallest number of leading `updates` after which
+/// `check` holds, `None` if it fails even after all of them
+///
+/// `check` has to be monotone: once it holds for a query, further updates
+/// keep it holding. Instead of a binary search per query, all queries are
+/// searched together by divide and conquer over the updates: `state`
+/// starts without any update, `apply` and `rollback` move it forwards and
+/// backwards by one update, in last in first out order, so any structure
+/// with an undo works, e.g. a [`Rollback`] or a [`Fenwick`] tree undoing an
+/// addition with a subtraction.
+///
+/// Algorithmic complexity: O((u + q) log u) calls for `u` updates and `q`
+/// queries
+///
+/// [`Fenwick`]: crate::fenwick::Fenwick
+///
+/// ```
+/// use kuehree::{fenwick::Fenwick, rollback::parallel_binary_search};
+///
+/// // Day by day rainfall per station, when does each query range reach
+/// // its total?
+/// let rain = [(0, 5), (2, 1), (1, 4), (0, 2), (2, 6)];
+/// let queries = [(0, 0, 6), (0, 2, 10), (1, 1, 5)];
+/// let days = parallel_binary_search(
+///     &mut Fenwick::new([0u32; 3]),
+///     &rain,
+///     &queries,
+///     |totals, &(station, mm)| totals.add(station, mm),
+///     |totals, &(station, mm)| totals.sub(station, mm),
+///     |totals, &(start, end, target)| totals.query(start, end) >= target,
+/// );
+/// assert_eq!(days, [Some(4), Some(3), None]);
+/// ```
+pub fn parallel_binary_search<S, U, Q>(
+    state: &mut S,
+    updates: &[U],
+    queries: &[Q],
+    mut apply: impl FnMut(&mut S, &U),
+    mut rollback: impl FnMut(&mut S, &U),
+    mut check: impl FnMut(&S, &Q) -> bool,
+) -> Vec<Option<usize>> {
+    let mut answers = vec![None; queries.len()];
+    let mut search = Search {
+        state,
+        updates,
+        queries,
+        apply: &mut apply,
+        rollback: &mut rollback,
+        check: &mut check,
+        answers: &mut answers,
+    };
+    // Answers lie in `0..=updates.len()`, or are never found
+    search.solve(0, updates.len() + 2, (0..queries.len()).collect());
+    answers
+}
+
+struct Search<'a, S, U, Q, A, R, C> {
+    state: &'a mut S,
+    updates: &'a [U],
+    queries: &'a [Q],
+    apply: &'a mut A,
+    rollback: &'a mut R,
+    check: &'a mut C,
+    answers: &'a mut Vec<Option<usize>>,
+}
+
+impl<S, U, Q, A, R, C> Search<'_, S, U, Q, A, R, C>
+where
+    A: FnMut(&mut S, &U),
+    R: FnMut(&mut S, &U),
+    C: FnMut(&S, &Q) -> bool,
+{
+    /// Answer `pending`, whose answers lie in `lo..hi`, with the first
+    /// `lo` updates applied to the state
+    fn solve(&mut self, lo: usize, hi: usize, pending: Vec<usize>) {
+        if pending.is_empty() {
+            return;
+        }
+        if hi - lo == 1 {
+            for query in pending {
+                self.answers[query] = Some(lo);
+            }
+            return;
+        }
+
+        // Test whether `mid` updates are enough, `mid < hi - 1`. Queries
+        // failing with all updates keep their answer of never
+        let mid = lo + (hi - lo - 1) / 2;
+        for update in &self.updates[lo..mid] {
+            (self.apply)(self.state, update);
+        }
+        let (early, late): (Vec<usize>, Vec<usize>) = pending
+            .into_iter()
+            .partition(|&query| (self.check)(self.state, &self.queries[query]));
+
+        if let Some(update) = self.updates.get(mid) {
+            (self.apply)(self.state, update);
+            self.solve(mid + 1, hi, late);
+            (self.rollback)(self.state, update);
+        }
+        for update in self.updates[lo..mid].iter().rev() {
+            (self.rollback)(self.state, update);
+        }
+        self.solve(lo, mid + 1, early);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmq::{Max, SegmentTree, SegmentTreeFixed};
+
+    #[test]
+    fn test_rollback() {
+        let mut tree = Rollback::new(SegmentTree::<_, Max>::new([1u32, 3, 4, 8]));
+        tree.update(3, 2);
+        let checkpoint = tree.checkpoint();
+        tree.update(0, 9);
+        tree.update(0, 7);
+        assert_eq!(tree.query(0, 3), 7);
+
+        tree.rollback(1);
+        assert_eq!(tree.query(0, 3), 9);
+        tree.rollback_to(checkpoint);
+        assert_eq!(tree.query(0, 3), 4);
+        tree.rollback(1);
+        assert_eq!(tree.into_inner(), SegmentTree::new([1, 3, 4, 8]));
+
+        let mut fixed = Rollback::new(SegmentTreeFixed::<_, Max, 4>::new([1u32, 3, 4, 8]));
+        fixed.update(3, 0);
+        fixed.clear_log();
+        assert_eq!(fixed.checkpoint(), 0);
+        assert_eq!(fixed.query(2, 3), 4);
+    }
+
+    #[test]
+    fn test_parallel_binary_search() {
+        // First update after which each range maximum reaches a threshold
+        let updates: Vec<(usize, u32)> = (0..40).map(|i| (i * 7 % 10, i as u32 * 3 % 50)).collect();
+        let queries: Vec<(usize, usize, u32)> = (0..30)
+            .map(|i| (i % 10, (i % 10 + i / 3).min(9), i as u32 * 2))
+            .collect();
+        let answers = parallel_binary_search(
+            &mut Rollback::new(SegmentTree::<_, Max>::new([0u32; 10])),
+            &updates,
+            &queries,
+            |tree, &(index, value)| {
+                let value = value.max(tree.get(index));
+                tree.update(index, value);
+            },
+            |tree, _| tree.rollback(1),
+            |tree, &(start, end, threshold)| tree.query(start, end) >= threshold,
+        );
+
+        for (&(start, end, threshold), answer) in queries.iter().zip(answers) {
+            let mut tree = SegmentTree::<_, Max>::new([0u32; 10]);
+            let expected = (0..=updates.len()).find(|&count| {
+                if count > 0 {
+                    let (index, value) = updates[count - 1];
+                    tree.update(index, value.max(tree.get(index)));
+                }
+                tree.query(start, end) >= threshold
+            });
+            assert_eq!(answer, expected);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rollback_too_far() {
+        let mut tree = Rollback::new(SegmentTree::<_, Max>::new([1u32, 3]));
+        tree.update(0, 2);
+        tree.rollback(2);
+    }
+}
@@ -0,0 +1,337 @@
+//! Sequence supporting insertion and removal at any position, with range
+//! sums
+
+use core::{
+    fmt, mem,
+    ops::{Add, Sub},
+};
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{sketch::splitmix, splay::in_order, trace::BuildSpan, MemoryUsage, RangeQuery, Zero};
+
+type Link<T> = Option<Box<Node<T>>>;
+
+#[derive(Clone)]
+struct Node<T> {
+    value: T,
+    priority: u64,
+    /// Number of elements in the subtree
+    size: usize,
+    /// Sum of the subtree
+    sum: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+fn size<T>(link: &Link<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+impl<T> Node<T>
+where
+    T: Copy + Add<Output = T> + Zero,
+{
+    fn sum(link: &Link<T>) -> T {
+        link.as_ref().map_or(T::zero(), |node| node.sum)
+    }
+
+    /// Recompute the size and sum from the children
+    fn pull(&mut self) {
+        self.size = size(&self.left) + 1 + size(&self.right);
+        self.sum = Self::sum(&self.left) + self.value + Self::sum(&self.right);
+    }
+}
+
+/// Sequence where elements are inserted and removed at any position,
+/// shifting the positions after them, with range sums
+///
+/// Elements are kept in a treap ordered by position, every operation takes
+/// O(log n) expected, e.g. for event logs or editor buffers where an array
+/// backed prefix sum would need rebuilding after each insertion.
+///
+/// ```
+/// use kuehree::rope::Rope;
+///
+/// let mut log = Rope::new([10, 20, 30]);
+/// log.insert(1, 5);
+/// assert_eq!(log.range_sum(0, 1), 15);
+/// assert_eq!(log.remove(0), 10);
+/// assert_eq!(log.range_sum(1, 2), 50);
+/// ```
+#[derive(Clone)]
+pub struct Rope<T> {
+    root: Link<T>,
+    /// Seed of the next node priority
+    seed: u64,
+}
+
+impl<T> Rope<T> {
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Elements in sequence order
+    ///
+    /// Algorithmic complexity: O(n) for the whole iteration
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_
+    where
+        T: Copy,
+    {
+        in_order(self.root.as_deref(), |node| {
+            (node.left.as_deref(), node.right.as_deref())
+        })
+        .map(|node| node.value)
+    }
+}
+
+impl<T> Rope<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("Rope");
+        let mut rope = Self {
+            root: None,
+            seed: 0,
+        };
+        // Cartesian tree of the priorities, the right spine on a stack
+        let mut spine: Vec<Box<Node<T>>> = Vec::new();
+        for value in data {
+            let mut node = rope.leaf(value);
+            let mut left = None;
+            while let Some(mut top) = spine.pop_if(|top| top.priority < node.priority) {
+                top.right = left;
+                top.pull();
+                left = Some(top);
+            }
+            node.left = left;
+            spine.push(node);
+        }
+        while let Some(mut node) = spine.pop() {
+            node.right = rope.root.take();
+            node.pull();
+            rope.root = Some(node);
+        }
+        span.record_len(rope.len());
+        rope
+    }
+
+    fn leaf(&mut self, value: T) -> Box<Node<T>> {
+        self.seed += 1;
+        Box::new(Node {
+            value,
+            priority: splitmix(self.seed),
+            size: 1,
+            sum: value,
+            left: None,
+            right: None,
+        })
+    }
+
+    /// The first `count` elements and the rest
+    fn split(link: Link<T>, count: usize) -> (Link<T>, Link<T>) {
+        let Some(mut node) = link else {
+            return (None, None);
+        };
+        let left_len = size(&node.left);
+        if count <= left_len {
+            let (left, middle) = Self::split(node.left.take(), count);
+            node.left = middle;
+            node.pull();
+            (left, Some(node))
+        } else {
+            let (middle, right) = Self::split(node.right.take(), count - left_len - 1);
+            node.right = middle;
+            node.pull();
+            (Some(node), right)
+        }
+    }
+
+    /// Concatenation of `left` and `right`
+    fn merge(left: Link<T>, right: Link<T>) -> Link<T> {
+        match (left, right) {
+            (Some(mut left), Some(mut right)) => Some(if left.priority > right.priority {
+                left.right = Self::merge(left.right.take(), Some(right));
+                left.pull();
+                left
+            } else {
+                right.left = Self::merge(Some(left), right.left.take());
+                right.pull();
+                right
+            }),
+            (link, None) | (None, link) => link,
+        }
+    }
+
+    /// Insert `value` before the element at `index`, or at the end if
+    /// `index` is [`Self::len`]
+    ///
+    /// Algorithmic complexity: O(log n) expected
+    ///
+    /// # Panics
+    ///
+    /// If `index` exceeds [`Self::len`]
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.len(), "insertion index {index} out of bounds");
+        let leaf = self.leaf(value);
+        let (left, right) = Self::split(self.root.take(), index);
+        self.root = Self::merge(Self::merge(left, Some(leaf)), right);
+    }
+
+    /// Append `value` at the end
+    ///
+    /// Algorithmic complexity: O(log n) expected
+    pub fn push(&mut self, value: T) {
+        self.insert(self.len(), value);
+    }
+
+    /// Remove and return the element at `index`
+    ///
+    /// Algorithmic complexity: O(log n) expected
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len(), "index {index} out of bounds");
+        let (left, rest) = Self::split(self.root.take(), index);
+        let (removed, right) = Self::split(rest, 1);
+        self.root = Self::merge(left, right);
+        removed
+            .map(|node| node.value)
+            .unwrap_or_else(|| unreachable!())
+    }
+
+    /// Element at `index`
+    ///
+    /// Algorithmic complexity: O(log n) expected
+    pub fn get(&self, index: usize) -> T {
+        assert!(index < self.len(), "index {index} out of bounds");
+        let (mut link, mut index) = (&self.root, index);
+        while let Some(node) = link {
+            let left_len = size(&node.left);
+            if index < left_len {
+                link = &node.left;
+            } else if index > left_len {
+                index -= left_len + 1;
+                link = &node.right;
+            } else {
+                return node.value;
+            }
+        }
+        unreachable!()
+    }
+
+    /// Sum of the first `count` elements
+    ///
+    /// Algorithmic complexity: O(log n) expected
+    pub fn prefix_sum(&self, count: usize) -> T {
+        assert!(count <= self.len());
+        let (mut link, mut count, mut sum) = (&self.root, count, T::zero());
+        while let Some(node) = link {
+            let left_len = size(&node.left);
+            if count <= left_len {
+                link = &node.left;
+            } else {
+                sum = sum + Node::sum(&node.left) + node.value;
+                count -= left_len + 1;
+                link = &node.right;
+            }
+        }
+        sum
+    }
+
+    /// Sum between start range and end range
+    ///
+    /// Algorithmic complexity: O(log n) expected
+    pub fn range_sum(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len());
+        self.prefix_sum(end + 1) - self.prefix_sum(start)
+    }
+}
+
+impl<T> Default for Rope<T> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            seed: 0,
+        }
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for Rope<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> FromIterator<T> for Rope<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+impl<T> RangeQuery<T> for Rope<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.range_sum(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T> MemoryUsage for Rope<T> {
+    fn heap_bytes(&self) -> usize {
+        self.len() * mem::size_of::<Node<T>>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rope() {
+        let mut model: Vec<i64> = (0..30).map(|i| i * 13 % 7 - 3).collect();
+        let mut rope: Rope<i64> = model.iter().copied().collect();
+        assert_eq!(rope.iter().collect::<Vec<_>>(), model);
+        for step in 0..400u64 {
+            let pick = |n: usize| splitmix(step) as usize % n;
+            if step % 3 == 0 && !model.is_empty() {
+                let index = pick(model.len());
+                assert_eq!(rope.remove(index), model.remove(index));
+            } else {
+                let index = pick(model.len() + 1);
+                model.insert(index, step as i64 - 200);
+                rope.insert(index, step as i64 - 200);
+            }
+            assert_eq!(rope.len(), model.len());
+            let start = pick(model.len());
+            let end = start + pick(model.len() - start);
+            assert_eq!(
+                rope.range_sum(start, end),
+                model[start..=end].iter().sum::<i64>()
+            );
+            assert_eq!(rope.get(end), model[end]);
+        }
+        assert_eq!(rope.iter().collect::<Vec<_>>(), model);
+
+        let mut tail = Rope::default();
+        for value in 0..100_000u64 {
+            tail.push(value);
+        }
+        assert_eq!(tail.query(0, 99_999), 4_999_950_000);
+    }
+}
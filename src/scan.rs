@@ -0,0 +1,387 @@
+//! Inclusive prefix scan engine behind both builds
+//!
+//! Every scan runs the [`Running`] kernel, sequential builds push their
+//! elements through it directly. Large arrays are split in one chunk per
+//! thread: the chunk totals are computed on their own, scanned with the
+//! work efficient up-sweep and down-sweep of Blelloch (in its inclusive
+//! Brent-Kung form) and each chunk is then scanned on top of the total
+//! of the chunks before it. Every step that touches the data runs on
+//! rayon, and the fallbacks without it exist so the tests run in every
+//! configuration.
+//!
+//! The chunk totals and the sweeps add sums no real prefix contains, so
+//! when they overflow the array, still untouched, is scanned sequentially
+//! instead. Once they fit, the final pass only computes real prefixes and
+//! its overflows are genuine.
+//!
+//! The sequential kernels have clones compiled for AVX2 and AVX-512,
+//! picked at runtime through [`crate::cpu`].
+
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+use alloc::vec::Vec;
+
+#[cfg(all(
+    feature = "alloc",
+    any(feature = "rayon", test),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+use crate::cpu::{simd_level, SimdLevel};
+
+/// Arrays shorter than this are scanned or built sequentially, splitting
+/// them costs more than it saves
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+pub(crate) const PARALLEL_THRESHOLD: usize = 1 << 14;
+
+/// Fallible addition used by the scans, `None` on overflow
+pub(crate) trait AddFn<T>: Fn(T, T) -> Option<T> + Sync {}
+
+impl<T, F: Fn(T, T) -> Option<T> + Sync> AddFn<T> for F {}
+
+/// Running total of the values pushed so far, the sequential kernel of
+/// every scan
+pub(crate) struct Running<T> {
+    total: Option<T>,
+    overflow: bool,
+}
+
+impl<T: Copy> Running<T> {
+    /// Running total on top of `start`, or starting at the first value
+    pub(crate) fn new(start: Option<T>) -> Self {
+        Self {
+            total: start,
+            overflow: false,
+        }
+    }
+
+    /// Add `value` to the total and return the new total
+    ///
+    /// After an overflow the total stays put and [`Self::overflowed`] is
+    /// set.
+    #[inline(always)]
+    pub(crate) fn push(&mut self, value: T, add: &impl AddFn<T>) -> T {
+        let total = match self.total {
+            Some(total) => add(total, value).unwrap_or_else(|| {
+                self.overflow = true;
+                total
+            }),
+            None => value,
+        };
+        self.total = Some(total);
+        total
+    }
+
+    /// Whether any [`Self::push`] overflowed
+    pub(crate) fn overflowed(&self) -> bool {
+        self.overflow
+    }
+}
+
+/// Run `f` over consecutive chunks of `size` elements, in parallel when
+/// possible, stopping at the first `None`
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+fn try_for_chunks<T: Send>(
+    data: &mut [T],
+    size: usize,
+    f: impl Fn(usize, &mut [T]) -> Option<()> + Sync + Send,
+) -> Option<()> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        data.par_chunks_mut(size)
+            .enumerate()
+            .try_for_each(|(idx, chunk)| f(idx, chunk))
+    }
+    #[cfg(not(feature = "rayon"))]
+    data.chunks_mut(size)
+        .enumerate()
+        .try_for_each(|(idx, chunk)| f(idx, chunk))
+}
+
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+#[inline(always)]
+fn total_kernel<T: Copy>(data: &[T], add: &impl AddFn<T>) -> Option<T> {
+    let (&first, rest) = data.split_first()?;
+    rest.iter().try_fold(first, |total, &d| add(total, d))
+}
+
+#[cfg(all(
+    feature = "alloc",
+    any(feature = "rayon", test),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[target_feature(enable = "avx2")]
+fn total_avx2<T: Copy>(data: &[T], add: &impl AddFn<T>) -> Option<T> {
+    total_kernel(data, add)
+}
+
+#[cfg(all(
+    feature = "alloc",
+    any(feature = "rayon", test),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[target_feature(enable = "avx512f")]
+fn total_avx512<T: Copy>(data: &[T], add: &impl AddFn<T>) -> Option<T> {
+    total_kernel(data, add)
+}
+
+/// Total of `data`, `None` if it is empty or overflows
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+fn total<T: Copy>(data: &[T], add: &impl AddFn<T>) -> Option<T> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[allow(unsafe_code)]
+    match simd_level() {
+        // SAFETY: the CPU supports AVX-512
+        SimdLevel::Avx512 => return unsafe { total_avx512(data, add) },
+        // SAFETY: the CPU supports AVX2
+        SimdLevel::Avx2 => return unsafe { total_avx2(data, add) },
+        _ => {}
+    }
+    total_kernel(data, add)
+}
+
+/// Total of every chunk of `size` elements, `None` if one overflows
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+fn chunk_totals<T>(data: &[T], size: usize, add: &impl AddFn<T>) -> Option<Vec<T>>
+where
+    T: Copy + Send + Sync,
+{
+    let total = |chunk: &[T]| total(chunk, add);
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        data.par_chunks(size).map(total).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    data.chunks(size).map(total).collect()
+}
+
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+#[inline(always)]
+fn sequential_kernel<T: Copy>(data: &mut [T], start: Option<T>, add: &impl AddFn<T>) -> Option<()> {
+    let mut running = Running::new(start);
+    for d in data {
+        *d = running.push(*d, add);
+        if running.overflowed() {
+            return None;
+        }
+    }
+    Some(())
+}
+
+#[cfg(all(
+    feature = "alloc",
+    any(feature = "rayon", test),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[target_feature(enable = "avx2")]
+fn sequential_avx2<T: Copy>(data: &mut [T], start: Option<T>, add: &impl AddFn<T>) -> Option<()> {
+    sequential_kernel(data, start, add)
+}
+
+#[cfg(all(
+    feature = "alloc",
+    any(feature = "rayon", test),
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+#[target_feature(enable = "avx512f")]
+fn sequential_avx512<T: Copy>(data: &mut [T], start: Option<T>, add: &impl AddFn<T>) -> Option<()> {
+    sequential_kernel(data, start, add)
+}
+
+/// Scan `data` in place on top of `start`, stopping at the first overflow
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+fn sequential<T: Copy>(data: &mut [T], start: Option<T>, add: &impl AddFn<T>) -> Option<()> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[allow(unsafe_code)]
+    match simd_level() {
+        // SAFETY: the CPU supports AVX-512
+        SimdLevel::Avx512 => return unsafe { sequential_avx512(data, start, add) },
+        // SAFETY: the CPU supports AVX2
+        SimdLevel::Avx2 => return unsafe { sequential_avx2(data, start, add) },
+        _ => {}
+    }
+    sequential_kernel(data, start, add)
+}
+
+/// Folds the last element of every full `2 * stride` chunk with the
+/// element `stride` before it, one level of either sweep
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+fn sweep_level<T>(data: &mut [T], stride: usize, add: &impl AddFn<T>) -> Option<()>
+where
+    T: Copy + Send,
+{
+    try_for_chunks(data, 2 * stride, |_, chunk| {
+        if chunk.len() == 2 * stride {
+            chunk[2 * stride - 1] = add(chunk[stride - 1], chunk[2 * stride - 1])?;
+        }
+        Some(())
+    })
+}
+
+/// Work efficient inclusive scan, O(n) additions over O(log n) levels
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+pub(crate) fn blelloch<T>(data: &mut [T], add: &impl AddFn<T>) -> Option<()>
+where
+    T: Copy + Send,
+{
+    // Up-sweep, element `i` ends up holding the total of the largest
+    // aligned block ending at it
+    let mut stride = 1;
+    while 2 * stride <= data.len() {
+        sweep_level(data, stride, add)?;
+        stride *= 2;
+    }
+    // Down-sweep, completing the elements between those blocks
+    while stride > 1 {
+        stride /= 2;
+        sweep_level(&mut data[stride..], stride, add)?;
+    }
+    Some(())
+}
+
+/// Scan in chunks of `size`, see the module documentation
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+fn chunked<T>(data: &mut [T], size: usize, add: &impl AddFn<T>) -> Option<()>
+where
+    T: Copy + Send + Sync,
+{
+    let offsets = chunk_totals(data, size, add).and_then(|mut offsets| {
+        blelloch(&mut offsets, add)?;
+        Some(offsets)
+    });
+    let Some(offsets) = offsets else {
+        // A sum spanning chunks overflowed, the real prefixes may still fit
+        return sequential(data, None, add);
+    };
+    try_for_chunks(data, size, |idx, chunk| {
+        sequential(chunk, idx.checked_sub(1).map(|idx| offsets[idx]), add)
+    })
+}
+
+/// Replace every element with the total of the elements up to it, `None`
+/// if `add` overflows
+///
+/// Picks a sequential scan for small arrays and the chunked one otherwise.
+#[cfg(all(feature = "alloc", any(feature = "rayon", test)))]
+pub(crate) fn inclusive_scan<T>(data: &mut [T], add: &impl AddFn<T>) -> Option<()>
+where
+    T: Copy + Send + Sync,
+{
+    #[cfg(feature = "rayon")]
+    let threads = rayon::current_num_threads();
+    #[cfg(not(feature = "rayon"))]
+    let threads = 1;
+
+    if data.len() < PARALLEL_THRESHOLD || threads == 1 {
+        sequential(data, None, add)
+    } else {
+        chunked(data, data.len().div_ceil(threads), add)
+    }
+}
+
+#[cfg(all(feature = "alloc", test))]
+mod test {
+    use super::*;
+
+    fn expected(data: &[u64]) -> Vec<u64> {
+        data.iter()
+            .scan(0, |total, &d| {
+                *total += d;
+                Some(*total)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_blelloch() {
+        let add = |a: u64, b: u64| a.checked_add(b);
+        for len in 0..70 {
+            let data: Vec<u64> = (0..len).map(|i| i * 7 % 13).collect();
+            let mut scanned = data.clone();
+            blelloch(&mut scanned, &add).unwrap();
+            assert_eq!(scanned, expected(&data), "length {len}");
+
+            for size in [1, 3, 8, 64] {
+                let mut scanned = data.clone();
+                chunked(&mut scanned, size, &add).unwrap();
+                assert_eq!(scanned, expected(&data), "length {len} chunks {size}");
+            }
+        }
+
+        let data: Vec<u64> = (0..100_000).collect();
+        let mut scanned = data.clone();
+        inclusive_scan(&mut scanned, &add).unwrap();
+        assert_eq!(scanned, expected(&data));
+        let mut scalar = data.clone();
+        sequential_kernel(&mut scalar, None, &add).unwrap();
+        assert_eq!(scalar, expected(&data));
+        assert_eq!(total_kernel(&data, &add), scalar.last().copied());
+
+        let add = |a: u8, b: u8| a.checked_add(b);
+        assert_eq!(blelloch(&mut [100, 100, 100], &add), None);
+        assert_eq!(total(&[100, 100, 100], &add), None);
+        assert_eq!(chunked(&mut [100, 100, 100], 2, &add), None);
+    }
+    #[test]
+    fn test_spanning_overflow() {
+        // The last chunk totals 2^63 although every prefix fits
+        let mut data = vec![0i64; 1 << 16];
+        data[0] = -i64::MAX;
+        data[(1 << 16) - 2] = 1 << 62;
+        data[(1 << 16) - 1] = 1 << 62;
+        let add = |a: i64, b: i64| a.checked_add(b);
+
+        let mut expected = data.clone();
+        sequential(&mut expected, None, &add).unwrap();
+        let mut scanned = data;
+        chunked(&mut scanned, 1 << 14, &add).unwrap();
+        assert_eq!(scanned, expected);
+        assert_eq!(scanned[(1 << 16) - 1], 1);
+    }
+
+    #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+    #[test]
+    #[allow(unsafe_code)]
+    fn test_avx2() {
+        if !std::is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let add = |a: u64, b: u64| a.checked_add(b);
+        let data: Vec<u64> = (0..1000).map(|i| i * 7 % 13).collect();
+        let mut scanned = data.clone();
+        // SAFETY: the CPU supports AVX2
+        unsafe { sequential_avx2(&mut scanned, Some(5), &add) }.unwrap();
+        let mut kernel = data.clone();
+        sequential_kernel(&mut kernel, Some(5), &add).unwrap();
+        assert_eq!(scanned, kernel);
+        // SAFETY: as above
+        let total = unsafe { total_avx2(&data, &add) };
+        assert_eq!(total, total_kernel(&data, &add));
+        // SAFETY: as above
+        let overflow = unsafe { total_avx2(&[u64::MAX, 1], &add) };
+        assert_eq!(overflow, None);
+    }
+
+    #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+    #[test]
+    #[allow(unsafe_code)]
+    fn test_avx512() {
+        if !std::is_x86_feature_detected!("avx512f") {
+            return;
+        }
+        let add = |a: u64, b: u64| a.checked_add(b);
+        let data: Vec<u64> = (0..1000).map(|i| i * 7 % 13).collect();
+        let mut scanned = data.clone();
+        // SAFETY: the CPU supports AVX-512
+        unsafe { sequential_avx512(&mut scanned, Some(5), &add) }.unwrap();
+        let mut kernel = data.clone();
+        sequential_kernel(&mut kernel, Some(5), &add).unwrap();
+        assert_eq!(scanned, kernel);
+        // SAFETY: as above
+        let total = unsafe { total_avx512(&data, &add) };
+        assert_eq!(total, total_kernel(&data, &add));
+        // SAFETY: as above
+        let overflow = unsafe { total_avx512(&[u64::MAX, 1], &add) };
+        assert_eq!(overflow, None);
+    }
+}
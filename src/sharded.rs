@@ -0,0 +1,192 @@
+//! Segment tree split in independently locked shards, for many threads
+//! updating and querying at once
+
+use std::sync::{PoisonError, RwLock};
+
+use crate::{
+    rmq::{Operation, SegmentTree},
+    trace::BuildSpan,
+    MemoryUsage, RangeQuery, UpdatableRangeQuery,
+};
+
+/// Segment tree over consecutive shards, each behind its own [`RwLock`]
+///
+/// Updates take `&self` and only lock the shard they change, so threads
+/// updating different shards never wait on each other, and queries only
+/// take read locks. A query spanning several shards reads them one after
+/// the other, so concurrent updates to different shards may be seen
+/// partially; every element read is a value it held at some point.
+#[derive(Debug)]
+pub struct ShardedSegmentTree<T, M> {
+    shard_len: usize,
+    len: usize,
+    shards: Vec<RwLock<SegmentTree<T, M>>>,
+}
+
+impl<T, M> ShardedSegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    /// Construct `Self` split in at most `shards` shards
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Panics
+    ///
+    /// If `shards` is 0
+    pub fn new(data: impl IntoIterator<Item = T>, shards: usize) -> Self {
+        assert!(shards > 0, "needs at least one shard");
+        let span = BuildSpan::enter("ShardedSegmentTree");
+        let data: Vec<T> = data.into_iter().collect();
+        span.record_len(data.len());
+        let shard_len = data.len().div_ceil(shards).max(1);
+
+        Self {
+            shard_len,
+            len: data.len(),
+            shards: data
+                .chunks(shard_len)
+                .map(|chunk| RwLock::new(SegmentTree::new(chunk.iter().copied())))
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of shards
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Fold of `start..=end` within shard `shard`, under its read lock
+    fn query_shard(&self, shard: usize, start: usize, end: usize) -> T {
+        let tree = self.shards[shard]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        tree.query(start, end)
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(shards + log n)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len);
+        let (first, last) = (start / self.shard_len, end / self.shard_len);
+        let (start, end) = (start % self.shard_len, end % self.shard_len);
+        if first == last {
+            return self.query_shard(first, start, end);
+        }
+
+        let mut acc = self.query_shard(first, start, self.shard_len - 1);
+        for shard in first + 1..last {
+            acc = M::combine(acc, self.query_shard(shard, 0, self.shard_len - 1));
+        }
+        M::combine(acc, self.query_shard(last, 0, end))
+    }
+
+    /// Current element at `index`
+    pub fn get(&self, index: usize) -> T {
+        self.query(index, index)
+    }
+
+    /// Replace the element at `index` with `value`, locking only its shard
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn update(&self, index: usize, value: T) {
+        assert!(index < self.len);
+        let mut tree = self.shards[index / self.shard_len]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        tree.update(index % self.shard_len, value);
+    }
+}
+
+impl<T, M> RangeQuery<T> for ShardedSegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T, M> UpdatableRangeQuery<T> for ShardedSegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        ShardedSegmentTree::update(self, index, value);
+    }
+}
+
+impl<T, M> MemoryUsage for ShardedSegmentTree<T, M> {
+    fn heap_bytes(&self) -> usize {
+        self.shards.heap_bytes()
+            + self
+                .shards
+                .iter()
+                .map(|shard| {
+                    shard
+                        .read()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .heap_bytes()
+                })
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmq::{Max, Sum};
+
+    #[test]
+    fn test_sharded() {
+        let arr = [1u32, 3, 4, 8, 6, 1, 4, 2, 9, 0];
+        let tree = ShardedSegmentTree::<_, Max>::new(arr, 3);
+        assert_eq!(tree.shards(), 3);
+        for start in 0..arr.len() {
+            for end in start..arr.len() {
+                assert_eq!(
+                    tree.query(start, end),
+                    arr[start..=end].iter().copied().max().unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_concurrent_updates() {
+        let tree = ShardedSegmentTree::<u64, Sum>::new(vec![0; 1000], 8);
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let tree = &tree;
+                scope.spawn(move || {
+                    for index in (thread..1000).step_by(4) {
+                        tree.update(index, index as u64);
+                        tree.query(0, 999);
+                    }
+                });
+            }
+        });
+        assert_eq!(tree.query(0, 999), 999 * 1000 / 2);
+        assert_eq!(tree.get(123), 123);
+    }
+}
@@ -0,0 +1,411 @@
+//! Fixed memory sketches answering approximate range queries over streams
+
+use alloc::{vec, vec::Vec};
+
+use crate::{trace::BuildSpan, MemoryUsage};
+
+/// Mersenne prime the row hashes are computed modulo
+const PRIME: u64 = (1 << 61) - 1;
+
+/// Splitmix64, deriving the row hash parameters from their position
+pub(crate) fn splitmix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Count-Min sketches over a dyadic decomposition of the items `0..2^bits`
+///
+/// Level `l` counts the items divided by `2^l`, so a range of items is
+/// covered by at most two nodes per level. Estimates never undercount, and
+/// with probability `1 - e^-depth` every node overcounts by at most
+/// `e / width` times the total count, making a range overcount at most
+/// `2 * bits` times that.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CountMinRange {
+    bits: u32,
+    width: usize,
+    /// `(a, b)` of the hash `(a * x + b) mod p mod width` of every row
+    rows: Vec<(u64, u64)>,
+    /// Counters of every level, row and column, in that order
+    counters: Vec<u64>,
+    total: u64,
+}
+
+impl CountMinRange {
+    /// Sketch of items below `2^bits`, with `depth` rows of `width`
+    /// counters per level
+    ///
+    /// # Panics
+    ///
+    /// If `bits` exceeds 63 or `width` or `depth` is 0
+    pub fn new(bits: u32, width: usize, depth: usize) -> Self {
+        assert!(bits < 64, "items have to fit in 63 bits");
+        assert!(width > 0 && depth > 0, "sketch can't be empty");
+        let rows = (0..depth as u64)
+            .map(|row| {
+                let a = splitmix(2 * row) % (PRIME - 1) + 1;
+                (a, splitmix(2 * row + 1) % PRIME)
+            })
+            .collect();
+
+        Self {
+            bits,
+            width,
+            rows,
+            counters: vec![0; bits as usize * depth * width],
+            total: 0,
+        }
+    }
+
+    /// Sum of every count added so far
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    fn counter(&self, level: u32, row: usize, node: u64) -> usize {
+        let (a, b) = self.rows[row];
+        let hash = ((u128::from(a) * u128::from(node) + u128::from(b)) % u128::from(PRIME)) as u64;
+        (level as usize * self.rows.len() + row) * self.width + (hash % self.width as u64) as usize
+    }
+
+    /// Add `count` occurrences of `item`
+    ///
+    /// Algorithmic complexity: O(bits * depth)
+    pub fn add(&mut self, item: u64, count: u64) {
+        assert!(item >> self.bits == 0, "item {item} out of range");
+        self.total += count;
+        for level in 0..self.bits {
+            for row in 0..self.rows.len() {
+                let idx = self.counter(level, row, item >> level);
+                self.counters[idx] += count;
+            }
+        }
+    }
+
+    fn estimate(&self, level: u32, node: u64) -> u64 {
+        if level == self.bits {
+            return self.total;
+        }
+        (0..self.rows.len())
+            .map(|row| self.counters[self.counter(level, row, node)])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Estimated count of `item`
+    pub fn count(&self, item: u64) -> u64 {
+        self.estimate(0, item)
+    }
+
+    /// Estimated count of the items between start range and end range
+    ///
+    /// Algorithmic complexity: O(bits * depth)
+    pub fn range_count(&self, start: u64, end: u64) -> u64 {
+        assert!(end >= start && end >> self.bits == 0);
+        let (mut lo, end) = (start, end + 1);
+        let mut sum = 0;
+        while lo < end {
+            // Largest aligned dyadic node starting at `lo` inside the range
+            let mut level = lo.trailing_zeros().min(self.bits);
+            while lo + (1 << level) > end {
+                level -= 1;
+            }
+            sum += self.estimate(level, lo >> level);
+            lo += 1 << level;
+        }
+        sum
+    }
+}
+
+impl MemoryUsage for CountMinRange {
+    fn heap_bytes(&self) -> usize {
+        self.rows.heap_bytes() + self.counters.heap_bytes()
+    }
+}
+
+/// t-digest, a summary of a distribution answering approximate quantiles
+///
+/// Values are grouped in centroids, small near the extremes and large in
+/// the middle, so that tail quantiles such as p99 stay accurate. The
+/// number of centroids is bounded by about `compression`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TDigest {
+    compression: f64,
+    /// `(mean, weight)` of every centroid, sorted by mean
+    centroids: Vec<(f64, f64)>,
+    count: f64,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    /// Empty digest
+    pub fn new(compression: f64) -> Self {
+        Self {
+            compression,
+            centroids: Vec::new(),
+            count: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Digest of `values`
+    ///
+    /// Algorithmic complexity: O(n log n)
+    pub fn from_values(values: impl IntoIterator<Item = f64>, compression: f64) -> Self {
+        let mut digest = Self::new(compression);
+        digest.centroids = values.into_iter().map(|value| (value, 1.0)).collect();
+        digest.compress();
+        digest
+    }
+
+    /// Number of values summarised
+    pub fn count(&self) -> f64 {
+        self.count
+    }
+
+    /// Digest of the values of both `self` and `other`
+    pub fn merge(&self, other: &Self) -> Self {
+        let mut digest = Self::new(self.compression.max(other.compression));
+        digest.centroids = self.centroids.clone();
+        digest.centroids.extend_from_slice(&other.centroids);
+        // The centroids of both have already been averaged, keep the exact
+        // extremes
+        digest.min = self.min.min(other.min);
+        digest.max = self.max.max(other.max);
+        digest.compress();
+        digest
+    }
+
+    /// Sort the centroids and merge neighbours while they stay below the
+    /// size limit `4 * count * q * (1 - q) / compression` at quantile `q`
+    fn compress(&mut self) {
+        self.centroids.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.count = self.centroids.iter().map(|&(_, weight)| weight).sum();
+        let (Some(&(first, _)), Some(&(last, _))) = (self.centroids.first(), self.centroids.last())
+        else {
+            return;
+        };
+        self.min = self.min.min(first);
+        self.max = self.max.max(last);
+
+        let count = self.count;
+        let limit = |q: f64| 4.0 * count * q * (1.0 - q) / self.compression;
+        let mut merged: Vec<(f64, f64)> = Vec::with_capacity(self.centroids.len());
+        let mut before = 0.0;
+        for &(mean, weight) in &self.centroids {
+            if let Some(current) = merged.last_mut() {
+                let proposed = current.1 + weight;
+                let (q0, q1) = (before / count, (before + proposed) / count);
+                if proposed <= limit(q0).min(limit(q1)) {
+                    current.0 += (mean - current.0) * weight / proposed;
+                    current.1 = proposed;
+                    continue;
+                }
+                before += current.1;
+            }
+            merged.push((mean, weight));
+        }
+        self.centroids = merged;
+    }
+
+    /// Approximate value below which a `q` fraction of the values lie,
+    /// `NaN` for an empty digest
+    pub fn quantile(&self, q: f64) -> f64 {
+        if self.centroids.is_empty() {
+            return f64::NAN;
+        }
+        let target = q.clamp(0.0, 1.0) * self.count;
+        // Each centroid sits at the middle of the ranks it covers,
+        // interpolate between neighbouring centres and the extremes
+        let (mut rank, mut value) = (0.0, self.min);
+        let mut before = 0.0;
+        for &(mean, weight) in &self.centroids {
+            let centre = before + weight / 2.0;
+            if target < centre {
+                return value + (mean - value) * (target - rank) / (centre - rank);
+            }
+            (rank, value) = (centre, mean);
+            before += weight;
+        }
+        if self.count > rank {
+            value + (self.max - value) * (target - rank) / (self.count - rank)
+        } else {
+            value
+        }
+    }
+}
+
+impl MemoryUsage for TDigest {
+    fn heap_bytes(&self) -> usize {
+        self.centroids.heap_bytes()
+    }
+}
+
+/// Approximate quantiles of any range, from t-digests of blocks of the data
+///
+/// The digests form a segment tree over the blocks, a query merges the
+/// O(log n) digests covering the whole blocks of the range with the values
+/// of the at most two blocks it covers partially.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeQuantiles {
+    block: usize,
+    compression: f64,
+    values: Vec<f64>,
+    /// Digest of node `i` summarises its children `2i` and `2i + 1`, the
+    /// leaves in the second half each summarise a block
+    tree: Vec<TDigest>,
+}
+
+impl RangeQuantiles {
+    /// Construct `Self` with digests of `compression` over blocks of
+    /// `block` elements
+    ///
+    /// Algorithmic complexity: O(n log n)
+    ///
+    /// # Panics
+    ///
+    /// If `block` is 0
+    pub fn new<T: Into<f64>>(
+        data: impl IntoIterator<Item = T>,
+        block: usize,
+        compression: f64,
+    ) -> Self {
+        assert!(block > 0, "blocks can't be empty");
+        let span = BuildSpan::enter("RangeQuantiles");
+        let values: Vec<f64> = data.into_iter().map(Into::into).collect();
+        span.record_len(values.len());
+
+        let blocks = values.len() / block;
+        let mut tree = vec![TDigest::new(compression); blocks];
+        tree.extend(
+            values
+                .chunks_exact(block)
+                .map(|chunk| TDigest::from_values(chunk.iter().copied(), compression)),
+        );
+        for idx in (1..blocks).rev() {
+            tree[idx] = tree[2 * idx].merge(&tree[2 * idx + 1]);
+        }
+
+        Self {
+            block,
+            compression,
+            values,
+            tree,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Digest of the values between start range and end range
+    ///
+    /// Algorithmic complexity: O(compression * log n + block)
+    pub fn digest(&self, start: usize, end: usize) -> TDigest {
+        assert!(end >= start && end < self.len());
+        let compression = self.compression;
+        let (first, last) = (start.div_ceil(self.block), (end + 1) / self.block);
+        if first >= last {
+            return TDigest::from_values(self.values[start..=end].iter().copied(), compression);
+        }
+
+        let edges = self.values[start..first * self.block]
+            .iter()
+            .chain(&self.values[last * self.block..=end]);
+        let mut digest = TDigest::from_values(edges.copied(), compression);
+        let blocks = self.tree.len() / 2;
+        let (mut l, mut r) = (first + blocks, last + blocks);
+        while l < r {
+            if l & 1 == 1 {
+                digest = digest.merge(&self.tree[l]);
+                l += 1;
+            }
+            if r & 1 == 1 {
+                r -= 1;
+                digest = digest.merge(&self.tree[r]);
+            }
+            l >>= 1;
+            r >>= 1;
+        }
+        digest
+    }
+
+    /// Approximate `q` quantile of the values between start range and end
+    /// range
+    pub fn quantile(&self, start: usize, end: usize, q: f64) -> f64 {
+        self.digest(start, end).quantile(q)
+    }
+}
+
+impl MemoryUsage for RangeQuantiles {
+    fn heap_bytes(&self) -> usize {
+        self.values.heap_bytes()
+            + self.tree.heap_bytes()
+            + self.tree.iter().map(MemoryUsage::heap_bytes).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_range_count() {
+        let mut sketch = CountMinRange::new(16, 256, 4);
+        let mut exact = vec![0u64; 1 << 16];
+        for idx in 0..5000u64 {
+            let item = splitmix(idx) % 1000 * 37;
+            sketch.add(item, idx % 3 + 1);
+            exact[item as usize] += idx % 3 + 1;
+        }
+        assert_eq!(sketch.total(), exact.iter().sum::<u64>());
+        assert_eq!(sketch.range_count(0, (1 << 16) - 1), sketch.total());
+
+        let slack = 2 * 16 * sketch.total() * 3 / 256;
+        for (start, end) in [
+            (0, 0),
+            (37, 37),
+            (100, 20_000),
+            (12_345, 65_535),
+            (1, 65_534),
+        ] {
+            let expected: u64 = exact[start as usize..=end as usize].iter().sum();
+            let estimate = sketch.range_count(start, end);
+            assert!(estimate >= expected && estimate <= expected + slack);
+        }
+        assert!(sketch.count(37) >= exact[37]);
+    }
+
+    #[test]
+    fn test_range_quantiles() {
+        let data: Vec<u32> = (0..10_000u32).map(|idx| idx * 7919 % 10_000).collect();
+        let quantiles = RangeQuantiles::new(data.iter().copied(), 64, 100.0);
+
+        for (start, end) in [(0, 9999), (1234, 8765), (10, 100), (5000, 5000)] {
+            let mut sorted = data[start..=end].to_vec();
+            sorted.sort_unstable();
+            for q in [0.0, 0.5, 0.95, 0.99, 1.0] {
+                let estimate = quantiles.quantile(start, end, q);
+                let rank = sorted.partition_point(|&value| f64::from(value) <= estimate);
+                let error = (rank as f64 / sorted.len() as f64 - q).abs();
+                assert!(error <= 0.01 + 1.0 / sorted.len() as f64);
+            }
+        }
+        assert_eq!(quantiles.digest(0, 9999).count(), 10_000.0);
+        assert!(TDigest::new(100.0).quantile(0.5).is_nan());
+
+        let low = TDigest::from_values((0..1_000).map(f64::from), 10.0);
+        let high = TDigest::from_values((1_000..2_000).map(f64::from), 10.0);
+        let both = low.merge(&high).merge(&TDigest::new(10.0));
+        assert_eq!(both.quantile(0.0), 0.0);
+        assert_eq!(both.quantile(1.0), 1_999.0);
+    }
+}
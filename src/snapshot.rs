@@ -0,0 +1,319 @@
+//! Copy-on-write snapshots, so readers can keep a consistent view while
+//! the writer keeps updating
+//!
+//! [`SegmentTree`](crate::rmq::SegmentTree) and
+//! [`Fenwick`](crate::fenwick::Fenwick) share their node array with their
+//! snapshots, and the first update after a snapshot copies it.
+//! [`CowSegmentTree`] shares its nodes one by one, so an update only
+//! copies the path to the updated leaf.
+
+use core::{marker::PhantomData, ops::Deref};
+
+use alloc::vec::Vec;
+
+use crate::{persistent::mid, rmq::Operation, trace::BuildSpan, RangeQuery, UpdatableRangeQuery};
+
+/// Reference counted storage shared between a structure and its
+/// snapshots, atomic where the target has atomics
+#[cfg(target_has_atomic = "ptr")]
+pub(crate) type Shared<T> = alloc::sync::Arc<T>;
+
+/// Reference counted storage shared between a structure and its
+/// snapshots, atomic where the target has atomics
+#[cfg(not(target_has_atomic = "ptr"))]
+pub(crate) type Shared<T> = alloc::rc::Rc<T>;
+
+/// Frozen view of an updatable structure, unaffected by later updates
+///
+/// Dereferences to the structure as it was when the snapshot was taken,
+/// which can be read but not updated.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Snapshot<S> {
+    inner: S,
+}
+
+impl<S> Snapshot<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> Deref for Snapshot<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<T, S: RangeQuery<T>> RangeQuery<T> for Snapshot<S> {
+    fn query(&self, start: usize, end: usize) -> T {
+        self.inner.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Node<T> {
+    Leaf(T),
+    Branch {
+        value: T,
+        left: Shared<Node<T>>,
+        right: Shared<Node<T>>,
+    },
+}
+
+impl<T: Copy> Node<T> {
+    fn value(&self) -> T {
+        match self {
+            Self::Leaf(value) | Self::Branch { value, .. } => *value,
+        }
+    }
+}
+
+fn build<T, M>(leaves: &[T]) -> Shared<Node<T>>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    if let [value] = leaves {
+        return Shared::new(Node::Leaf(*value));
+    }
+    let (left, right) = leaves.split_at(leaves.len().div_ceil(2));
+    let (left, right) = (build::<T, M>(left), build::<T, M>(right));
+    Shared::new(Node::Branch {
+        value: M::combine(left.value(), right.value()),
+        left,
+        right,
+    })
+}
+
+fn fold<T, M>(node: &Node<T>, lo: usize, hi: usize, start: usize, end: usize) -> T
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    let Node::Branch { value, left, right } = node else {
+        return node.value();
+    };
+    if start == lo && end == hi {
+        return *value;
+    }
+    let mid = mid(lo, hi);
+    if end <= mid {
+        fold::<T, M>(left, lo, mid, start, end)
+    } else if start > mid {
+        fold::<T, M>(right, mid + 1, hi, start, end)
+    } else {
+        M::combine(
+            fold::<T, M>(left, lo, mid, start, mid),
+            fold::<T, M>(right, mid + 1, hi, mid + 1, end),
+        )
+    }
+}
+
+/// Replaces a leaf, copying only the nodes still shared with a snapshot
+fn set<T, M>(node: &mut Shared<Node<T>>, lo: usize, hi: usize, index: usize, value: T)
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    match Shared::make_mut(node) {
+        Node::Leaf(leaf) => *leaf = value,
+        Node::Branch {
+            value: combined,
+            left,
+            right,
+        } => {
+            let mid = mid(lo, hi);
+            if index <= mid {
+                set::<T, M>(left, lo, mid, index, value);
+            } else {
+                set::<T, M>(right, mid + 1, hi, index, value);
+            }
+            *combined = M::combine(left.value(), right.value());
+        }
+    }
+}
+
+/// Segment tree whose nodes are reference counted, see [`Self::snapshot`]
+///
+/// Unlike the snapshots of [`SegmentTree`](crate::rmq::SegmentTree), which
+/// copy the whole node array on the next update, only the O(log n) nodes
+/// on the path to the updated leaf are copied.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CowSegmentTree<T, M> {
+    root: Shared<Node<T>>,
+    len: usize,
+    _phantom: PhantomData<M>,
+}
+
+impl<T, M> CowSegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Panics
+    ///
+    /// If `data` is empty
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("CowSegmentTree");
+        let leaves: Vec<T> = data.into_iter().collect();
+        span.record_len(leaves.len());
+        assert!(
+            !leaves.is_empty(),
+            "segment tree needs at least one element"
+        );
+
+        Self {
+            root: build::<T, M>(&leaves),
+            len: leaves.len(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Number of elements in the tree
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Always `false`, the tree is never empty
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len);
+        fold::<T, M>(&self.root, 0, self.len - 1, start, end)
+    }
+
+    /// Current element at `index`
+    pub fn get(&self, index: usize) -> T {
+        self.query(index, index)
+    }
+
+    /// Replace the element at `index` with `value`
+    ///
+    /// Algorithmic complexity: O(log n), nodes shared with a snapshot are
+    /// copied instead of changed
+    pub fn update(&mut self, index: usize, value: T) {
+        assert!(index < self.len);
+        set::<T, M>(&mut self.root, 0, self.len - 1, index, value);
+    }
+
+    /// Frozen view of the current elements
+    ///
+    /// Algorithmic complexity: O(1), the snapshot shares every node with
+    /// the tree until an update replaces it
+    pub fn snapshot(&self) -> Snapshot<Self> {
+        Snapshot::new(Self {
+            root: Shared::clone(&self.root),
+            len: self.len,
+            _phantom: PhantomData,
+        })
+    }
+}
+
+impl<T, M> RangeQuery<T> for CowSegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    fn query(&self, start: usize, end: usize) -> T {
+        self.query(start, end)
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<T, M> UpdatableRangeQuery<T> for CowSegmentTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    fn get(&self, index: usize) -> T {
+        self.get(index)
+    }
+
+    fn update(&mut self, index: usize, value: T) {
+        self.update(index, value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        fenwick::Fenwick,
+        rmq::{Max, SegmentTree},
+    };
+
+    #[test]
+    fn test_snapshot() {
+        let arr = [1u32, 3, 4, 8, 6, 1, 4];
+        let mut tree = CowSegmentTree::<_, Max>::new(arr);
+        let before = tree.snapshot();
+        tree.update(3, 2);
+        let after = tree.snapshot();
+        tree.update(0, 9);
+
+        assert_eq!(before.query(0, 6), 8);
+        assert_eq!(after.query(0, 6), 6);
+        assert_eq!(tree.query(0, 6), 9);
+        assert_eq!(after.get(0), 1);
+
+        let reference = SegmentTree::<_, Max>::new([9, 3, 4, 2, 6, 1, 4]);
+        for start in 0..arr.len() {
+            for end in start..arr.len() {
+                assert_eq!(tree.query(start, end), reference.query(start, end));
+            }
+        }
+    }
+
+    #[test]
+    fn test_shared() {
+        let mut tree = CowSegmentTree::<_, Max>::new([1u32, 3, 4, 8]);
+        let snapshot = tree.snapshot();
+        tree.update(0, 5);
+        // Only the path to the updated leaf is copied
+        let (Node::Branch { right, .. }, Node::Branch { right: old, .. }) =
+            (&*tree.root, &*snapshot.root)
+        else {
+            unreachable!()
+        };
+        assert!(Shared::ptr_eq(right, old));
+    }
+
+    #[test]
+    fn test_array_snapshot() {
+        let mut tree = SegmentTree::<_, Max>::new([1u32, 3, 4, 8]);
+        let before = tree.snapshot();
+        assert!(core::ptr::eq(tree.data(), before.data()));
+        tree.update(3, 2);
+        assert_eq!(before.query(0, 3), 8);
+        assert_eq!(RangeQuery::query(&before, 2, 3), 8);
+        assert_eq!(tree.query(0, 3), 4);
+        assert_eq!(before.data(), [1, 3, 4, 8]);
+
+        let mut sums = Fenwick::new([1i64, 3, 4, 8]);
+        let before = sums.snapshot();
+        sums.update(3, 2);
+        sums.add(0, 10);
+        assert_eq!(before.query(0, 3), 16);
+        assert_eq!(before.get(0), 1);
+        assert_eq!(sums.query(0, 3), 20);
+        assert_eq!(before.len(), 4);
+    }
+}
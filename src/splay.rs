@@ -0,0 +1,405 @@
+//! Splay tree sequence, splitting and concatenating in amortized O(log n)
+
+use core::{fmt, marker::PhantomData, mem};
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::{rmq::Operation, trace::BuildSpan, MemoryUsage};
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    value: T,
+    /// Number of elements in the subtree
+    size: usize,
+    /// Fold of the subtree in sequence order
+    aggregate: T,
+    left: Link<T>,
+    right: Link<T>,
+}
+
+fn size<T>(link: &Link<T>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+/// Nodes of the binary tree under `root` in order, `children` giving the
+/// left and right child of a node
+///
+/// Shared with the treap of [`Rope`](crate::rope::Rope).
+pub(crate) fn in_order<'a, N>(
+    root: Option<&'a N>,
+    children: impl Fn(&'a N) -> (Option<&'a N>, Option<&'a N>),
+) -> impl Iterator<Item = &'a N> {
+    let mut stack = Vec::new();
+    let mut link = root;
+    core::iter::from_fn(move || {
+        while let Some(node) = link {
+            stack.push(node);
+            link = children(node).0;
+        }
+        let node = stack.pop()?;
+        link = children(node).1;
+        Some(node)
+    })
+}
+
+impl<T: Copy> Node<T> {
+    /// Recompute the size and fold from the children
+    fn pull<M: Operation<T>>(&mut self) {
+        self.size = size(&self.left) + 1 + size(&self.right);
+        self.aggregate = self.value;
+        if let Some(left) = &self.left {
+            self.aggregate = M::combine(left.aggregate, self.aggregate);
+        }
+        if let Some(right) = &self.right {
+            self.aggregate = M::combine(self.aggregate, right.aggregate);
+        }
+    }
+}
+
+/// Sequence of elements stored as a splay tree, folding ranges under `M`
+///
+/// Every access moves the accessed element to the root, so all operations
+/// take amortized O(log n) and elements accessed often or close to the
+/// previous access are found faster. Splitting at a position and
+/// concatenating two sequences are as cheap as an access, which no array
+/// backed structure of the crate offers.
+///
+/// Reads reshape the tree, hence take `&mut self`. For shared reads and
+/// worst case bounds use a [`SegmentTree`](crate::rmq::SegmentTree), for
+/// positions shifting under shared reads a [`Rope`](crate::rope::Rope).
+///
+/// ```
+/// use kuehree::{rmq::Min, splay::SplayTree};
+///
+/// let mut tree = SplayTree::<_, Min>::new([5, 2, 8, 6, 1]);
+/// let mut tail = tree.split_off(3);
+/// assert_eq!(tree.query(0, 2), 2);
+/// tail.append(&mut tree);
+/// assert_eq!(tail.iter().collect::<Vec<_>>(), [6, 1, 5, 2, 8]);
+/// assert_eq!(tail.query(2, 4), 2);
+/// ```
+pub struct SplayTree<T, M> {
+    root: Link<T>,
+    _phantom: PhantomData<M>,
+}
+
+impl<T, M> SplayTree<T, M> {
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Elements in sequence order
+    ///
+    /// Algorithmic complexity: O(n) for the whole iteration
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_
+    where
+        T: Copy,
+    {
+        in_order(self.root.as_deref(), |node| {
+            (node.left.as_deref(), node.right.as_deref())
+        })
+        .map(|node| node.value)
+    }
+}
+
+impl<T, M> SplayTree<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    /// Construct `Self`, balanced
+    ///
+    /// Algorithmic complexity: O(n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("SplayTree");
+        let values: Vec<T> = data.into_iter().collect();
+        span.record_len(values.len());
+        Self {
+            root: Self::build(&values),
+            _phantom: PhantomData,
+        }
+    }
+
+    fn build(values: &[T]) -> Link<T> {
+        if values.is_empty() {
+            return None;
+        }
+        let mid = values.len() / 2;
+        let mut node = Box::new(Node {
+            value: values[mid],
+            size: 1,
+            aggregate: values[mid],
+            left: Self::build(&values[..mid]),
+            right: Self::build(&values[mid + 1..]),
+        });
+        node.pull::<M>();
+        Some(node)
+    }
+
+    /// Move the element at `index` to the root, top-down
+    fn splay(mut node: Box<Node<T>>, mut index: usize) -> Box<Node<T>> {
+        // Nodes waiting for their right, resp. left, child, top to bottom
+        let (mut lesser, mut greater) = (vec![], vec![]);
+        loop {
+            let left_len = size(&node.left);
+            if index < left_len {
+                let mut child = node.left.take().unwrap_or_else(|| unreachable!());
+                if index < size(&child.left) {
+                    // Zig-zig, rotate right before linking
+                    node.left = child.right.take();
+                    node.pull::<M>();
+                    child.right = Some(node);
+                    node = child;
+                    child = node.left.take().unwrap_or_else(|| unreachable!());
+                }
+                greater.push(node);
+                node = child;
+            } else if index > left_len {
+                index -= left_len + 1;
+                let mut child = node.right.take().unwrap_or_else(|| unreachable!());
+                let child_left_len = size(&child.left);
+                if index > child_left_len {
+                    // Zig-zig, rotate left before linking
+                    index -= child_left_len + 1;
+                    node.right = child.left.take();
+                    node.pull::<M>();
+                    child.left = Some(node);
+                    node = child;
+                    child = node.right.take().unwrap_or_else(|| unreachable!());
+                }
+                lesser.push(node);
+                node = child;
+            } else {
+                break;
+            }
+        }
+
+        let mut left = node.left.take();
+        for mut parent in lesser.into_iter().rev() {
+            parent.right = left;
+            parent.pull::<M>();
+            left = Some(parent);
+        }
+        let mut right = node.right.take();
+        for mut parent in greater.into_iter().rev() {
+            parent.left = right;
+            parent.pull::<M>();
+            right = Some(parent);
+        }
+        node.left = left;
+        node.right = right;
+        node.pull::<M>();
+        node
+    }
+
+    /// Root after splaying the element at `index`
+    fn root_at(&mut self, index: usize) -> &mut Node<T> {
+        assert!(index < self.len(), "index {index} out of bounds");
+        let root = self.root.take().unwrap_or_else(|| unreachable!());
+        self.root.insert(Self::splay(root, index))
+    }
+
+    /// Element at `index`
+    ///
+    /// Algorithmic complexity: amortized O(log n)
+    pub fn get(&mut self, index: usize) -> T {
+        self.root_at(index).value
+    }
+
+    /// Replace the element at `index` with `value`
+    ///
+    /// Algorithmic complexity: amortized O(log n)
+    pub fn update(&mut self, index: usize, value: T) {
+        let root = self.root_at(index);
+        root.value = value;
+        root.pull::<M>();
+    }
+
+    /// Query between start range and end range
+    ///
+    /// Algorithmic complexity: amortized O(log n)
+    pub fn query(&mut self, start: usize, end: usize) -> T {
+        assert!(end >= start && end < self.len());
+        let mut tail = self.split_off(end + 1);
+        let mut middle = self.split_off(start);
+        let value = middle.root.as_ref().map(|root| root.aggregate);
+        middle.append(&mut tail);
+        self.append(&mut middle);
+        value.unwrap_or_else(|| unreachable!())
+    }
+
+    /// Split off the elements from `at` onwards, `self` keeps the first
+    /// `at`
+    ///
+    /// Algorithmic complexity: amortized O(log n)
+    ///
+    /// # Panics
+    ///
+    /// If `at` exceeds [`Self::len`]
+    #[must_use]
+    pub fn split_off(&mut self, at: usize) -> Self {
+        assert!(at <= self.len(), "split position {at} out of bounds");
+        let tail = if at == self.len() {
+            None
+        } else {
+            self.root_at(at);
+            let mut tail = self.root.take().unwrap_or_else(|| unreachable!());
+            self.root = tail.left.take();
+            tail.pull::<M>();
+            Some(tail)
+        };
+        Self {
+            root: tail,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Move all elements of `other` to the end of `self`
+    ///
+    /// Algorithmic complexity: amortized O(log n)
+    pub fn append(&mut self, other: &mut Self) {
+        let Some(tail) = other.root.take() else {
+            return;
+        };
+        if self.root.is_none() {
+            self.root = Some(tail);
+            return;
+        }
+        let root = self.root_at(self.len() - 1);
+        root.right = Some(tail);
+        root.pull::<M>();
+    }
+
+    /// Insert `value` before the element at `index`
+    ///
+    /// Algorithmic complexity: amortized O(log n)
+    pub fn insert(&mut self, index: usize, value: T) {
+        let mut tail = self.split_off(index);
+        self.append(&mut Self::new([value]));
+        self.append(&mut tail);
+    }
+
+    /// Remove and return the element at `index`
+    ///
+    /// Algorithmic complexity: amortized O(log n)
+    pub fn remove(&mut self, index: usize) -> T {
+        let value = self.root_at(index).value;
+        let mut root = self.root.take().unwrap_or_else(|| unreachable!());
+        self.root = root.left.take();
+        self.append(&mut Self {
+            root: root.right.take(),
+            _phantom: PhantomData,
+        });
+        value
+    }
+}
+
+/// Dismantles the tree iteratively, a splay tree can degenerate into a
+/// path too deep to drop recursively
+impl<T, M> Drop for SplayTree<T, M> {
+    fn drop(&mut self) {
+        let mut stack: Vec<_> = self.root.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+        }
+    }
+}
+
+impl<T, M> Default for SplayTree<T, M> {
+    fn default() -> Self {
+        Self {
+            root: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Rebuilds a balanced copy
+impl<T: Copy, M: Operation<T>> Clone for SplayTree<T, M> {
+    fn clone(&self) -> Self {
+        Self::new(self.iter())
+    }
+}
+
+impl<T: Copy + fmt::Debug, M> fmt::Debug for SplayTree<T, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Copy, M: Operation<T>> FromIterator<T> for SplayTree<T, M> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+impl<T, M> MemoryUsage for SplayTree<T, M> {
+    fn heap_bytes(&self) -> usize {
+        self.len() * mem::size_of::<Node<T>>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmq::{Min, Sum};
+
+    #[test]
+    fn test_splay() {
+        let mut model: Vec<u64> = (0..40).map(|i| i * 37 % 23).collect();
+        let mut sums = SplayTree::<_, Sum>::new(model.iter().copied());
+        let mut mins: SplayTree<_, Min> = model.iter().copied().collect();
+        for step in 0..300u64 {
+            let pick = |n: usize| crate::sketch::splitmix(step) as usize % n;
+            match step % 4 {
+                0 => {
+                    let index = pick(model.len() + 1);
+                    model.insert(index, step);
+                    sums.insert(index, step);
+                    mins.insert(index, step);
+                }
+                1 if model.len() > 1 => {
+                    let index = pick(model.len());
+                    assert_eq!(sums.remove(index), model.remove(index));
+                    mins.remove(index);
+                }
+                2 => {
+                    let index = pick(model.len());
+                    model[index] = step % 17;
+                    sums.update(index, step % 17);
+                    mins.update(index, step % 17);
+                }
+                _ => {
+                    let at = pick(model.len() + 1);
+                    let (mut sums_tail, mut mins_tail) = (sums.split_off(at), mins.split_off(at));
+                    assert_eq!(sums.len(), at);
+                    model.rotate_left(at);
+                    sums_tail.append(&mut sums);
+                    mins_tail.append(&mut mins);
+                    (sums, mins) = (sums_tail, mins_tail);
+                }
+            }
+            assert_eq!(sums.iter().collect::<Vec<_>>(), model);
+            let start = pick(model.len());
+            let end = start + pick(model.len() - start);
+            let range = &model[start..=end];
+            assert_eq!(sums.query(start, end), range.iter().sum::<u64>());
+            assert_eq!(Some(mins.query(start, end)), range.iter().copied().min());
+            assert_eq!(mins.get(end), model[end]);
+        }
+
+        // Sequential access leaves a path, which must still drop
+        let mut path = SplayTree::<u64, Sum>::new(0..1_000_000);
+        for index in 0..path.len() {
+            path.get(index);
+        }
+        assert_eq!(path.clone().query(0, 999_999), 499_999_500_000);
+    }
+}
@@ -1,135 +1,1111 @@
-use std::{
-    num::NonZeroUsize,
-    ops::{Add, Index, Sub},
+use core::{
+    fmt::{self, Write},
+    marker::PhantomData,
+    ops::{Add, Range, Sub},
 };
 
-use num::Zero;
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
+use alloc::{borrow::Cow, boxed::Box, vec, vec::Vec};
 
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct SumQuery<T: IntoIterator> {
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+use crate::cpu::{simd_level, SimdLevel};
+use crate::{trace::BuildSpan, Error, MemoryUsage, RangeQuery, Zero};
+
+/// Prefix sum array answering range sum queries in O(1)
+///
+/// `T` is the backing container of the prefix sums: `Vec<T>`, `Box<[T]>`
+/// or `[T; N]` for a fixed size table that needs no allocator. Any type
+/// implementing [`PrefixStorage`] works.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct SumQuery<T> {
     prefix_sum_array: T,
 }
 
-/// This trait provides methods required for `SumQuery` types
-pub trait IndexableSumQuery<T>
+/// Old name of the `Vec` backed [`SumQuery`]
+#[cfg(feature = "alloc")]
+#[deprecated(note = "use `SumQuery<Vec<T>>` instead")]
+pub type SumQueryVec<T> = SumQuery<Vec<T>>;
+
+/// Old name of the boxed slice backed [`SumQuery`]
+#[cfg(feature = "alloc")]
+#[deprecated(note = "use `SumQuery<Box<[T]>>` instead")]
+pub type SumQuerySlice<T> = SumQuery<Box<[T]>>;
+
+/// Old name of the array backed [`SumQuery`]
+#[deprecated(note = "use `SumQuery<[T; N]>` instead")]
+pub type SumQueryFixed<T, const N: usize> = SumQuery<[T; N]>;
+
+/// Container holding the prefix sums of a [`SumQuery`]
+///
+/// The scan and the query are written once against this trait, so a new
+/// backend only has to say how to store and read back the running totals.
+pub trait PrefixStorage<T> {
+    /// Whether the stored totals start with a leading zero, see
+    /// [`IndexableSumQuery::prefix_sum_array`]
+    const LEADING_ZERO: bool;
+
+    /// Iterator over the stored totals
+    type Iter<'a>: Iterator<Item = T>
+    where
+        Self: 'a;
+
+    /// Collect running totals, already including the leading zero if the
+    /// storage has one
+    fn from_totals(totals: impl Iterator<Item = T>) -> Self;
+
+    /// Like [`Self::from_totals`], without panicking on the number of totals
+    ///
+    /// # Errors
+    ///
+    /// [`Error::LengthMismatch`] where [`Self::from_totals`] would panic
+    fn try_from_totals(totals: impl Iterator<Item = T>) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        Ok(Self::from_totals(totals))
+    }
+
+    /// Number of stored totals
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Stored total at `index`, panics if out of bounds
+    fn total(&self, index: usize) -> T;
+
+    fn totals(&self) -> Self::Iter<'_>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy> PrefixStorage<T> for Vec<T> {
+    const LEADING_ZERO: bool = true;
+
+    type Iter<'a>
+        = core::iter::Copied<core::slice::Iter<'a, T>>
+    where
+        T: 'a;
+
+    fn from_totals(totals: impl Iterator<Item = T>) -> Self {
+        totals.collect()
+    }
+
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    fn total(&self, index: usize) -> T {
+        self[index]
+    }
+
+    fn totals(&self) -> Self::Iter<'_> {
+        self.iter().copied()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy> PrefixStorage<T> for Box<[T]> {
+    const LEADING_ZERO: bool = true;
+
+    type Iter<'a>
+        = core::iter::Copied<core::slice::Iter<'a, T>>
+    where
+        T: 'a;
+
+    fn from_totals(totals: impl Iterator<Item = T>) -> Self {
+        totals.collect()
+    }
+
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    fn total(&self, index: usize) -> T {
+        self[index]
+    }
+
+    fn totals(&self) -> Self::Iter<'_> {
+        self.iter().copied()
+    }
+}
+
+/// Cloning is O(1), so one table can be handed to many threads
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<T: Copy> PrefixStorage<T> for Arc<[T]> {
+    const LEADING_ZERO: bool = true;
+
+    type Iter<'a>
+        = core::iter::Copied<core::slice::Iter<'a, T>>
+    where
+        T: 'a;
+
+    fn from_totals(totals: impl Iterator<Item = T>) -> Self {
+        totals.collect()
+    }
+
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    fn total(&self, index: usize) -> T {
+        self[index]
+    }
+
+    fn totals(&self) -> Self::Iter<'_> {
+        self.iter().copied()
+    }
+}
+
+/// Borrowed prefix sums are used as is, freshly computed ones are owned
+#[cfg(feature = "alloc")]
+impl<T: Copy> PrefixStorage<T> for Cow<'_, [T]> {
+    const LEADING_ZERO: bool = true;
+
+    type Iter<'a>
+        = core::iter::Copied<core::slice::Iter<'a, T>>
+    where
+        Self: 'a;
+
+    fn from_totals(totals: impl Iterator<Item = T>) -> Self {
+        Cow::Owned(totals.collect())
+    }
+
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    fn total(&self, index: usize) -> T {
+        self[index]
+    }
+
+    fn totals(&self) -> Self::Iter<'_> {
+        self.iter().copied()
+    }
+}
+
+/// Arrays can't grow by one element on stable Rust, so they store no
+/// leading zero and queries starting at 0 take a branch
+impl<T: Copy + Zero, const N: usize> PrefixStorage<T> for [T; N] {
+    const LEADING_ZERO: bool = false;
+
+    type Iter<'a>
+        = core::iter::Copied<core::slice::Iter<'a, T>>
+    where
+        T: 'a;
+
+    /// # Panics
+    ///
+    /// Unless there are exactly `N` totals
+    fn from_totals(totals: impl Iterator<Item = T>) -> Self {
+        Self::try_from_totals(totals).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn try_from_totals(totals: impl Iterator<Item = T>) -> Result<Self, Error> {
+        let mut prefix_sum_array = [T::zero(); N];
+        let mut found = 0;
+        for total in totals {
+            if let Some(slot) = prefix_sum_array.get_mut(found) {
+                *slot = total;
+            }
+            found += 1;
+        }
+        if found == N {
+            Ok(prefix_sum_array)
+        } else {
+            Err(Error::LengthMismatch { expected: N, found })
+        }
+    }
+
+    fn len(&self) -> usize {
+        N
+    }
+
+    fn total(&self, index: usize) -> T {
+        self[index]
+    }
+
+    fn totals(&self) -> Self::Iter<'_> {
+        self.iter().copied()
+    }
+}
+
+/// This trait provides methods required for `SumQuery` types
+///
+/// `query` used to be a method of this trait and now comes from
+/// [`RangeQuery`], shared by every structure of the crate. Import it
+/// alongside, or bring both in with `use kuehree::prelude::*`.
+pub trait IndexableSumQuery<T>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T>,
+{
+    type PrefixSumContainer: PrefixStorage<T>;
+
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Panics
+    ///
+    /// For a fixed size container, unless `data` holds exactly `N`
+    /// elements
+    fn new(data: impl IntoIterator<Item = T>) -> Self;
+
+    /// Running totals of the data
+    ///
+    /// Heap backed containers start with a leading zero, so index `i` holds
+    /// the sum of the first `i` elements. For `[T; N]` index `i` holds the
+    /// sum of the first `i + 1` elements.
+    fn prefix_sum_array(&self) -> &Self::PrefixSumContainer;
+}
+
+impl<T, C> IndexableSumQuery<T> for SumQuery<C>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+    C: PrefixStorage<T>,
+{
+    type PrefixSumContainer = C;
+
+    fn new(data: impl IntoIterator<Item = T>) -> Self {
+        Self::try_collect(data).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn prefix_sum_array(&self) -> &Self::PrefixSumContainer {
+        &self.prefix_sum_array
+    }
+}
+
+impl<C> SumQuery<C> {
+    /// [`IndexableSumQuery::new`] failing with [`Error::LengthMismatch`]
+    /// when `C` can't hold as many totals as `data` has elements
+    pub(crate) fn try_collect<T>(data: impl IntoIterator<Item = T>) -> Result<Self, Error>
+    where
+        T: Copy + Add<Output = T> + Zero,
+        C: PrefixStorage<T>,
+    {
+        let span = BuildSpan::enter("SumQuery");
+        let leading_zero = C::LEADING_ZERO.then(T::zero);
+        let mut total = T::zero();
+        let totals = data.into_iter().map(|d| {
+            total = total + d;
+            total
+        });
+
+        let prefix_sum_array = C::try_from_totals(leading_zero.into_iter().chain(totals))?;
+        span.record_len(prefix_sum_array.len() - usize::from(C::LEADING_ZERO));
+        Ok(Self { prefix_sum_array })
+    }
+}
+
+impl<C: MemoryUsage> MemoryUsage for SumQuery<C> {
+    fn heap_bytes(&self) -> usize {
+        self.prefix_sum_array.heap_bytes()
+    }
+}
+
+impl<T, C> RangeQuery<T> for SumQuery<C>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+    C: PrefixStorage<T>,
+{
+    /// Query between start range and end range
+    ///
+    /// Negative querying is not implemented, hence end has to be greater
+    /// or equal to start
+    fn query(&self, start: usize, end: usize) -> T {
+        assert!(end >= start);
+        let prefix_sum_array = &self.prefix_sum_array;
+
+        // The leading zero makes this a single subtraction, the check is
+        // resolved at compile time
+        if C::LEADING_ZERO {
+            prefix_sum_array.total(end + 1) - prefix_sum_array.total(start)
+        } else if start == 0 {
+            prefix_sum_array.total(end)
+        } else {
+            prefix_sum_array.total(end) - prefix_sum_array.total(start - 1)
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.prefix_sum_array.len() - usize::from(C::LEADING_ZERO)
+    }
+}
+
+#[inline(always)]
+fn query_many_kernel<T, C>(sum: &SumQuery<C>, ranges: &[(usize, usize)], out: &mut [T])
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+    C: PrefixStorage<T>,
+{
+    for (slot, &(start, end)) in out.iter_mut().zip(ranges) {
+        *slot = sum.query(start, end);
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+fn query_many_avx2<T, C>(sum: &SumQuery<C>, ranges: &[(usize, usize)], out: &mut [T])
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+    C: PrefixStorage<T>,
+{
+    query_many_kernel(sum, ranges, out);
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx512f")]
+fn query_many_avx512<T, C>(sum: &SumQuery<C>, ranges: &[(usize, usize)], out: &mut [T])
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+    C: PrefixStorage<T>,
+{
+    query_many_kernel(sum, ranges, out);
+}
+
+impl<C> SumQuery<C> {
+    /// Query every range between start range and end range of `ranges`,
+    /// writing the sums to `out` in the same order
+    ///
+    /// The loop is compiled for AVX2 and AVX-512 as well, picked at runtime
+    /// by [`crate::cpu`].
+    ///
+    /// Algorithmic complexity: O(q)
+    ///
+    /// # Panics
+    ///
+    /// If `ranges` and `out` differ in length, or any range is invalid
+    pub fn query_many<T>(&self, ranges: &[(usize, usize)], out: &mut [T])
+    where
+        T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+        C: PrefixStorage<T>,
+    {
+        assert_eq!(ranges.len(), out.len(), "one output per range");
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        #[allow(unsafe_code)]
+        match simd_level() {
+            // SAFETY: the CPU supports AVX-512
+            SimdLevel::Avx512 => return unsafe { query_many_avx512(self, ranges, out) },
+            // SAFETY: the CPU supports AVX2
+            SimdLevel::Avx2 => return unsafe { query_many_avx2(self, ranges, out) },
+            _ => {}
+        }
+        query_many_kernel(self, ranges, out);
+    }
+}
+
+/// Counts the characters written to it
+struct Width(usize);
+
+impl Write for Width {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.chars().count();
+        Ok(())
+    }
+}
+
+fn text_width(value: &impl fmt::Display) -> usize {
+    let mut width = Width(0);
+    let _ = write!(width, "{value}");
+    width.0
+}
+
+/// Writes one labelled row, right aligning each cell to `width(idx)`
+fn fmt_row<D: fmt::Display>(
+    f: &mut fmt::Formatter<'_>,
+    label: &str,
+    len: usize,
+    width: &impl Fn(usize) -> usize,
+    cell: impl Fn(usize) -> D,
+) -> fmt::Result {
+    write!(f, "{label:<6} |")?;
+    for idx in 0..len {
+        write!(f, " {:>width$}", cell(idx), width = width(idx))?;
+    }
+    Ok(())
+}
+
+/// Writes the index, data and prefix sum rows aligned in columns
+fn fmt_table<T, C>(sum: &SumQuery<C>, f: &mut fmt::Formatter<'_>) -> fmt::Result
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero + fmt::Display,
+    C: PrefixStorage<T>,
+{
+    let len = sum.len();
+    let width = |idx: usize| {
+        let data = text_width(&sum.query(idx, idx));
+        let prefix = text_width(&sum.query(0, idx));
+        text_width(&idx).max(data).max(prefix)
+    };
+
+    fmt_row(f, "index", len, &width, |idx| idx)?;
+    writeln!(f)?;
+    fmt_row(f, "data", len, &width, |idx| sum.query(idx, idx))?;
+    writeln!(f)?;
+    fmt_row(f, "prefix", len, &width, |idx| sum.query(0, idx))
+}
+
+/// Renders the data and its running totals aligned in columns
+#[cfg(feature = "alloc")]
+impl<T> fmt::Display for SumQuery<Vec<T>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_table(self, f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> fmt::Display for SumQuery<Box<[T]>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_table(self, f)
+    }
+}
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<T> fmt::Display for SumQuery<Arc<[T]>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_table(self, f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> fmt::Display for SumQuery<Cow<'_, [T]>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_table(self, f)
+    }
+}
+
+impl<T, const N: usize> fmt::Display for SumQuery<[T; N]>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero + fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_table(self, f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, T2> From<T2> for SumQuery<Vec<T>>
+where
+    T2: AsRef<[T]>,
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn from(data: T2) -> Self {
+        Self::new(data.as_ref().iter().copied())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, T2> From<T2> for SumQuery<Box<[T]>>
+where
+    T2: AsRef<[T]>,
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn from(data: T2) -> Self {
+        Self::new(data.as_ref().iter().copied())
+    }
+}
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<T, T2> From<T2> for SumQuery<Arc<[T]>>
+where
+    T2: AsRef<[T]>,
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn from(data: T2) -> Self {
+        Self::new(data.as_ref().iter().copied())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T, T2> From<T2> for SumQuery<Cow<'_, [T]>>
+where
+    T2: AsRef<[T]>,
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn from(data: T2) -> Self {
+        Self::new(data.as_ref().iter().copied())
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for SumQuery<[T; N]>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn from(data: [T; N]) -> Self {
+        Self::new(data)
+    }
+}
+
+impl<T, const N: usize> From<&[T; N]> for SumQuery<[T; N]>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn from(data: &[T; N]) -> Self {
+        Self::new(data.iter().copied())
+    }
+}
+
+macro_rules! impl_const_query {
+    ($($ty:ty),*) => {
+        $(
+            impl<const N: usize> SumQuery<[$ty; N]> {
+                /// Construct `Self` in a const context, so the table of a
+                /// `const` or `static` is computed at compile time
+                ///
+                /// Algorithmic complexity: O(n)
+                pub const fn from_array(data: [$ty; N]) -> Self {
+                    let mut prefix_sum_array = data;
+                    let mut idx = 1;
+                    while idx < N {
+                        prefix_sum_array[idx] += prefix_sum_array[idx - 1];
+                        idx += 1;
+                    }
+                    Self { prefix_sum_array }
+                }
+
+                /// Query between start range and end range, also in a const
+                /// context
+                ///
+                /// Same as [`RangeQuery::query`]. A `const` inherent `query`
+                /// would make the calls on tables of untyped integer
+                /// literals ambiguous.
+                ///
+                /// Algorithmic complexity: O(1)
+                pub const fn range_sum(&self, start: usize, end: usize) -> $ty {
+                    assert!(end >= start);
+                    let prefix_sum_array = &self.prefix_sum_array;
+                    if start == 0 {
+                        prefix_sum_array[end]
+                    } else {
+                        prefix_sum_array[end] - prefix_sum_array[start - 1]
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_const_query!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+/// Fails with [`Error::LengthMismatch`] unless the slice holds exactly `N`
+/// elements
+impl<T, const N: usize> TryFrom<&[T]> for SumQuery<[T; N]>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    type Error = Error;
+
+    fn try_from(data: &[T]) -> Result<Self, Self::Error> {
+        if data.len() == N {
+            Ok(Self::new(data.iter().copied()))
+        } else {
+            Err(Error::LengthMismatch {
+                expected: N,
+                found: data.len(),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> FromIterator<T> for SumQuery<Vec<T>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> FromIterator<T> for SumQuery<Box<[T]>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+/// Owning iterator over the elements of a [`SumQuery`]
+///
+/// Created by its [`IntoIterator`] implementations
+#[derive(Debug, Clone)]
+pub struct IntoIter<T, C> {
+    sum: SumQuery<C>,
+    range: Range<usize>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T, C> IntoIter<T, C>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+    C: PrefixStorage<T>,
+{
+    fn new(sum: SumQuery<C>) -> Self {
+        Self {
+            range: 0..sum.len(),
+            sum,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, C> Iterator for IntoIter<T, C>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+    C: PrefixStorage<T>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.range.next().map(|idx| self.sum.query(idx, idx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<T, C> DoubleEndedIterator for IntoIter<T, C>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+    C: PrefixStorage<T>,
+{
+    fn next_back(&mut self) -> Option<T> {
+        self.range.next_back().map(|idx| self.sum.query(idx, idx))
+    }
+}
+
+impl<T, C> ExactSizeIterator for IntoIter<T, C>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+    C: PrefixStorage<T>,
+{
+}
+
+#[cfg(feature = "alloc")]
+impl<T> IntoIterator for SumQuery<Vec<T>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    type Item = T;
+    type IntoIter = IntoIter<T, Vec<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> IntoIterator for SumQuery<Box<[T]>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    type Item = T;
+    type IntoIter = IntoIter<T, Box<[T]>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self)
+    }
+}
+
+impl<T, const N: usize> IntoIterator for SumQuery<[T; N]>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    type Item = T;
+    type IntoIter = IntoIter<T, [T; N]>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self)
+    }
+}
+
+/// Table over no elements
+#[cfg(feature = "alloc")]
+impl<T> Default for SumQuery<Vec<T>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn default() -> Self {
+        Self::new([])
+    }
+}
+
+/// Table over no elements
+#[cfg(feature = "alloc")]
+impl<T> Default for SumQuery<Box<[T]>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn default() -> Self {
+        Self::new([])
+    }
+}
+
+/// Table over `N` zeros
+impl<T, const N: usize> Default for SumQuery<[T; N]>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    fn default() -> Self {
+        Self {
+            prefix_sum_array: [T::zero(); N],
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> SumQuery<Vec<T>>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    /// Empty table with room for `capacity` elements before reallocating
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut prefix_sum_array = Vec::with_capacity(capacity + 1);
+        prefix_sum_array.push(T::zero());
+        Self { prefix_sum_array }
+    }
+
+    /// Number of elements the table can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        self.prefix_sum_array.capacity() - 1
+    }
+
+    /// Reserve room for at least `additional` more elements
+    pub fn reserve(&mut self, additional: usize) {
+        self.prefix_sum_array.reserve(additional);
+    }
+
+    /// Release unused capacity, e.g. after bulk loading
+    pub fn shrink_to_fit(&mut self) {
+        self.prefix_sum_array.shrink_to_fit();
+    }
+
+    /// Append one element
+    ///
+    /// Algorithmic complexity: O(1) amortized
+    pub fn push(&mut self, value: T) {
+        let total = self.prefix_sum_array[self.prefix_sum_array.len() - 1];
+        self.prefix_sum_array.push(total + value);
+    }
+}
+
+/// Appends elements after the existing ones
+///
+/// Algorithmic complexity: O(k) for k new elements
+#[cfg(feature = "alloc")]
+impl<T> Extend<T> for SumQuery<Vec<T>>
 where
-    T: Copy + Sub<Output = T> + Add<Output = T>,
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
 {
-    type PrefixSumContainer: IntoIterator<Item = T> + Index<usize, Output = T>;
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let prefix_sum_array = &mut self.prefix_sum_array;
+        let mut total = prefix_sum_array.last().copied().unwrap_or_else(T::zero);
+        prefix_sum_array.extend(iter.into_iter().map(|d| {
+            total = total + d;
+            total
+        }));
+    }
+}
 
-    /// Construct `Self`
-    ///
-    /// Algorithmic complexity: O(n)
-    fn new(data: impl IntoIterator<Item = T>) -> Self;
+/// Reuses the allocation
+#[cfg(feature = "alloc")]
+impl<T> From<SumQuery<Vec<T>>> for SumQuery<Box<[T]>> {
+    fn from(sum: SumQuery<Vec<T>>) -> Self {
+        Self {
+            prefix_sum_array: sum.prefix_sum_array.into_boxed_slice(),
+        }
+    }
+}
 
-    fn prefix_sum_array(&self) -> &Self::PrefixSumContainer;
+/// Reuses the allocation
+#[cfg(feature = "alloc")]
+impl<T> From<SumQuery<Box<[T]>>> for SumQuery<Vec<T>> {
+    fn from(sum: SumQuery<Box<[T]>>) -> Self {
+        Self {
+            prefix_sum_array: sum.prefix_sum_array.into_vec(),
+        }
+    }
+}
 
-    /// Query between start range and end range
-    ///
-    /// Negative querying is not implemented, hence end has to be greater
-    /// or equal to start
-    fn query(&self, start: usize, end: usize) -> T {
-        assert!(end >= start);
-        let prefix_sum_array = self.prefix_sum_array();
+#[cfg(feature = "alloc")]
+impl<T: Copy + Zero, const N: usize> From<SumQuery<[T; N]>> for SumQuery<Vec<T>> {
+    fn from(sum: SumQuery<[T; N]>) -> Self {
+        sum.to_storage()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Copy + Zero, const N: usize> From<SumQuery<[T; N]>> for SumQuery<Box<[T]>> {
+    fn from(sum: SumQuery<[T; N]>) -> Self {
+        sum.to_storage()
+    }
+}
+
+/// Fails with [`Error::LengthMismatch`] unless the table holds exactly `N`
+/// elements
+#[cfg(feature = "alloc")]
+impl<T: Copy + Zero, const N: usize> TryFrom<SumQuery<Vec<T>>> for SumQuery<[T; N]> {
+    type Error = Error;
+
+    fn try_from(sum: SumQuery<Vec<T>>) -> Result<Self, Self::Error> {
+        SumQuery::<Box<[T]>>::from(sum).try_into()
+    }
+}
+
+/// Fails with [`Error::LengthMismatch`] unless the table holds exactly `N`
+/// elements
+#[cfg(feature = "alloc")]
+impl<T: Copy + Zero, const N: usize> TryFrom<SumQuery<Box<[T]>>> for SumQuery<[T; N]> {
+    type Error = Error;
 
-        if start == 0 {
-            prefix_sum_array[end]
+    fn try_from(sum: SumQuery<Box<[T]>>) -> Result<Self, Self::Error> {
+        let found = sum.prefix_sum_array.len() - 1;
+        if found == N {
+            Ok(sum.to_storage())
         } else {
-            prefix_sum_array[end] - prefix_sum_array[start - 1]
+            Err(Error::LengthMismatch { expected: N, found })
         }
     }
+}
 
-    /// Query between start range and end rage
+impl<T, const N: usize> SumQuery<[T; N]>
+where
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+{
+    /// Query between `S` and `E`, both inclusive, with the range checked
+    /// at compile time
     ///
-    /// This function elides the branch for when `start` == 0
-    fn non_zero_query(&self, start: NonZeroUsize, end: NonZeroUsize) -> T {
-        assert!(end >= start);
-        let prefix_sum_array = self.prefix_sum_array();
-        prefix_sum_array[end.get()] - prefix_sum_array[(start.get()) - 1]
+    /// A range with `S > E` or `E >= N` fails the build instead of
+    /// panicking. The check runs when the call is compiled to code, so
+    /// `cargo check` alone does not report it.
+    ///
+    /// Algorithmic complexity: O(1)
+    ///
+    /// ```
+    /// use kuehree::{IndexableSumQuery, SumQuery};
+    ///
+    /// let sum = SumQuery::<[u32; 4]>::new([1, 3, 4, 8]);
+    /// assert_eq!(sum.query_const::<1, 3>(), 15);
+    /// ```
+    ///
+    /// ```compile_fail
+    /// use kuehree::{IndexableSumQuery, SumQuery};
+    ///
+    /// let sum = SumQuery::<[u32; 4]>::new([1, 3, 4, 8]);
+    /// sum.query_const::<1, 4>();
+    /// ```
+    pub fn query_const<const S: usize, const E: usize>(&self) -> T {
+        const {
+            assert!(S <= E, "range start is greater than end");
+            assert!(E < N, "range end is past the last element");
+        }
+        RangeQuery::query(self, S, E)
     }
 }
 
-impl<T> IndexableSumQuery<T> for SumQuery<Vec<T>>
+#[cfg(feature = "alloc")]
+impl<T, const N: usize> SumQuery<[T; N]>
 where
-    T: Copy + Sub<Output = T> + Add<Output = T>,
+    T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
 {
-    type PrefixSumContainer = Vec<T>;
-
-    fn new(data: impl IntoIterator<Item = T>) -> Self {
-        let mut prefix_sum_array = vec![];
-        for (idx, d) in data.into_iter().enumerate() {
-            if idx == 0 {
-                prefix_sum_array.push(d);
-            } else {
-                prefix_sum_array.push(d + prefix_sum_array[idx - 1]);
+    /// Build the table directly on the heap, for sizes that would overflow
+    /// the stack through [`IndexableSumQuery::new`]
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Panics
+    ///
+    /// Unless `data` holds exactly `N` elements, as with `new`
+    #[allow(unsafe_code)]
+    pub fn new_boxed(data: impl IntoIterator<Item = T>) -> Box<Self> {
+        let span = BuildSpan::enter("SumQuery");
+        span.record_len(N);
+        let mut prefix_sum_array = vec![T::zero(); N].into_boxed_slice();
+        let mut total = T::zero();
+        let mut found = 0;
+        for d in data {
+            total = total + d;
+            if let Some(slot) = prefix_sum_array.get_mut(found) {
+                *slot = total;
             }
+            found += 1;
         }
-        Self { prefix_sum_array }
-    }
+        assert_eq!(found, N, "expected {N} elements but found {found}");
 
-    fn prefix_sum_array(&self) -> &Self::PrefixSumContainer {
-        &self.prefix_sum_array
+        let prefix_sum_array: Box<[T; N]> = prefix_sum_array
+            .try_into()
+            .unwrap_or_else(|_| unreachable!());
+        // SAFETY: `SumQuery` is `repr(transparent)` over its container
+        unsafe { Box::from_raw(Box::into_raw(prefix_sum_array).cast::<Self>()) }
     }
 }
 
-impl<'a, T, const N: usize> IndexableSumQuery<T> for SumQuery<[T; N]>
+#[cfg(feature = "alloc")]
+impl<'a, T> SumQuery<Cow<'a, [T]>>
 where
     T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
 {
-    type PrefixSumContainer = [T; N];
+    /// Wrap prefix sums computed elsewhere, borrowing them if possible
+    ///
+    /// `prefix_sums` has to start with a leading zero, see
+    /// [`IndexableSumQuery::prefix_sum_array`]
+    ///
+    /// # Errors
+    ///
+    /// [`Error::EmptyInput`] if `prefix_sums` doesn't even hold the leading
+    /// zero
+    pub fn from_prefix_sums(prefix_sums: impl Into<Cow<'a, [T]>>) -> Result<Self, Error> {
+        let prefix_sum_array = prefix_sums.into();
+        if prefix_sum_array.is_empty() {
+            return Err(Error::EmptyInput);
+        }
+        Ok(Self { prefix_sum_array })
+    }
 
-    fn new(data: impl IntoIterator<Item = T>) -> Self {
-        let mut prefix_sum_array = [T::zero(); N];
-        for (idx, d) in data.into_iter().enumerate() {
-            if idx == 0 {
-                prefix_sum_array[idx] = d;
-            } else {
-                prefix_sum_array[idx] = d + prefix_sum_array[idx - 1];
-            }
+    /// Take ownership of the prefix sums, copying them only if borrowed
+    pub fn into_owned(self) -> SumQuery<Vec<T>> {
+        SumQuery {
+            prefix_sum_array: self.prefix_sum_array.into_owned(),
         }
+    }
+}
+
+impl<C> SumQuery<C> {
+    /// Wrap an already computed prefix sum container
+    pub(crate) fn from_container(prefix_sum_array: C) -> Self {
         Self { prefix_sum_array }
     }
 
-    fn prefix_sum_array(&self) -> &Self::PrefixSumContainer {
+    /// Borrow the prefix sum container, laid out as described on
+    /// [`IndexableSumQuery::prefix_sum_array`]
+    ///
+    /// The original elements are not kept, only their running totals
+    pub fn prefix_sums(&self) -> &C {
         &self.prefix_sum_array
     }
-}
 
-impl<T, T2> From<T2> for SumQuery<Vec<T>>
-where
-    T2: AsRef<[T]>,
-    T: Copy + Add<Output = T>,
-{
-    fn from(data: T2) -> Self {
-        let data = data.as_ref();
-        let mut prefix_sum_array = vec![];
-        for (idx, d) in data.iter().enumerate() {
-            if idx == 0 {
-                prefix_sum_array.push(*d);
-            } else {
-                prefix_sum_array.push(*d + prefix_sum_array[idx - 1]);
-            }
+    /// Take back the prefix sum container
+    pub fn into_inner(self) -> C {
+        self.prefix_sum_array
+    }
+
+    /// Element at `index`, or `None` if out of bounds
+    ///
+    /// Elements are not stored, so this is recovered from two prefix sums
+    /// and is returned by value, `Index` can not be implemented. Floating
+    /// point elements may come back with rounding error.
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn get<T>(&self, index: usize) -> Option<T>
+    where
+        T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+        C: PrefixStorage<T>,
+    {
+        (index < self.len()).then(|| self.query(index, index))
+    }
+
+    /// Iterate over the elements, recovered from adjacent prefix sums
+    ///
+    /// Floating point elements may come back with rounding error
+    pub fn iter<'a, T>(&'a self) -> impl Iterator<Item = T> + 'a
+    where
+        T: 'a + Copy + Sub<Output = T> + Add<Output = T> + Zero,
+        C: PrefixStorage<T>,
+    {
+        let mut previous = T::zero();
+        self.prefix_iter().map(move |total| {
+            let d = total - previous;
+            previous = total;
+            d
+        })
+    }
+
+    /// Iterate over the running totals, item `i` is the sum of the first
+    /// `i + 1` elements whatever the backend
+    pub fn prefix_iter<T>(&self) -> core::iter::Skip<C::Iter<'_>>
+    where
+        C: PrefixStorage<T>,
+    {
+        self.prefix_sum_array
+            .totals()
+            .skip(usize::from(C::LEADING_ZERO))
+    }
+
+    /// Copy the prefix sums into another backend, without recomputing them
+    ///
+    /// Algorithmic complexity: O(n)
+    ///
+    /// # Panics
+    ///
+    /// When `D` is a fixed size array of another length, prefer the
+    /// `TryFrom` conversions there
+    pub fn to_storage<D, T>(&self) -> SumQuery<D>
+    where
+        T: Zero,
+        C: PrefixStorage<T>,
+        D: PrefixStorage<T>,
+    {
+        let totals = self
+            .prefix_sum_array
+            .totals()
+            .skip(usize::from(C::LEADING_ZERO));
+        let leading_zero = D::LEADING_ZERO.then(T::zero);
+        SumQuery {
+            prefix_sum_array: D::from_totals(leading_zero.into_iter().chain(totals)),
         }
-        Self { prefix_sum_array }
     }
 }
 
-impl<T, T2, const N: usize> From<T2> for SumQuery<[T; N]>
-where
-    T2: AsRef<[T]>,
-    T: Copy + Add<Output = T> + Zero,
-{
-    fn from(data: T2) -> Self {
-        let data = data.as_ref();
-        let mut prefix_sum_array = [T::zero(); N];
-        for (idx, d) in data.iter().enumerate() {
-            if idx == 0 {
-                prefix_sum_array[idx] = *d;
-            } else {
-                prefix_sum_array[idx] = *d + prefix_sum_array[idx - 1];
-            }
-        }
-        Self { prefix_sum_array }
+#[cfg(feature = "alloc")]
+impl<C> SumQuery<C> {
+    /// View as a [`Cow`] backed [`SumQuery`]
+    ///
+    /// Borrows the prefix sums when `C` stores a leading zero and copies
+    /// them otherwise
+    pub fn to_cow<T>(&self) -> SumQuery<Cow<'_, [T]>>
+    where
+        T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+        C: PrefixStorage<T> + AsRef<[T]>,
+    {
+        let prefix_sum_array = if C::LEADING_ZERO {
+            Cow::Borrowed(self.prefix_sum_array.as_ref())
+        } else {
+            let totals = self.prefix_sum_array.totals();
+            Cow::Owned(core::iter::once(T::zero()).chain(totals).collect())
+        };
+        SumQuery { prefix_sum_array }
+    }
+}
+
+#[cfg(all(feature = "alloc", target_has_atomic = "ptr"))]
+impl<C> SumQuery<C> {
+    /// Move the prefix sums into an [`Arc`], without recomputing them
+    ///
+    /// Clones of the result are O(1) and can be queried from many threads
+    pub fn into_shared<T>(self) -> SumQuery<Arc<[T]>>
+    where
+        T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+        C: PrefixStorage<T>,
+    {
+        self.to_storage()
     }
 }
 
@@ -140,6 +1116,7 @@ mod test {
     use super::*;
 
     #[test]
+    #[allow(unused_variables)]
     fn test() {
         let data = [123u32];
         let data2 = vec![123u32];
@@ -150,9 +1127,11 @@ mod test {
         let _sum = SumQuery::<[_; 1]>::new(data);
         let _sum = SumQuery::<Vec<_>>::new(data2);
         let _sum = SumQuery::<Vec<_>>::from(data3);
+        let _sum = SumQuery::<Box<[_]>>::from(data3);
     }
 
     #[test]
+    #[allow(clippy::no_effect)]
     fn test_query_u32_vec() {
         let data = vec![1, 3, 4, 8, 6, 1, 4, 2];
         let sum = SumQuery::<Vec<_>>::from(&data);
@@ -246,6 +1225,23 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_try_from_slice() {
+        let data = [1u32, 3, 4, 8, 6, 1, 4, 2];
+        let sum = SumQuery::<[_; 8]>::try_from(&data[..]).unwrap();
+        assert_eq!(sum, SumQuery::from(data));
+        assert_eq!(sum.query(3, 6), 19);
+
+        assert_eq!(
+            SumQuery::<[u32; 8]>::try_from(&data[..5]),
+            Err(Error::LengthMismatch {
+                expected: 8,
+                found: 5
+            })
+        );
+        assert!(SumQuery::<[u32; 4]>::try_from(&data[..]).is_err());
+    }
+
     #[test]
     fn test_query_slice_vec() {
         let slice = vec![1u32, 3, 4, 8, 6, 1, 4, 2];
@@ -266,6 +1262,364 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_query_boxed_slice() {
+        let sum = SumQuery::<Box<[_]>>::new([1u32, 3, 4, 8, 6, 1, 4, 2]);
+
+        let results = [
+            (sum.query(3, 6), 19u32),
+            (sum.query(0, 7), 29),
+            (sum.query(0, 6), 27),
+            (sum.query(1, 6), 26),
+            (sum.query(2, 7), 25),
+            (sum.query(5, 6), 5),
+        ];
+
+        for (l, r) in results {
+            assert_eq!(l, r);
+        }
+    }
+
+    #[test]
+    fn test_leading_zero() {
+        let sum = SumQuery::<Vec<_>>::new([1u32, 3, 4]);
+        let empty = SumQuery::<Vec<u32>>::new([]);
+
+        assert_eq!(sum.prefix_sum_array(), &[0, 1, 4, 8]);
+        assert_eq!(sum.len(), 3);
+        assert_eq!(sum.query(0, 2), 8);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_query_many() {
+        let data: Vec<u64> = (0..200).map(|i| i * 7 % 31).collect();
+        let sum = SumQuery::<Vec<_>>::new(data.iter().copied());
+        let fixed = SumQuery::<[u64; 200]>::new(data.iter().copied());
+        let ranges: Vec<(usize, usize)> = (0..150).map(|i| (i, i + i % 50)).collect();
+        let mut out = vec![0; ranges.len()];
+        sum.query_many(&ranges, &mut out);
+        for (&(start, end), &total) in ranges.iter().zip(&out) {
+            assert_eq!(total, data[start..=end].iter().sum::<u64>());
+        }
+        let mut fixed_out = vec![0; ranges.len()];
+        fixed.query_many(&ranges, &mut fixed_out);
+        assert_eq!(fixed_out, out);
+        let mut scalar = vec![0; ranges.len()];
+        query_many_kernel(&sum, &ranges, &mut scalar);
+        assert_eq!(scalar, out);
+    }
+
+    #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+    #[test]
+    #[allow(unsafe_code)]
+    fn test_query_many_avx2() {
+        if !std::is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let sum = SumQuery::<Vec<u64>>::new((0..200).map(|i| i * 7 % 31));
+        let ranges: Vec<(usize, usize)> = (0..150).map(|i| (i, i + i % 50)).collect();
+        let mut out = vec![0; ranges.len()];
+        // SAFETY: the CPU supports AVX2
+        unsafe { query_many_avx2(&sum, &ranges, &mut out) };
+        let mut kernel = vec![0; ranges.len()];
+        query_many_kernel(&sum, &ranges, &mut kernel);
+        assert_eq!(out, kernel);
+    }
+
+    #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
+    #[test]
+    #[allow(unsafe_code)]
+    fn test_query_many_avx512() {
+        if !std::is_x86_feature_detected!("avx512f") {
+            return;
+        }
+        let sum = SumQuery::<Vec<u64>>::new((0..200).map(|i| i * 7 % 31));
+        let ranges: Vec<(usize, usize)> = (0..150).map(|i| (i, i + i % 50)).collect();
+        let mut out = vec![0; ranges.len()];
+        // SAFETY: the CPU supports AVX-512
+        unsafe { query_many_avx512(&sum, &ranges, &mut out) };
+        let mut kernel = vec![0; ranges.len()];
+        query_many_kernel(&sum, &ranges, &mut kernel);
+        assert_eq!(out, kernel);
+    }
+
+    #[test]
+    fn test_const_query() {
+        const DAYS: SumQuery<[u16; 12]> =
+            SumQuery::<[u16; 12]>::from_array([31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31]);
+        const SECOND_QUARTER: u16 = DAYS.range_sum(3, 5);
+        const _: () = assert!(DAYS.range_sum(0, 11) == 365);
+
+        assert_eq!(SECOND_QUARTER, 91);
+        let generic = SumQuery::<[u16; 12]>::new(DAYS.iter());
+        assert_eq!(DAYS, generic);
+        assert_eq!(DAYS.query(1, 4), DAYS.range_sum(1, 4));
+        assert_eq!(DAYS.query_const::<0, 0>(), 31);
+        assert_eq!(DAYS.query_const::<11, 11>(), 31);
+    }
+
+    #[test]
+    fn test_query_user_type() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Cents(i64);
+
+        impl Add for Cents {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                Self(self.0 + rhs.0)
+            }
+        }
+
+        impl Sub for Cents {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                Self(self.0 - rhs.0)
+            }
+        }
+
+        impl Zero for Cents {
+            fn zero() -> Self {
+                Self(0)
+            }
+        }
+
+        let sum = SumQuery::<Vec<_>>::new([125, -50, 300].map(Cents));
+        assert_eq!(sum.query(0, 1), Cents(75));
+        assert_eq!(sum.query(1, 2), Cents(250));
+    }
+
+    #[test]
+    fn test_custom_storage() {
+        /// Totals kept in a deque, only the storage trait is implemented
+        struct Deque(std::collections::VecDeque<u64>);
+
+        impl PrefixStorage<u64> for Deque {
+            const LEADING_ZERO: bool = true;
+
+            type Iter<'a> = std::iter::Copied<std::collections::vec_deque::Iter<'a, u64>>;
+
+            fn from_totals(totals: impl Iterator<Item = u64>) -> Self {
+                Self(totals.collect())
+            }
+
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+
+            fn total(&self, index: usize) -> u64 {
+                self.0[index]
+            }
+
+            fn totals(&self) -> Self::Iter<'_> {
+                self.0.iter().copied()
+            }
+        }
+
+        let sum = SumQuery::<Deque>::new([1, 3, 4, 8, 6, 1, 4, 2]);
+        assert_eq!(sum.len(), 8);
+        assert_eq!(sum.query(3, 6), 19);
+        assert_eq!(sum.query(0, 7), 29);
+    }
+
+    #[test]
+    fn test_query_cow() {
+        let owned = SumQuery::<Vec<_>>::new([1u32, 3, 4, 8, 6, 1, 4, 2]);
+        let fixed = SumQuery::<[_; 8]>::new([1u32, 3, 4, 8, 6, 1, 4, 2]);
+
+        let borrowed = owned.to_cow();
+        let copied = fixed.to_cow();
+        assert!(matches!(borrowed.prefix_sum_array(), Cow::Borrowed(_)));
+        assert!(matches!(copied.prefix_sum_array(), Cow::Owned(_)));
+
+        let prefix_sums = [0u32, 1, 4, 8];
+        let wrapped = SumQuery::from_prefix_sums(&prefix_sums[..]).unwrap();
+        assert!(SumQuery::<Cow<'_, [u32]>>::from_prefix_sums(vec![]).is_err());
+
+        for start in 0..8 {
+            for end in start..8 {
+                assert_eq!(borrowed.query(start, end), owned.query(start, end));
+                assert_eq!(copied.query(start, end), owned.query(start, end));
+            }
+        }
+        assert_eq!(wrapped.query(1, 2), 7);
+        assert_eq!(wrapped.into_owned(), SumQuery::<Vec<_>>::new([1, 3, 4]));
+    }
+
+    #[test]
+    fn test_accessors() {
+        let sum = SumQuery::<Vec<_>>::new([1u32, 3, 4]);
+        assert_eq!(sum.prefix_sums(), &[0, 1, 4, 8]);
+        assert_eq!(sum.into_inner(), [0, 1, 4, 8]);
+
+        let fixed = SumQuery::<[_; 3]>::new([1u32, 3, 4]);
+        assert_eq!(fixed.prefix_sums(), &[1, 4, 8]);
+    }
+
+    #[test]
+    fn test_get() {
+        let data = [1i32, -3, 4, 8];
+        let sum = SumQuery::<Vec<_>>::new(data);
+        let fixed = SumQuery::<[_; 4]>::new(data);
+
+        for (idx, d) in data.into_iter().enumerate() {
+            assert_eq!(sum.get(idx), Some(d));
+            assert_eq!(fixed.get(idx), Some(d));
+        }
+        assert_eq!(sum.get(4), None);
+        assert_eq!(fixed.get(4), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let expected = "index  | 0 1 2  3  4\ndata   | 1 3 4  8 -7\nprefix | 1 4 8 16  9";
+        let data = [1i32, 3, 4, 8, -7];
+        assert_eq!(SumQuery::<Vec<_>>::new(data).to_string(), expected);
+        assert_eq!(SumQuery::<[_; 5]>::new(data).to_string(), expected);
+    }
+
+    #[test]
+    fn test_collect() {
+        let sum: SumQuery<Vec<_>> = (1..=10u32).map(|d| d * d).collect();
+        assert_eq!(sum.len(), 10);
+        assert_eq!(sum.query(0, 9), 385);
+        assert_eq!(sum.query(2, 3), 25);
+
+        let boxed: SumQuery<Box<[_]>> = (1..=10u32).map(|d| d * d).collect();
+        assert_eq!(boxed.query(2, 3), 25);
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut sum = SumQuery::<Vec<_>>::new([1u32, 3, 4]);
+        sum.extend([8, 6]);
+        assert_eq!(sum, SumQuery::<Vec<_>>::new([1, 3, 4, 8, 6]));
+        assert_eq!(sum.query(2, 4), 18);
+
+        let mut empty = SumQuery::<Vec<u32>>::new([]);
+        empty.extend([2, 5]);
+        assert_eq!(empty.query(0, 1), 7);
+    }
+
+    #[test]
+    fn test_iter() {
+        let data = [1i32, 3, -4, 8, 6];
+        let sum = SumQuery::<Vec<_>>::new(data);
+        let fixed = SumQuery::<[_; 5]>::new(data);
+
+        assert!(sum.iter().eq(data));
+        assert!(fixed.iter().eq(data));
+        assert!(sum.prefix_iter().eq([1, 4, 0, 8, 14]));
+        assert!(fixed.prefix_iter().eq([1, 4, 0, 8, 14]));
+
+        let owned = sum.into_iter();
+        assert_eq!(owned.len(), 5);
+        assert!(owned.rev().eq(data.into_iter().rev()));
+
+        let mut total = 0;
+        for d in fixed {
+            total += d;
+        }
+        assert_eq!(total, 14);
+    }
+
+    #[test]
+    fn test_default() {
+        assert!(SumQuery::<Vec<u32>>::default().is_empty());
+        assert!(SumQuery::<Box<[u32]>>::default().is_empty());
+        assert_eq!(SumQuery::<[u32; 4]>::default().query(0, 3), 0);
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let data = [1u64, 3, 4, 8];
+        let fixed = SumQuery::<[_; 4]>::new(data);
+        assert_eq!(fixed.heap_bytes(), 0);
+        assert_eq!(fixed.memory_footprint(), 32);
+
+        let boxed = SumQuery::<Box<[_]>>::new(data);
+        assert_eq!(boxed.heap_bytes(), 40);
+        assert_eq!(boxed.memory_footprint(), 40 + size_of::<Box<[u64]>>());
+        assert!(SumQuery::<Vec<_>>::new(data).heap_bytes() >= 40);
+        assert_eq!(boxed.to_cow().heap_bytes(), 0);
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut sum = SumQuery::<Vec<u32>>::with_capacity(16);
+        assert!(sum.is_empty());
+        assert!(sum.capacity() >= 16);
+
+        let heap_bytes = sum.heap_bytes();
+        for d in [1, 3, 4, 8] {
+            sum.push(d);
+        }
+        assert_eq!(sum.heap_bytes(), heap_bytes);
+        assert_eq!(sum.query(1, 3), 15);
+
+        sum.shrink_to_fit();
+        assert_eq!(sum.capacity(), 4);
+        sum.reserve(8);
+        assert!(sum.capacity() >= 12);
+        assert_eq!(sum, SumQuery::<Vec<_>>::new([1, 3, 4, 8]));
+    }
+
+    #[test]
+    fn test_convert_storage() {
+        let data = [1u32, 3, 4, 8, 6, 1, 4, 2];
+        let fixed = SumQuery::<[_; 8]>::new(data);
+        let vec = SumQuery::<Vec<_>>::from(fixed);
+        assert_eq!(vec, SumQuery::<Vec<_>>::new(data));
+
+        let boxed = SumQuery::<Box<[_]>>::from(vec.clone());
+        assert_eq!(boxed, SumQuery::<Box<[_]>>::new(data));
+        assert_eq!(SumQuery::<Vec<_>>::from(boxed.clone()), vec);
+
+        assert_eq!(SumQuery::<[_; 8]>::try_from(vec.clone()), Ok(fixed));
+        assert_eq!(SumQuery::<[_; 8]>::try_from(boxed), Ok(fixed));
+        assert_eq!(
+            SumQuery::<[u32; 4]>::try_from(vec.clone()),
+            Err(Error::LengthMismatch {
+                expected: 4,
+                found: 8
+            })
+        );
+        assert_eq!(vec.to_storage::<[_; 8], _>(), fixed);
+    }
+
+    #[test]
+    fn test_query_shared() {
+        let shared = SumQuery::<[_; 8]>::new([1u64, 3, 4, 8, 6, 1, 4, 2]).into_shared();
+
+        let handles: Vec<_> = (0..4)
+            .map(|start| {
+                let shared = shared.clone();
+                std::thread::spawn(move || shared.query(start, 7))
+            })
+            .collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_eq!(results, [29, 28, 25, 21]);
+        assert_eq!(SumQuery::<Arc<[_]>>::from([1u64, 2]).query(0, 1), 3);
+    }
+
+    #[test]
+    fn test_new_boxed() {
+        const N: usize = 1 << 20;
+        let boxed = SumQuery::<[u64; N]>::new_boxed(1..=N as u64);
+        assert_eq!(boxed.len(), N);
+        assert_eq!(boxed.query(0, N - 1), (N as u64) * (N as u64 + 1) / 2);
+        assert_eq!(boxed.query(2, 3), 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 4 elements but found 5")]
+    fn test_fixed_length_mismatch() {
+        SumQuery::<[u32; 4]>::new([1, 2, 3, 4, 5]);
+    }
+
     #[ignore]
     #[test]
     fn test_sz() {
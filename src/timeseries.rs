@@ -0,0 +1,262 @@
+//! Sum, minimum and maximum over time ranges of timestamped values
+
+use core::{
+    marker::PhantomData,
+    ops::{Add, Sub},
+};
+
+use alloc::vec::Vec;
+
+use crate::{
+    chunked::ChunkedPrefixSums,
+    rmq::{Max, Min, Operation, SegmentTree},
+    MemoryUsage, Zero,
+};
+
+/// Width of the buckets values are aggregated in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Window {
+    Second,
+    Minute,
+    Hour,
+    /// Any number of seconds
+    Seconds(u64),
+}
+
+impl Window {
+    /// Width in seconds
+    pub fn seconds(self) -> u64 {
+        match self {
+            Self::Second => 1,
+            Self::Minute => 60,
+            Self::Hour => 3600,
+            Self::Seconds(seconds) => seconds,
+        }
+    }
+}
+
+/// `M` over the buckets holding a value, skipping the empty ones
+#[derive(Debug)]
+struct Present<M>(PhantomData<M>);
+
+impl<T, M: Operation<T>> Operation<Option<T>> for Present<M> {
+    fn combine(a: Option<T>, b: Option<T>) -> Option<T> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(M::combine(a, b)),
+            (a, None) => a,
+            (None, b) => b,
+        }
+    }
+}
+
+/// Values bucketed by timestamp, answering sums, minimums and maximums
+/// over arbitrary time ranges
+///
+/// Timestamps are seconds from any fixed epoch. Bucket sums live in
+/// [`ChunkedPrefixSums`] and bucket extremes in segment trees, so a range
+/// is answered in O(log buckets) whatever its length. A range covers
+/// every bucket it overlaps.
+///
+/// ```
+/// use kuehree::timeseries::{TimeSeriesQuery, Window};
+///
+/// let mut series = TimeSeriesQuery::new(Window::Minute);
+/// series.insert(0, 4);
+/// series.insert(30, 1);
+/// series.insert(150, 7);
+/// assert_eq!(series.sum(0, 59), 5);
+/// assert_eq!(series.min(0, 179), Some(1));
+/// assert_eq!(series.max(60, 119), None);
+/// ```
+#[derive(Debug)]
+pub struct TimeSeriesQuery<T> {
+    window: u64,
+    /// Start of the first bucket, set by the first insertion
+    origin: Option<u64>,
+    sums: ChunkedPrefixSums<T>,
+    /// Grown by doubling, buckets past [`Self::buckets`] are `None`
+    mins: SegmentTree<Option<T>, Present<Min>>,
+    maxs: SegmentTree<Option<T>, Present<Max>>,
+}
+
+impl<T> TimeSeriesQuery<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + PartialOrd + Zero,
+{
+    /// Empty `Self` with buckets of `window`, starting at the bucket of
+    /// the first inserted timestamp
+    ///
+    /// # Panics
+    ///
+    /// If `window` is 0 seconds wide
+    pub fn new(window: Window) -> Self {
+        assert!(window.seconds() > 0, "window has to be at least a second");
+        Self {
+            window: window.seconds(),
+            origin: None,
+            sums: ChunkedPrefixSums::default(),
+            mins: SegmentTree::new([]),
+            maxs: SegmentTree::new([]),
+        }
+    }
+
+    /// Empty `Self` whose first bucket starts at `origin`
+    ///
+    /// # Panics
+    ///
+    /// If `window` is 0 seconds wide
+    pub fn with_origin(window: Window, origin: u64) -> Self {
+        let mut series = Self::new(window);
+        series.origin = Some(origin);
+        series
+    }
+
+    /// Number of buckets, from the first to the latest one holding a value
+    pub fn buckets(&self) -> usize {
+        self.sums.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sums.is_empty()
+    }
+
+    /// Start of the first bucket
+    pub fn origin(&self) -> Option<u64> {
+        self.origin
+    }
+
+    /// Add `value` to the bucket of `timestamp`
+    ///
+    /// Algorithmic complexity: O(chunk length + buckets / chunk length) to
+    /// update the bucket sums in [`ChunkedPrefixSums`], plus O(log buckets)
+    /// amortized for the extremes and the number of empty buckets skipped
+    /// when `timestamp` is past the latest bucket
+    ///
+    /// # Panics
+    ///
+    /// If `timestamp` is before the first bucket
+    pub fn insert(&mut self, timestamp: u64, value: T) {
+        let origin = *self
+            .origin
+            .get_or_insert(timestamp - timestamp % self.window);
+        assert!(timestamp >= origin, "timestamp before the first bucket");
+        let bucket = usize::try_from((timestamp - origin) / self.window)
+            .expect("bucket index overflows usize");
+
+        if bucket >= self.mins.len() {
+            let capacity = (2 * self.mins.len()).max(bucket + 1);
+            self.mins = grow(&self.mins, capacity);
+            self.maxs = grow(&self.maxs, capacity);
+        }
+        while self.sums.len() <= bucket {
+            self.sums.push(T::zero());
+        }
+
+        self.sums.update(bucket, self.sums.get(bucket) + value);
+        let min = Present::<Min>::combine(self.mins.get(bucket), Some(value));
+        self.mins.update(bucket, min);
+        let max = Present::<Max>::combine(self.maxs.get(bucket), Some(value));
+        self.maxs.update(bucket, max);
+    }
+
+    /// Buckets overlapping `from..=to`, if any
+    fn bucket_range(&self, from: u64, to: u64) -> Option<(usize, usize)> {
+        let origin = self.origin?;
+        if to < from || to < origin || self.is_empty() {
+            return None;
+        }
+        let first = usize::try_from(from.saturating_sub(origin) / self.window).ok()?;
+        let last = usize::try_from((to - origin) / self.window)
+            .map_or(self.buckets() - 1, |last| last.min(self.buckets() - 1));
+        (first <= last).then_some((first, last))
+    }
+
+    /// Sum of the values between timestamps `from` and `to` inclusive
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn sum(&self, from: u64, to: u64) -> T {
+        self.bucket_range(from, to)
+            .map_or_else(T::zero, |(first, last)| self.sums.query(first, last))
+    }
+
+    /// Smallest value between timestamps `from` and `to` inclusive
+    ///
+    /// Algorithmic complexity: O(log buckets)
+    pub fn min(&self, from: u64, to: u64) -> Option<T> {
+        let (first, last) = self.bucket_range(from, to)?;
+        self.mins.query(first, last)
+    }
+
+    /// Largest value between timestamps `from` and `to` inclusive
+    ///
+    /// Algorithmic complexity: O(log buckets)
+    pub fn max(&self, from: u64, to: u64) -> Option<T> {
+        let (first, last) = self.bucket_range(from, to)?;
+        self.maxs.query(first, last)
+    }
+}
+
+/// Copy of `tree` padded with empty buckets up to `capacity`
+fn grow<T, M>(tree: &SegmentTree<Option<T>, M>, capacity: usize) -> SegmentTree<Option<T>, M>
+where
+    T: Copy,
+    M: Operation<Option<T>>,
+{
+    let mut data: Vec<Option<T>> = tree.data().to_vec();
+    data.resize(capacity, None);
+    SegmentTree::new(data)
+}
+
+impl<T> MemoryUsage for TimeSeriesQuery<T> {
+    fn heap_bytes(&self) -> usize {
+        self.sums.heap_bytes() + self.mins.heap_bytes() + self.maxs.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_time_series() {
+        let points: Vec<(u64, i64)> = (0..500)
+            .map(|i| (1_000 + i * 37, i as i64 % 11 - 5))
+            .collect();
+        let mut series = TimeSeriesQuery::new(Window::Seconds(100));
+        // Out of order after the first point
+        series.insert(points[0].0, points[0].1);
+        for &(timestamp, value) in points[1..].iter().rev() {
+            series.insert(timestamp, value);
+        }
+        assert_eq!(series.origin(), Some(1_000));
+        assert_eq!(series.buckets(), 185);
+
+        for (from, to) in [
+            (0, 5_000),
+            (1_000, 1_099),
+            (1_050, 3_020),
+            (5_000, 90_000),
+            (950, 999),
+        ] {
+            // Ranges cover whole buckets
+            let from_bucket = from.max(1_000) / 100 * 100;
+            let to_bucket = to / 100 * 100 + 99;
+            let values = points
+                .iter()
+                .filter(|(timestamp, _)| (from_bucket..=to_bucket).contains(timestamp))
+                .map(|&(_, value)| value);
+            assert_eq!(series.sum(from, to), values.clone().sum::<i64>());
+            assert_eq!(series.min(from, to), values.clone().min());
+            assert_eq!(series.max(from, to), values.max());
+        }
+        assert_eq!(series.sum(20_000, 30_000), 0);
+        assert_eq!(series.max(3_000, 2_000), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "timestamp before the first bucket")]
+    fn test_before_origin() {
+        let mut series = TimeSeriesQuery::with_origin(Window::Hour, 7_200);
+        series.insert(7_199, 1u32);
+    }
+}
@@ -0,0 +1,66 @@
+//! Hooks emitting `tracing` spans and events and `metrics` build counters,
+//! no-ops without the `tracing` and `metrics` features
+
+use crate::Error;
+
+/// Ranges covering at least this many elements are reported as large
+#[cfg(feature = "tracing")]
+pub(crate) const LARGE_QUERY: usize = 1 << 20;
+
+/// Span covering the construction of a structure, entered until dropped
+pub(crate) struct BuildSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+    #[cfg(feature = "metrics")]
+    structure: &'static str,
+    #[cfg(feature = "metrics")]
+    started: std::time::Instant,
+}
+
+impl BuildSpan {
+    pub(crate) fn enter(structure: &'static str) -> Self {
+        #[cfg(not(any(feature = "tracing", feature = "metrics")))]
+        let _ = structure;
+        #[cfg(feature = "metrics")]
+        metrics::counter!("kuehree_builds_total", "structure" => structure).increment(1);
+        Self {
+            #[cfg(feature = "tracing")]
+            span: tracing::debug_span!("build", structure, len = tracing::field::Empty).entered(),
+            #[cfg(feature = "metrics")]
+            structure,
+            #[cfg(feature = "metrics")]
+            started: std::time::Instant::now(),
+        }
+    }
+
+    /// Record the number of elements once it is known
+    #[cfg_attr(not(feature = "tracing"), allow(clippy::unused_self))]
+    pub(crate) fn record_len(&self, len: usize) {
+        #[cfg(feature = "tracing")]
+        self.span.record("len", len);
+        #[cfg(not(feature = "tracing"))]
+        let _ = len;
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for BuildSpan {
+    fn drop(&mut self) {
+        metrics::histogram!("kuehree_build_duration_seconds", "structure" => self.structure)
+            .record(self.started.elapsed());
+    }
+}
+
+/// Report a checked query that was rejected or spans a large range
+pub(crate) fn checked_query(start: usize, end: usize, result: &Result<(), Error>) {
+    #[cfg(feature = "tracing")]
+    match result {
+        Err(error) => tracing::debug!(start, end, %error, "invalid range query"),
+        Ok(()) if end - start + 1 >= LARGE_QUERY => {
+            tracing::debug!(start, end, "large range query");
+        }
+        Ok(()) => {}
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (start, end, result);
+}
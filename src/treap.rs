@@ -0,0 +1,348 @@
+//! Fully persistent sorted map with aggregates over key ranges
+
+use core::{borrow::Borrow, fmt, marker::PhantomData, mem};
+
+use alloc::rc::Rc;
+
+use crate::{rmq::Operation, sketch::splitmix, MemoryUsage};
+
+type Link<K, V> = Option<Rc<Node<K, V>>>;
+
+#[derive(Debug)]
+struct Node<K, V> {
+    key: K,
+    value: V,
+    priority: u64,
+    /// Number of entries in the subtree
+    size: usize,
+    /// Fold of the values in the subtree, in key order
+    aggregate: V,
+    left: Link<K, V>,
+    right: Link<K, V>,
+}
+
+fn size<K, V>(link: &Link<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.size)
+}
+
+impl<K: Clone, V: Copy> Node<K, V> {
+    /// Copy of `node` with new children, the path copying step
+    fn with<M: Operation<V>>(node: &Self, left: Link<K, V>, right: Link<K, V>) -> Rc<Self> {
+        let mut aggregate = node.value;
+        if let Some(left) = &left {
+            aggregate = M::combine(left.aggregate, aggregate);
+        }
+        if let Some(right) = &right {
+            aggregate = M::combine(aggregate, right.aggregate);
+        }
+        Rc::new(Self {
+            key: node.key.clone(),
+            value: node.value,
+            priority: node.priority,
+            size: size(&left) + 1 + size(&right),
+            aggregate,
+            left,
+            right,
+        })
+    }
+}
+
+/// Sorted map from `K` to `V` where every change returns a new map and
+/// leaves the old one untouched
+///
+/// The map is a treap sharing all nodes off the changed path between
+/// versions, so cloning is O(1) and a change costs O(log n) expected time
+/// and memory. Each node keeps the fold under `M` of the values below it,
+/// so a range of keys is folded in O(log n) expected.
+///
+/// ```
+/// use kuehree::{rmq::Sum, treap::PersistentMap};
+///
+/// let empty = PersistentMap::<u32, u64, Sum>::new();
+/// let week = empty.insert(3, 40).insert(1, 10).insert(7, 5);
+/// let edited = week.insert(3, 0).remove(&7);
+/// assert_eq!(week.query(&1, &5), Some(50));
+/// assert_eq!(edited.query(&1, &9), Some(10));
+/// assert_eq!(empty.len(), 0);
+/// ```
+pub struct PersistentMap<K, V, M> {
+    root: Link<K, V>,
+    /// Seed of the next node priority
+    seed: u64,
+    _phantom: PhantomData<M>,
+}
+
+impl<K, V, M> PersistentMap<K, V, M> {
+    /// Construct an empty `Self`
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            seed: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        size(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+}
+
+impl<K, V, M> PersistentMap<K, V, M>
+where
+    K: Ord + Clone,
+    V: Copy,
+    M: Operation<V>,
+{
+    /// Value of `key`
+    ///
+    /// Algorithmic complexity: O(log n) expected
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            link = match key.cmp(node.key.borrow()) {
+                core::cmp::Ordering::Less => &node.left,
+                core::cmp::Ordering::Greater => &node.right,
+                core::cmp::Ordering::Equal => return Some(&node.value),
+            };
+        }
+        None
+    }
+
+    /// Map with `key` set to `value`, replacing any previous value
+    ///
+    /// Algorithmic complexity: O(log n) expected time and memory
+    #[must_use]
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let (before, rest) = Self::split(&self.root, |node| node < &key);
+        let (_, after) = Self::split(&rest, |node| node <= &key);
+        let leaf = Rc::new(Node {
+            key,
+            value,
+            priority: splitmix(self.seed),
+            size: 1,
+            aggregate: value,
+            left: None,
+            right: None,
+        });
+        Self {
+            root: Self::merge(&Self::merge(&before, &Some(leaf)), &after),
+            seed: self.seed + 1,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Map without `key`
+    ///
+    /// Algorithmic complexity: O(log n) expected time and memory
+    #[must_use]
+    pub fn remove<Q>(&self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (before, rest) = Self::split(&self.root, |node| node.borrow() < key);
+        let (_, after) = Self::split(&rest, |node| node.borrow() <= key);
+        Self {
+            root: Self::merge(&before, &after),
+            seed: self.seed,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Fold of the values of the keys between `start` and `end`, both
+    /// inclusive, `None` if there are none
+    ///
+    /// Algorithmic complexity: O(log n) expected
+    pub fn query<Q>(&self, start: &Q, end: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Self::fold(&self.root, Some(start), Some(end)).map(|(value, _)| value)
+    }
+
+    /// Number of keys between `start` and `end`, both inclusive
+    ///
+    /// Algorithmic complexity: O(log n) expected
+    pub fn count<Q>(&self, start: &Q, end: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        Self::fold(&self.root, Some(start), Some(end)).map_or(0, |(_, count)| count)
+    }
+
+    /// Fold and count of the keys within the bounds, `None` standing for
+    /// no bound
+    fn fold<Q>(link: &Link<K, V>, start: Option<&Q>, end: Option<&Q>) -> Option<(V, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let node = link.as_ref()?;
+        let key = node.key.borrow();
+        if start.is_none() && end.is_none() {
+            return Some((node.aggregate, node.size));
+        }
+        if start.is_some_and(|start| key < start) {
+            return Self::fold(&node.right, start, end);
+        }
+        if end.is_some_and(|end| key > end) {
+            return Self::fold(&node.left, start, end);
+        }
+
+        let (mut value, mut count) = (node.value, 1);
+        if let Some((left, left_count)) = Self::fold(&node.left, start, None) {
+            value = M::combine(left, value);
+            count += left_count;
+        }
+        if let Some((right, right_count)) = Self::fold(&node.right, None, end) {
+            value = M::combine(value, right);
+            count += right_count;
+        }
+        Some((value, count))
+    }
+
+    /// Keys for which `goes_left` holds and the others, `goes_left` must
+    /// hold for a prefix of the keys
+    fn split(link: &Link<K, V>, goes_left: impl Fn(&K) -> bool + Copy) -> (Link<K, V>, Link<K, V>) {
+        let Some(node) = link else {
+            return (None, None);
+        };
+        if goes_left(&node.key) {
+            let (middle, right) = Self::split(&node.right, goes_left);
+            (
+                Some(Node::with::<M>(node, node.left.clone(), middle)),
+                right,
+            )
+        } else {
+            let (left, middle) = Self::split(&node.left, goes_left);
+            (
+                left,
+                Some(Node::with::<M>(node, middle, node.right.clone())),
+            )
+        }
+    }
+
+    /// Concatenation of `left` and `right`, all keys of `left` come first
+    fn merge(left: &Link<K, V>, right: &Link<K, V>) -> Link<K, V> {
+        match (left, right) {
+            (Some(left_node), Some(right_node)) => {
+                Some(if left_node.priority > right_node.priority {
+                    let right = Self::merge(&left_node.right, right);
+                    Node::with::<M>(left_node, left_node.left.clone(), right)
+                } else {
+                    let left = Self::merge(left, &right_node.left);
+                    Node::with::<M>(right_node, left, right_node.right.clone())
+                })
+            }
+            (link, None) | (None, link) => link.clone(),
+        }
+    }
+}
+
+impl<K, V, M> Default for PersistentMap<K, V, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Shares every node, O(1)
+impl<K, V, M> Clone for PersistentMap<K, V, M> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            seed: self.seed,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, M> fmt::Debug for PersistentMap<K, V, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PersistentMap")
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, V, M> FromIterator<(K, V)> for PersistentMap<K, V, M>
+where
+    K: Ord + Clone,
+    V: Copy,
+    M: Operation<V>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Self::new(), |map, (key, value)| map.insert(key, value))
+    }
+}
+
+/// Counts the nodes reachable from this version, including those shared
+/// with other versions
+impl<K, V, M> MemoryUsage for PersistentMap<K, V, M> {
+    fn heap_bytes(&self) -> usize {
+        self.len() * mem::size_of::<Node<K, V>>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmq::{Max, Sum};
+
+    #[test]
+    fn test_persistent_map() {
+        let mut versions = vec![PersistentMap::<i32, i64, Sum>::new()];
+        let mut model = vec![std::collections::BTreeMap::new()];
+        for step in 0..200u64 {
+            let key = (splitmix(step) % 50) as i32 - 25;
+            let (map, mut entries) = (versions.last().unwrap(), model.last().unwrap().clone());
+            let map = if step % 3 == 0 {
+                entries.remove(&key);
+                map.remove(&key)
+            } else {
+                entries.insert(key, step as i64);
+                map.insert(key, step as i64)
+            };
+            versions.push(map);
+            model.push(entries);
+        }
+
+        for (map, entries) in versions.iter().zip(&model) {
+            assert_eq!(map.len(), entries.len());
+            for (start, end) in [(-30, 30), (-5, 5), (0, 0), (10, -10), (24, 40)] {
+                let range = || {
+                    entries
+                        .iter()
+                        .filter(|(&key, _)| start <= key && key <= end)
+                };
+                let expected = range().map(|(_, &value)| value).reduce(|a, b| a + b);
+                assert_eq!(map.query(&start, &end), expected);
+                assert_eq!(map.count(&start, &end), range().count());
+            }
+            for key in -26..26 {
+                assert_eq!(map.get(&key), entries.get(&key));
+            }
+        }
+
+        let names: PersistentMap<String, u8, Max> = [("kiwi", 3), ("fig", 9), ("apple", 4)]
+            .map(|(name, value)| (name.to_owned(), value))
+            .into_iter()
+            .collect();
+        assert_eq!(names.query("b", "l"), Some(9));
+        assert_eq!(names.clone().remove("fig").query("b", "l"), Some(3));
+        assert_eq!(names.get("fig"), Some(&9));
+    }
+}
@@ -0,0 +1,56 @@
+//! Unchecked query fast paths, enabled with the `unsafe-fast` feature
+
+#![allow(unsafe_code)]
+
+use core::ops::{Add, Sub};
+
+use crate::{IndexableSumQuery, PrefixStorage, SumQuery, Zero};
+
+impl<C> SumQuery<C> {
+    /// Query between start range and end range without bounds checks
+    ///
+    /// For hot loops over ranges that were already validated
+    ///
+    /// # Safety
+    ///
+    /// `start <= end`, and `end + usize::from(C::LEADING_ZERO)` has to be
+    /// less than `self.prefix_sum_array().as_ref().len()`. For the crate's
+    /// containers that is `end` less than the number of elements, but
+    /// [`PrefixStorage::len`] and [`AsRef`] are separate safe traits and a
+    /// custom container may disagree between them.
+    pub unsafe fn query_unchecked<T>(&self, start: usize, end: usize) -> T
+    where
+        T: Copy + Sub<Output = T> + Add<Output = T> + Zero,
+        C: PrefixStorage<T> + AsRef<[T]>,
+    {
+        let prefix_sum_array = self.prefix_sum_array().as_ref();
+        debug_assert!(start <= end && end + usize::from(C::LEADING_ZERO) < prefix_sum_array.len());
+
+        if C::LEADING_ZERO {
+            *prefix_sum_array.get_unchecked(end + 1) - *prefix_sum_array.get_unchecked(start)
+        } else if start == 0 {
+            *prefix_sum_array.get_unchecked(end)
+        } else {
+            *prefix_sum_array.get_unchecked(end) - *prefix_sum_array.get_unchecked(start - 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{IndexableSumQuery, RangeQuery, SumQuery};
+
+    #[test]
+    fn test_query_unchecked() {
+        let sum = SumQuery::<[_; 8]>::new([1u32, 3, 4, 8, 6, 1, 4, 2]);
+
+        for start in 0..8 {
+            for end in start..8 {
+                assert_eq!(
+                    unsafe { sum.query_unchecked(start, end) },
+                    sum.query(start, end)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,436 @@
+//! van Emde Boas tree, ordered set operations on `u32` keys in
+//! O(log log U)
+
+use core::{fmt, mem};
+
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::MemoryUsage;
+
+/// Bits of a universe small enough for a single word
+const LEAF_BITS: u32 = 6;
+
+enum Node {
+    /// Bit set of a universe of at most 64 keys
+    Leaf(u64),
+    Branch(Box<Branch>),
+}
+
+/// Universe of `2^(high + low)` keys, split in `2^high` clusters of
+/// `2^low` keys
+struct Branch {
+    low: u32,
+    /// Minimum and maximum, the minimum is not stored in any cluster
+    bounds: Option<(u32, u32)>,
+    /// Set of the non-empty clusters
+    summary: Node,
+    clusters: Vec<Option<Node>>,
+}
+
+impl Branch {
+    fn split(&self, key: u32) -> (usize, u32) {
+        ((key >> self.low) as usize, key & ((1 << self.low) - 1))
+    }
+
+    fn join(&self, cluster: usize, key: u32) -> u32 {
+        ((cluster as u32) << self.low) | key
+    }
+
+    /// Key of `cluster` picked by `pick`, the cluster must not be empty
+    fn key_in(&self, cluster: usize, pick: impl Fn(&Node) -> Option<u32>) -> u32 {
+        let key = self.clusters[cluster].as_ref().and_then(pick);
+        self.join(cluster, key.unwrap_or_else(|| unreachable!()))
+    }
+}
+
+impl Node {
+    fn new(bits: u32) -> Self {
+        if bits <= LEAF_BITS {
+            return Self::Leaf(0);
+        }
+        let low = bits / 2;
+        Self::Branch(Box::new(Branch {
+            low,
+            bounds: None,
+            summary: Self::new(bits - low),
+            clusters: (0..1usize << (bits - low)).map(|_| None).collect(),
+        }))
+    }
+
+    fn min(&self) -> Option<u32> {
+        match self {
+            Self::Leaf(word) => (*word != 0).then(|| word.trailing_zeros()),
+            Self::Branch(branch) => branch.bounds.map(|(min, _)| min),
+        }
+    }
+
+    fn max(&self) -> Option<u32> {
+        match self {
+            Self::Leaf(word) => (*word != 0).then(|| 63 - word.leading_zeros()),
+            Self::Branch(branch) => branch.bounds.map(|(_, max)| max),
+        }
+    }
+
+    fn contains(&self, key: u32) -> bool {
+        match self {
+            Self::Leaf(word) => word >> key & 1 == 1,
+            Self::Branch(branch) => match branch.bounds {
+                None => false,
+                Some((min, max)) if key == min || key == max => true,
+                Some(_) => {
+                    let (cluster, low) = branch.split(key);
+                    branch.clusters[cluster]
+                        .as_ref()
+                        .is_some_and(|node| node.contains(low))
+                }
+            },
+        }
+    }
+
+    /// Insert `key`, returning whether it was absent
+    fn insert(&mut self, key: u32) -> bool {
+        let branch = match self {
+            Self::Leaf(word) => {
+                let absent = *word >> key & 1 == 0;
+                *word |= 1 << key;
+                return absent;
+            }
+            Self::Branch(branch) => branch,
+        };
+        let Some((min, max)) = &mut branch.bounds else {
+            branch.bounds = Some((key, key));
+            return true;
+        };
+        let mut key = key;
+        if key == *min {
+            return false;
+        }
+        if key < *min {
+            // The new key becomes the minimum, the old one moves down
+            mem::swap(&mut key, min);
+        }
+        *max = (*max).max(key);
+
+        let (cluster, low) = branch.split(key);
+        let bits = branch.low;
+        let node = branch.clusters[cluster].get_or_insert_with(|| Self::new(bits));
+        if node.min().is_none() {
+            branch.summary.insert(cluster as u32);
+        }
+        node.insert(low)
+    }
+
+    /// Remove `key`, returning whether it was present
+    fn remove(&mut self, key: u32) -> bool {
+        let branch = match self {
+            Self::Leaf(word) => {
+                let present = *word >> key & 1 == 1;
+                *word &= !(1 << key);
+                return present;
+            }
+            Self::Branch(branch) => branch,
+        };
+        let Some((min, max)) = branch.bounds else {
+            return false;
+        };
+        if min == max {
+            if key == min {
+                branch.bounds = None;
+            }
+            return key == min;
+        }
+
+        let mut key = key;
+        let mut min = min;
+        if key == min {
+            // The smallest key of the clusters becomes the minimum and
+            // leaves its cluster
+            let first = branch.summary.min().unwrap_or_else(|| unreachable!());
+            key = branch.key_in(first as usize, Self::min);
+            min = key;
+        }
+        let (cluster, low) = branch.split(key);
+        let Some(node) = branch.clusters[cluster].as_mut() else {
+            return false;
+        };
+        if !node.remove(low) {
+            return false;
+        }
+        if node.min().is_none() {
+            branch.summary.remove(cluster as u32);
+        }
+        let max = if key == max {
+            branch
+                .summary
+                .max()
+                .map_or(min, |last| branch.key_in(last as usize, Self::max))
+        } else {
+            max
+        };
+        branch.bounds = Some((min, max));
+        true
+    }
+
+    /// Smallest key greater than `key`
+    fn next(&self, key: u32) -> Option<u32> {
+        let branch = match self {
+            Self::Leaf(word) => {
+                let above = word.checked_shr(key + 1).unwrap_or(0);
+                return (above != 0).then(|| key + 1 + above.trailing_zeros());
+            }
+            Self::Branch(branch) => branch,
+        };
+        let (min, max) = branch.bounds?;
+        if key < min {
+            return Some(min);
+        }
+        if key >= max {
+            return None;
+        }
+        let (cluster, low) = branch.split(key);
+        let node = branch.clusters[cluster].as_ref();
+        if let Some(next) = node.and_then(|node| node.next(low)) {
+            return Some(branch.join(cluster, next));
+        }
+        let cluster = branch.summary.next(cluster as u32)? as usize;
+        Some(branch.key_in(cluster, Self::min))
+    }
+
+    /// Largest key smaller than `key`
+    fn prev(&self, key: u32) -> Option<u32> {
+        let branch = match self {
+            Self::Leaf(word) => {
+                let below = word & ((1 << key) - 1);
+                return (below != 0).then(|| 63 - below.leading_zeros());
+            }
+            Self::Branch(branch) => branch,
+        };
+        let (min, max) = branch.bounds?;
+        if key > max {
+            return Some(max);
+        }
+        if key <= min {
+            return None;
+        }
+        let (cluster, low) = branch.split(key);
+        let node = branch.clusters[cluster].as_ref();
+        if let Some(prev) = node.and_then(|node| node.prev(low)) {
+            return Some(branch.join(cluster, prev));
+        }
+        // The minimum is in no cluster, it is the fallback
+        Some(match branch.summary.prev(cluster as u32) {
+            Some(cluster) => branch.key_in(cluster as usize, Self::max),
+            None => min,
+        })
+    }
+
+    fn heap_bytes(&self) -> usize {
+        match self {
+            Self::Leaf(_) => 0,
+            Self::Branch(branch) => {
+                let clusters = branch.clusters.iter().flatten().map(Self::heap_bytes);
+                mem::size_of::<Branch>()
+                    + branch.summary.heap_bytes()
+                    + branch.clusters.heap_bytes()
+                    + clusters.sum::<usize>()
+            }
+        }
+    }
+}
+
+/// Ordered set of `u32` keys as a van Emde Boas tree
+///
+/// Insertion, removal, minimum, maximum, predecessor and successor all
+/// take O(log log U), about 5 steps for the `2^32` keys, whatever the
+/// number of keys stored.
+///
+/// The price is memory: an empty set already takes about 1 MiB for the
+/// top level clusters, and the first key of a cluster allocates the next
+/// level, so sparse keys cost kilobytes each. Clusters are never freed.
+///
+/// ```
+/// use kuehree::veb::VebSet;
+///
+/// let mut set = VebSet::new();
+/// set.insert(40);
+/// set.insert(7);
+/// set.insert(3_000_000_000);
+/// assert_eq!(set.successor(41), Some(3_000_000_000));
+/// assert_eq!(set.predecessor(39), Some(7));
+/// set.remove(7);
+/// assert_eq!(set.min(), Some(40));
+/// ```
+pub struct VebSet {
+    root: Node,
+    len: usize,
+}
+
+impl VebSet {
+    /// Construct an empty `Self`
+    ///
+    /// Algorithmic complexity: O(sqrt U) for the top level clusters
+    pub fn new() -> Self {
+        Self {
+            root: Node::new(u32::BITS),
+            len: 0,
+        }
+    }
+
+    /// Number of keys
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Insert `key`, returning whether it was absent
+    ///
+    /// Algorithmic complexity: O(log log U), plus allocating the clusters
+    /// of the key on their first use
+    pub fn insert(&mut self, key: u32) -> bool {
+        let inserted = self.root.insert(key);
+        self.len += usize::from(inserted);
+        inserted
+    }
+
+    /// Remove `key`, returning whether it was present
+    ///
+    /// Algorithmic complexity: O(log log U)
+    pub fn remove(&mut self, key: u32) -> bool {
+        let removed = self.root.remove(key);
+        self.len -= usize::from(removed);
+        removed
+    }
+
+    /// Whether `key` is in the set
+    ///
+    /// Algorithmic complexity: O(log log U)
+    pub fn contains(&self, key: u32) -> bool {
+        self.root.contains(key)
+    }
+
+    /// Smallest key
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn min(&self) -> Option<u32> {
+        self.root.min()
+    }
+
+    /// Largest key
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn max(&self) -> Option<u32> {
+        self.root.max()
+    }
+
+    /// Smallest key at least `key`
+    ///
+    /// Algorithmic complexity: O(log log U)
+    pub fn successor(&self, key: u32) -> Option<u32> {
+        match key.checked_sub(1) {
+            Some(below) => self.root.next(below),
+            None => self.min(),
+        }
+    }
+
+    /// Largest key at most `key`
+    ///
+    /// Algorithmic complexity: O(log log U)
+    pub fn predecessor(&self, key: u32) -> Option<u32> {
+        match key.checked_add(1) {
+            Some(above) => self.root.prev(above),
+            None => self.max(),
+        }
+    }
+
+    /// Keys in increasing order
+    ///
+    /// Algorithmic complexity: O(log log U) per key
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        core::iter::successors(self.min(), |&key| self.root.next(key))
+    }
+}
+
+impl Default for VebSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for VebSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<u32> for VebSet {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for key in iter {
+            set.insert(key);
+        }
+        set
+    }
+}
+
+impl MemoryUsage for VebSet {
+    fn heap_bytes(&self) -> usize {
+        self.root.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sketch::splitmix;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_veb() {
+        let mut set = VebSet::new();
+        let mut model = BTreeSet::new();
+        assert_eq!(
+            (set.min(), set.successor(0), set.predecessor(u32::MAX)),
+            (None, None, None)
+        );
+        for step in 0..3_000u64 {
+            let key = match step % 3 {
+                0 => splitmix(step) as u32,
+                1 => splitmix(step) as u32 % 500,
+                _ => u32::MAX - splitmix(step) as u32 % 70,
+            };
+            if step % 4 == 3 {
+                assert_eq!(set.remove(key), model.remove(&key));
+                let victim = model.iter().nth(step as usize % 7).copied();
+                if let Some(victim) = victim {
+                    assert!(set.remove(victim));
+                    model.remove(&victim);
+                }
+            } else {
+                assert_eq!(set.insert(key), model.insert(key));
+            }
+            assert_eq!(set.len(), model.len());
+            assert_eq!(set.min(), model.first().copied());
+            assert_eq!(set.max(), model.last().copied());
+            for probe in [
+                key,
+                key.wrapping_add(1),
+                key.wrapping_sub(1),
+                0,
+                250,
+                u32::MAX,
+            ] {
+                assert_eq!(set.contains(probe), model.contains(&probe));
+                assert_eq!(set.successor(probe), model.range(probe..).next().copied());
+                assert_eq!(
+                    set.predecessor(probe),
+                    model.range(..=probe).next_back().copied()
+                );
+            }
+        }
+        assert!(set.iter().eq(model.iter().copied()));
+        assert!(set.heap_bytes() > 1 << 20);
+    }
+}
@@ -0,0 +1,129 @@
+//! JS friendly wrappers, enabled with the `wasm` feature
+//!
+//! Typed arrays are passed in directly and queries take and return plain
+//! numbers. Invalid ranges throw a JS `Error`. Integer sums are
+//! accumulated in `i64` and handed back to JS as `number`, so they are
+//! exact up to 2^53.
+
+use wasm_bindgen::{prelude::wasm_bindgen, JsError};
+
+use crate::{
+    rmq::{Max, Min, SparseTable},
+    IndexableSumQuery, RangeQuery, SumQuery,
+};
+
+/// Sum queries over a `Float64Array`
+#[wasm_bindgen]
+pub struct SumQueryF64 {
+    inner: SumQuery<Vec<f64>>,
+}
+
+#[wasm_bindgen]
+impl SumQueryF64 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[f64]) -> Self {
+        Self {
+            inner: SumQuery::from(data),
+        }
+    }
+
+    /// Query between start range and end range
+    ///
+    /// # Errors
+    ///
+    /// Throws if the range is invalid
+    pub fn query(&self, start: usize, end: usize) -> Result<f64, JsError> {
+        Ok(self.inner.try_query(start, end)?)
+    }
+}
+
+/// Sum queries over an `Int32Array`
+#[wasm_bindgen]
+pub struct SumQueryI32 {
+    inner: SumQuery<Vec<i64>>,
+}
+
+#[wasm_bindgen]
+impl SumQueryI32 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[i32]) -> Self {
+        Self {
+            inner: SumQuery::new(data.iter().map(|&d| i64::from(d))),
+        }
+    }
+
+    /// Query between start range and end range
+    ///
+    /// # Errors
+    ///
+    /// Throws if the range is invalid
+    pub fn query(&self, start: usize, end: usize) -> Result<f64, JsError> {
+        Ok(self.inner.try_query(start, end)? as f64)
+    }
+}
+
+/// Range minimum queries over a `Float64Array`
+#[wasm_bindgen]
+pub struct RangeMinF64 {
+    inner: SparseTable<f64, Min>,
+}
+
+#[wasm_bindgen]
+impl RangeMinF64 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[f64]) -> Self {
+        Self {
+            inner: SparseTable::new(data.iter().copied()),
+        }
+    }
+
+    /// Query between start range and end range
+    ///
+    /// # Errors
+    ///
+    /// Throws if the range is invalid
+    pub fn query(&self, start: usize, end: usize) -> Result<f64, JsError> {
+        Ok(self.inner.try_query(start, end)?)
+    }
+}
+
+/// Range maximum queries over a `Float64Array`
+#[wasm_bindgen]
+pub struct RangeMaxF64 {
+    inner: SparseTable<f64, Max>,
+}
+
+#[wasm_bindgen]
+impl RangeMaxF64 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[f64]) -> Self {
+        Self {
+            inner: SparseTable::new(data.iter().copied()),
+        }
+    }
+
+    /// Query between start range and end range
+    ///
+    /// # Errors
+    ///
+    /// Throws if the range is invalid
+    pub fn query(&self, start: usize, end: usize) -> Result<f64, JsError> {
+        Ok(self.inner.try_query(start, end)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_query() {
+        let data = [1, 3, 4, 8, 6, 1, 4, 2];
+        let floats = data.map(f64::from);
+
+        assert_eq!(SumQueryI32::new(&data).query(3, 6).ok(), Some(19.0));
+        assert_eq!(SumQueryF64::new(&floats).query(3, 6).ok(), Some(19.0));
+        assert_eq!(RangeMinF64::new(&floats).query(3, 6).ok(), Some(1.0));
+        assert_eq!(RangeMaxF64::new(&floats).query(3, 6).ok(), Some(8.0));
+    }
+}
@@ -0,0 +1,225 @@
+//! Wavelet matrix, range queries restricted to a band of values
+
+use core::ops::{Add, RangeInclusive, Sub};
+
+use alloc::{vec, vec::Vec};
+
+use crate::{trace::BuildSpan, MemoryUsage, Zero};
+
+/// One bit of the value ranks, over the elements ordered by the higher bits
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Level<T> {
+    /// `zeros[i]` counts the elements before `i` whose bit is 0
+    zeros: Vec<usize>,
+    /// `sums[i]` sums the elements before `i` whose bit is 0
+    sums: Vec<T>,
+}
+
+/// Wavelet matrix over the ranks of the values, each level augmented with
+/// prefix sums
+///
+/// Level `l` splits the elements, stably, by bit `l` of their rank from
+/// the top, so the elements of a range whose rank is below a bound are
+/// collected from one level to the next in O(log d) for `d` distinct
+/// values. Memory is O(n log d).
+///
+/// ```
+/// use kuehree::wavelet::WaveletMatrix;
+///
+/// let prices = WaveletMatrix::new([12, 250, 40, 7, 99, 40, 310]);
+/// assert_eq!(prices.sum_where_value_in(1, 5, 10..=100), 179);
+/// assert_eq!(prices.count_where_value_in(0, 6, 100..=1_000), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct WaveletMatrix<T> {
+    /// Distinct values in increasing order, ranks index into it
+    values: Vec<T>,
+    levels: Vec<Level<T>>,
+    /// Prefix sums of the data, for bounds above every rank
+    totals: Vec<T>,
+}
+
+impl<T> WaveletMatrix<T>
+where
+    T: Copy + Ord + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    /// Construct `Self`
+    ///
+    /// Algorithmic complexity: O(n log n)
+    pub fn new(data: impl IntoIterator<Item = T>) -> Self {
+        let span = BuildSpan::enter("WaveletMatrix");
+        let data: Vec<T> = data.into_iter().collect();
+        span.record_len(data.len());
+        let mut values = data.clone();
+        values.sort_unstable();
+        values.dedup();
+
+        let mut totals = vec![T::zero()];
+        totals.extend(data.iter().scan(T::zero(), |total, &value| {
+            *total = *total + value;
+            Some(*total)
+        }));
+
+        let bits = values.len().next_power_of_two().trailing_zeros();
+        let mut current: Vec<(usize, T)> = data
+            .into_iter()
+            .map(|value| {
+                (
+                    values
+                        .binary_search(&value)
+                        .unwrap_or_else(|_| unreachable!()),
+                    value,
+                )
+            })
+            .collect();
+        let levels = (0..bits)
+            .rev()
+            .map(|bit| {
+                let mut level = Level {
+                    zeros: vec![0],
+                    sums: vec![T::zero()],
+                };
+                for &(rank, value) in &current {
+                    let (mut zeros, mut sum) = (
+                        level.zeros[level.zeros.len() - 1],
+                        level.sums[level.sums.len() - 1],
+                    );
+                    if rank >> bit & 1 == 0 {
+                        zeros += 1;
+                        sum = sum + value;
+                    }
+                    level.zeros.push(zeros);
+                    level.sums.push(sum);
+                }
+                let (zeros, ones): (Vec<_>, Vec<_>) =
+                    current.iter().partition(|&&(rank, _)| rank >> bit & 1 == 0);
+                current = zeros.into_iter().chain(ones).collect();
+                level
+            })
+            .collect();
+
+        Self {
+            values,
+            levels,
+            totals,
+        }
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.totals.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Count and sum of the elements in `start..end` whose rank is below
+    /// `bound`
+    fn below(&self, mut start: usize, mut end: usize, bound: usize) -> (usize, T) {
+        if bound >> self.levels.len() != 0 {
+            return (end - start, self.totals[end] - self.totals[start]);
+        }
+        let (mut count, mut sum) = (0, T::zero());
+        for (level, bit) in self.levels.iter().zip((0..self.levels.len()).rev()) {
+            let (zeros_start, zeros_end) = (level.zeros[start], level.zeros[end]);
+            if bound >> bit & 1 == 1 {
+                // Elements with a 0 here rank below the bound, continue
+                // with those with a 1
+                count += zeros_end - zeros_start;
+                sum = sum + (level.sums[end] - level.sums[start]);
+                let all_zeros = level.zeros[self.len()];
+                start = all_zeros + start - zeros_start;
+                end = all_zeros + end - zeros_end;
+            } else {
+                start = zeros_start;
+                end = zeros_end;
+            }
+        }
+        (count, sum)
+    }
+
+    /// Count and sum of the elements between start range and end range
+    /// whose value lies in `band`
+    fn band(&self, start: usize, end: usize, band: RangeInclusive<T>) -> (usize, T) {
+        assert!(end >= start && end < self.len());
+        let (lo, hi) = band.into_inner();
+        let lo = self.values.partition_point(|&value| value < lo);
+        let hi = self.values.partition_point(|&value| value <= hi);
+        if hi <= lo {
+            return (0, T::zero());
+        }
+        let (below_count, below_sum) = self.below(start, end + 1, lo);
+        let (count, sum) = self.below(start, end + 1, hi);
+        (count - below_count, sum - below_sum)
+    }
+
+    /// Sum of the elements between start range and end range whose value
+    /// lies in `band`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn sum_where_value_in(&self, start: usize, end: usize, band: RangeInclusive<T>) -> T {
+        self.band(start, end, band).1
+    }
+
+    /// Number of elements between start range and end range whose value
+    /// lies in `band`
+    ///
+    /// Algorithmic complexity: O(log n)
+    pub fn count_where_value_in(&self, start: usize, end: usize, band: RangeInclusive<T>) -> usize {
+        self.band(start, end, band).0
+    }
+}
+
+impl<T> FromIterator<T> for WaveletMatrix<T>
+where
+    T: Copy + Ord + Add<Output = T> + Sub<Output = T> + Zero,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+impl<T> MemoryUsage for WaveletMatrix<T> {
+    fn heap_bytes(&self) -> usize {
+        let levels = self
+            .levels
+            .iter()
+            .map(|level| level.zeros.heap_bytes() + level.sums.heap_bytes())
+            .sum::<usize>();
+        self.values.heap_bytes() + self.levels.heap_bytes() + levels + self.totals.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sketch::splitmix;
+
+    #[test]
+    fn test_sum_where_value_in() {
+        let data: Vec<i64> = (0..40).map(|i| (splitmix(i) % 21) as i64 - 10).collect();
+        let matrix: WaveletMatrix<i64> = data.iter().copied().collect();
+        for start in 0..data.len() {
+            for end in start..data.len() {
+                for (lo, hi) in [(-10, 10), (-3, 4), (5, 5), (2, -2), (-20, -9), (11, 30)] {
+                    let band = data[start..=end]
+                        .iter()
+                        .filter(|&&value| (lo..=hi).contains(&value));
+                    assert_eq!(
+                        matrix.sum_where_value_in(start, end, lo..=hi),
+                        band.clone().sum::<i64>()
+                    );
+                    assert_eq!(
+                        matrix.count_where_value_in(start, end, lo..=hi),
+                        band.count()
+                    );
+                }
+            }
+        }
+
+        let single = WaveletMatrix::new([7u32; 5]);
+        assert_eq!(single.sum_where_value_in(1, 3, 0..=7), 21);
+        assert_eq!(single.sum_where_value_in(1, 3, 8..=9), 0);
+    }
+}
@@ -0,0 +1,157 @@
+//! Sliding window aggregation over a queue, for streaming analytics
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::{rmq::Operation, MemoryUsage};
+
+/// Queue folding its elements with the associative operation `M`
+///
+/// Implemented with two stacks: elements are pushed on the back stack,
+/// which keeps the fold of all its elements, and popped from the front
+/// stack, which keeps the fold of every suffix. When the front runs empty
+/// the back is moved over, so every operation is O(1) amortized. `M` needs
+/// to be associative but not commutative.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SlidingAggregator<T, M> {
+    /// `(element, fold of it and every element behind it)`, the element at
+    /// the front of the queue on top
+    front: Vec<(T, T)>,
+    back: Vec<T>,
+    /// Fold of the back stack
+    back_fold: Option<T>,
+    _phantom: PhantomData<M>,
+}
+
+impl<T, M> Default for SlidingAggregator<T, M> {
+    fn default() -> Self {
+        Self {
+            front: Vec::new(),
+            back: Vec::new(),
+            back_fold: None,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, M> SlidingAggregator<T, M>
+where
+    T: Copy,
+    M: Operation<T>,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.front.len() + self.back.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.front.is_empty() && self.back.is_empty()
+    }
+
+    /// Append `value` to the back of the window
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn push_back(&mut self, value: T) {
+        self.back.push(value);
+        self.back_fold = Some(self.back_fold.map_or(value, |fold| M::combine(fold, value)));
+    }
+
+    /// Remove the element at the front of the window
+    ///
+    /// Algorithmic complexity: O(1) amortized
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.front.is_empty() {
+            let mut fold: Option<T> = None;
+            while let Some(value) = self.back.pop() {
+                let suffix = fold.map_or(value, |fold| M::combine(value, fold));
+                self.front.push((value, suffix));
+                fold = Some(suffix);
+            }
+            self.back_fold = None;
+        }
+        self.front.pop().map(|(value, _)| value)
+    }
+
+    /// Fold of every element in the window, front to back, `None` if the
+    /// window is empty
+    ///
+    /// Algorithmic complexity: O(1)
+    pub fn aggregate(&self) -> Option<T> {
+        match (self.front.last(), self.back_fold) {
+            (Some(&(_, front)), Some(back)) => Some(M::combine(front, back)),
+            (Some(&(_, front)), None) => Some(front),
+            (None, back) => back,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.front.clear();
+        self.back.clear();
+        self.back_fold = None;
+    }
+}
+
+impl<T: Copy, M: Operation<T>> Extend<T> for SlidingAggregator<T, M> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push_back(value);
+        }
+    }
+}
+
+impl<T: Copy, M: Operation<T>> FromIterator<T> for SlidingAggregator<T, M> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut window = Self::new();
+        window.extend(iter);
+        window
+    }
+}
+
+impl<T, M> MemoryUsage for SlidingAggregator<T, M> {
+    fn heap_bytes(&self) -> usize {
+        self.front.heap_bytes() + self.back.heap_bytes()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rmq::Max;
+
+    /// Associative but not commutative
+    struct First;
+
+    impl Operation<u32> for First {
+        fn combine(a: u32, _: u32) -> u32 {
+            a
+        }
+    }
+
+    #[test]
+    fn test_sliding_window() {
+        let data = [1u32, 3, 4, 8, 6, 1, 4, 2, 9, 0];
+        let mut max = SlidingAggregator::<_, Max>::new();
+        let mut first = SlidingAggregator::<_, First>::new();
+        assert_eq!(max.aggregate(), None);
+
+        for (idx, &value) in data.iter().enumerate() {
+            max.push_back(value);
+            first.push_back(value);
+            if idx >= 3 {
+                assert_eq!(max.pop_front(), Some(data[idx - 3]));
+                first.pop_front();
+            }
+            let window = &data[idx.saturating_sub(2)..=idx];
+            assert_eq!(max.aggregate(), window.iter().copied().max());
+            assert_eq!(first.aggregate(), Some(window[0]));
+        }
+
+        let mut window: SlidingAggregator<_, Max> = data.into_iter().collect();
+        assert_eq!(window.len(), 10);
+        window.clear();
+        assert_eq!(window.pop_front(), None);
+    }
+}
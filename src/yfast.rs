@@ -0,0 +1,225 @@
+//! Static y-fast trie, predecessor, successor and range counts over
+//! integer keys in O(log log U)
+
+use std::collections::HashMap;
+
+use crate::{trace::BuildSpan, MemoryUsage};
+
+/// Keys per bucket, the word size as in the textbook construction
+const BUCKET_LEN: usize = 64;
+
+/// Bits of a key
+const BITS: u32 = u64::BITS;
+
+/// Set of `u64` keys answering predecessor, successor and range count
+/// queries in O(log log U) for the universe `U = 2^64`
+///
+/// The sorted keys are cut in buckets of 64, and the first key of every
+/// bucket goes in an x-fast trie: one hash map per prefix length, holding
+/// the first and last bucket below every prefix. A binary search over the
+/// prefix lengths finds the bucket of a key in O(log 64) hash lookups and
+/// a binary search within the bucket finishes. Buckets are positions of a
+/// sorted array, so ranks, and with them range counts, come for free.
+///
+/// Unlike the comparison based trees this costs a constant number of
+/// steps whatever the number of keys, at the price of about one hash map
+/// entry per key.
+///
+/// ```
+/// use kuehree::yfast::YFastTrie;
+///
+/// let ports = YFastTrie::new([443, 22, 8080, 80, 5432]);
+/// assert_eq!(ports.predecessor(1000), Some(443));
+/// assert_eq!(ports.successor(1000), Some(5432));
+/// assert_eq!(ports.count(80, 8080), 4);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct YFastTrie {
+    keys: Vec<u64>,
+    /// `levels[l]` maps the `l` bit prefixes of the bucket representatives
+    /// to the first and last bucket below them
+    levels: Vec<HashMap<u64, (usize, usize)>>,
+}
+
+/// Top `len` bits of `key`
+fn prefix(key: u64, len: u32) -> u64 {
+    key.checked_shr(BITS - len).unwrap_or(0)
+}
+
+impl YFastTrie {
+    /// Construct `Self`, duplicate keys are stored once
+    ///
+    /// Algorithmic complexity: O(n log n)
+    pub fn new(keys: impl IntoIterator<Item = u64>) -> Self {
+        let span = BuildSpan::enter("YFastTrie");
+        let mut keys: Vec<u64> = keys.into_iter().collect();
+        keys.sort_unstable();
+        keys.dedup();
+        span.record_len(keys.len());
+
+        let mut levels = vec![HashMap::new(); BITS as usize + 1];
+        for (bucket, chunk) in keys.chunks(BUCKET_LEN).enumerate() {
+            for (len, level) in (0..=BITS).zip(&mut levels) {
+                level
+                    .entry(prefix(chunk[0], len))
+                    .and_modify(|(_, last)| *last = bucket)
+                    .or_insert((bucket, bucket));
+            }
+        }
+        Self { keys, levels }
+    }
+
+    /// Number of keys
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Last bucket whose first key is at most `key`
+    fn bucket(&self, key: u64) -> Option<usize> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        // Prefixes of `key` present in the trie are exactly the shortest
+        // ones, binary search the longest
+        let (mut lo, mut hi) = (0, BITS);
+        while lo < hi {
+            let mid = (lo + hi).div_ceil(2);
+            if self.levels[mid as usize].contains_key(&prefix(key, mid)) {
+                lo = mid;
+            } else {
+                hi = mid - 1;
+            }
+        }
+        let (first, last) = self.levels[lo as usize][&prefix(key, lo)];
+        if lo == BITS {
+            return Some(first);
+        }
+        // No representative continues the prefix with the next bit of
+        // `key`, so they all lie on one side of it
+        if key >> (BITS - lo - 1) & 1 == 1 {
+            Some(last)
+        } else {
+            first.checked_sub(1)
+        }
+    }
+
+    /// Number of keys at most `key`
+    ///
+    /// Algorithmic complexity: O(log log U)
+    pub fn rank(&self, key: u64) -> usize {
+        self.bucket(key).map_or(0, |bucket| {
+            let start = bucket * BUCKET_LEN;
+            let end = self.len().min(start + BUCKET_LEN);
+            start + self.keys[start..end].partition_point(|&stored| stored <= key)
+        })
+    }
+
+    /// Whether `key` is in the set
+    ///
+    /// Algorithmic complexity: O(log log U)
+    pub fn contains(&self, key: u64) -> bool {
+        self.predecessor(key) == Some(key)
+    }
+
+    /// Largest key at most `key`
+    ///
+    /// Algorithmic complexity: O(log log U)
+    pub fn predecessor(&self, key: u64) -> Option<u64> {
+        self.rank(key).checked_sub(1).map(|rank| self.keys[rank])
+    }
+
+    /// Smallest key at least `key`
+    ///
+    /// Algorithmic complexity: O(log log U)
+    pub fn successor(&self, key: u64) -> Option<u64> {
+        let below = key.checked_sub(1).map_or(0, |key| self.rank(key));
+        self.keys.get(below).copied()
+    }
+
+    /// Number of keys between `start` and `end`, both inclusive
+    ///
+    /// Algorithmic complexity: O(log log U)
+    pub fn count(&self, start: u64, end: u64) -> usize {
+        if start > end {
+            return 0;
+        }
+        let below = start.checked_sub(1).map_or(0, |start| self.rank(start));
+        self.rank(end) - below
+    }
+
+    /// Keys in increasing order
+    pub fn keys(&self) -> &[u64] {
+        &self.keys
+    }
+}
+
+impl FromIterator<u64> for YFastTrie {
+    fn from_iter<I: IntoIterator<Item = u64>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+/// Counts the hash map entries, not their spare capacity
+impl MemoryUsage for YFastTrie {
+    fn heap_bytes(&self) -> usize {
+        let entries = self.levels.iter().map(HashMap::len).sum::<usize>();
+        self.keys.heap_bytes()
+            + self.levels.heap_bytes()
+            + entries * core::mem::size_of::<(u64, (usize, usize))>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::sketch::splitmix;
+
+    #[test]
+    fn test_yfast() {
+        let keys: Vec<u64> = (0..2_000)
+            .map(|i| match i % 3 {
+                0 => splitmix(i),
+                1 => splitmix(i) % 10_000,
+                _ => u64::MAX - splitmix(i) % 100,
+            })
+            .collect();
+        let trie: YFastTrie = keys.iter().copied().collect();
+        let mut sorted = keys.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(trie.keys(), sorted);
+
+        let probes = keys
+            .iter()
+            .flat_map(|&key| [key, key.wrapping_add(1), key.wrapping_sub(1)])
+            .chain([0, 1, 5_000, u64::MAX, u64::MAX / 2]);
+        for probe in probes {
+            let rank = sorted.partition_point(|&key| key <= probe);
+            assert_eq!(trie.rank(probe), rank);
+            assert_eq!(
+                trie.predecessor(probe),
+                rank.checked_sub(1).map(|r| sorted[r])
+            );
+            let below = sorted.partition_point(|&key| key < probe);
+            assert_eq!(trie.successor(probe), sorted.get(below).copied());
+            assert_eq!(trie.contains(probe), sorted.binary_search(&probe).is_ok());
+        }
+        assert_eq!(trie.count(0, u64::MAX), sorted.len());
+        assert_eq!(
+            trie.count(100, 9_999),
+            sorted
+                .iter()
+                .filter(|&&key| (100..=9_999).contains(&key))
+                .count()
+        );
+        assert_eq!(trie.count(9, 3), 0);
+
+        let empty = YFastTrie::new([]);
+        assert_eq!((empty.predecessor(7), empty.successor(7)), (None, None));
+        assert_eq!(empty.count(0, u64::MAX), 0);
+    }
+}